@@ -0,0 +1,464 @@
+// Org chart export: Graphviz DOT and Mermaid flowchart text for a department
+// tree, with one box per employee and an arrow from each manager to their reports.
+
+use crate::money::Money;
+use crate::{Department, Employee};
+use std::fmt;
+
+// ----------------------------
+// Options
+// ----------------------------
+
+// The `LabelField` enum lists what can be shown in an employee's box, one line each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelField {
+    Name,
+    Id,
+    Role,
+    // The range the salary falls in, e.g. "$75k-$100k", rather than the exact figure.
+    SalaryBand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChartOptions {
+    pub fields: Vec<LabelField>,
+    // Width of a salary band in major units, e.g. 25000 for $25k bands.
+    pub band_width: i64,
+    // Draw each department as a box around its employees.
+    pub clusters: bool,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        ChartOptions {
+            fields: vec![LabelField::Name, LabelField::Role],
+            band_width: 25000,
+            clusters: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChartError {
+    // The band width is zero or negative, or too wide to count in minor units.
+    InvalidBandWidth(i64),
+}
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartError::InvalidBandWidth(width) => {
+                write!(f, "{} is not a usable salary band width", width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChartError {}
+
+// ----------------------------
+// Labels and Ordering
+// ----------------------------
+
+/// The salary band as "$75k-$100k", or as "$750-$1000" when the width is not
+/// a whole number of thousands.
+fn salary_band(salary: &Money, width: i64) -> Result<String, ChartError> {
+    let invalid = ChartError::InvalidBandWidth(width);
+    if width <= 0 {
+        return Err(invalid);
+    }
+    let per_major = salary.currency().minor_per_major();
+    let step = per_major.checked_mul(width).ok_or(invalid.clone())?;
+    let low = salary.minor().div_euclid(step) * width;
+    let high = low.checked_add(width).ok_or(invalid)?;
+    let symbol = salary.currency().symbol();
+    Ok(if width % 1000 == 0 {
+        format!("{}{}k-{}{}k", symbol, low / 1000, symbol, high / 1000)
+    } else {
+        format!("{}{}-{}{}", symbol, low, symbol, high)
+    })
+}
+
+fn label_lines(emp: &Employee, options: &ChartOptions) -> Result<Vec<String>, ChartError> {
+    options
+        .fields
+        .iter()
+        .map(|field| {
+            Ok(match field {
+                LabelField::Name => emp.name.clone(),
+                LabelField::Id => format!("#{}", emp.id),
+                LabelField::Role => emp.role.to_string(),
+                LabelField::SalaryBand => salary_band(&emp.salary, options.band_width)?,
+            })
+        })
+        .collect()
+}
+
+fn node_id(emp: &Employee) -> String {
+    format!("e{}", emp.id)
+}
+
+/// An identifier built from the department's position among its sorted
+/// siblings at each level, e.g. `d_1_0`, so it is unique even when names
+/// repeat and does not change when the input is reordered.
+fn cluster_id(parent: Option<&str>, position: usize) -> String {
+    match parent {
+        None => String::from("d"),
+        Some(parent) => format!("{}_{}", parent, position),
+    }
+}
+
+fn sorted_employees(dept: &Department) -> Vec<&Employee> {
+    let mut employees: Vec<&Employee> = dept.employees.iter().collect();
+    employees.sort_by_key(|e| e.id);
+    employees
+}
+
+/// Sub-departments by name, with same-named ones kept in input order.
+fn sorted_sub_departments(dept: &Department) -> Vec<&Department> {
+    let mut subs: Vec<(usize, &Department)> = dept.sub_departments.iter().enumerate().collect();
+    subs.sort_by(|(i, a), (j, b)| a.name.cmp(&b.name).then(i.cmp(j)));
+    subs.into_iter().map(|(_, d)| d).collect()
+}
+
+/// Manager-to-report pairs where the manager is in the chart, sorted.
+fn reporting_edges(dept: &Department) -> Vec<(i32, i32)> {
+    let employees = dept.all_employees();
+    let mut edges: Vec<(i32, i32)> = employees
+        .iter()
+        .filter_map(|e| {
+            let manager = e.manager_id?;
+            employees
+                .iter()
+                .any(|m| m.id == manager)
+                .then_some((manager, e.id))
+        })
+        .collect();
+    edges.sort();
+    edges
+}
+
+// ----------------------------
+// Graphviz DOT
+// ----------------------------
+
+/// Escapes quotes and backslashes, and turns line breaks into DOT's `\n`.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n")
+}
+
+fn dot_node(
+    out: &mut String,
+    emp: &Employee,
+    options: &ChartOptions,
+    indent: &str,
+) -> Result<(), ChartError> {
+    let lines: Vec<String> = label_lines(emp, options)?
+        .iter()
+        .map(|l| dot_escape(l))
+        .collect();
+    out.push_str(&format!(
+        "{}{} [label=\"{}\"];\n",
+        indent,
+        node_id(emp),
+        lines.join("\\n")
+    ));
+    Ok(())
+}
+
+fn dot_cluster(
+    out: &mut String,
+    dept: &Department,
+    options: &ChartOptions,
+    id: &str,
+    depth: usize,
+) -> Result<(), ChartError> {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!("{}subgraph cluster_{} {{\n", indent, id));
+    out.push_str(&format!(
+        "{}  label=\"{}\";\n",
+        indent,
+        dot_escape(&dept.name)
+    ));
+    for emp in sorted_employees(dept) {
+        dot_node(out, emp, options, &format!("{}  ", indent))?;
+    }
+    for (position, sub) in sorted_sub_departments(dept).into_iter().enumerate() {
+        let sub_id = cluster_id(Some(id), position);
+        dot_cluster(out, sub, options, &sub_id, depth + 1)?;
+    }
+    out.push_str(&format!("{}}}\n", indent));
+    Ok(())
+}
+
+// ----------------------------
+// Mermaid
+// ----------------------------
+
+/// Replaces characters Mermaid would read as syntax with its `#code;`
+/// entities. `#` itself is escaped, so the entities are not mistaken for text.
+/// Line breaks become `<br/>`, as between label lines.
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.replace("\r\n", "\n").chars() {
+        match c {
+            '\n' | '\r' => escaped.push_str("<br/>"),
+            '"' => escaped.push_str("#quot;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '&' => escaped.push_str("#amp;"),
+            '#' => escaped.push_str("#35;"),
+            ';' => escaped.push_str("#59;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+fn mermaid_node(
+    out: &mut String,
+    emp: &Employee,
+    options: &ChartOptions,
+    indent: &str,
+) -> Result<(), ChartError> {
+    let lines: Vec<String> = label_lines(emp, options)?
+        .iter()
+        .map(|l| mermaid_escape(l))
+        .collect();
+    out.push_str(&format!(
+        "{}{}[\"{}\"]\n",
+        indent,
+        node_id(emp),
+        lines.join("<br/>")
+    ));
+    Ok(())
+}
+
+fn mermaid_subgraph(
+    out: &mut String,
+    dept: &Department,
+    options: &ChartOptions,
+    id: &str,
+    depth: usize,
+) -> Result<(), ChartError> {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!(
+        "{}subgraph {}[\"{}\"]\n",
+        indent,
+        id,
+        mermaid_escape(&dept.name)
+    ));
+    for emp in sorted_employees(dept) {
+        mermaid_node(out, emp, options, &format!("{}  ", indent))?;
+    }
+    for (position, sub) in sorted_sub_departments(dept).into_iter().enumerate() {
+        let sub_id = cluster_id(Some(id), position);
+        mermaid_subgraph(out, sub, options, &sub_id, depth + 1)?;
+    }
+    out.push_str(&format!("{}end\n", indent));
+    Ok(())
+}
+
+// ----------------------------
+// Export
+// ----------------------------
+
+impl Department {
+    /// The department tree as a Graphviz `digraph`. Render it with
+    /// `dot -Tsvg org.dot -o org.svg`. Fails if salary bands are shown and
+    /// `band_width` cannot be used.
+    pub fn to_dot(&self, options: &ChartOptions) -> Result<String, ChartError> {
+        let mut out = String::from("digraph org {\n  rankdir=TB;\n  node [shape=box];\n");
+        if options.clusters {
+            dot_cluster(&mut out, self, options, &cluster_id(None, 0), 1)?;
+        } else {
+            let mut employees = self.all_employees();
+            employees.sort_by_key(|e| e.id);
+            for emp in employees {
+                dot_node(&mut out, emp, options, "  ")?;
+            }
+        }
+        for (manager, report) in reporting_edges(self) {
+            out.push_str(&format!("  e{} -> e{};\n", manager, report));
+        }
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    /// The department tree as a Mermaid `flowchart`, which renders inside
+    /// Markdown on most code hosts.
+    pub fn to_mermaid(&self, options: &ChartOptions) -> Result<String, ChartError> {
+        let mut out = String::from("flowchart TD\n");
+        if options.clusters {
+            mermaid_subgraph(&mut out, self, options, &cluster_id(None, 0), 1)?;
+        } else {
+            let mut employees = self.all_employees();
+            employees.sort_by_key(|e| e.id);
+            for emp in employees {
+                mermaid_node(&mut out, emp, options, "  ")?;
+            }
+        }
+        for (manager, report) in reporting_edges(self) {
+            out.push_str(&format!("  e{} --> e{}\n", manager, report));
+        }
+        Ok(out)
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::employee;
+    use crate::Role;
+
+    fn team() -> Department {
+        Department {
+            name: String::from("R&D"),
+            employees: vec![employee(1, Role::Manager, 150000).named("Ada \"The Boss\"")],
+            sub_departments: vec![
+                Department {
+                    name: String::from("Web"),
+                    employees: vec![
+                        employee(3, Role::Developer, 99999)
+                            .named("Cy")
+                            .reporting_to(1),
+                        employee(2, Role::Designer, 75000)
+                            .named("Bo")
+                            .reporting_to(1),
+                    ],
+                    sub_departments: Vec::new(),
+                },
+                // A manager outside the chart gets no edge.
+                Department {
+                    name: String::from("Apps"),
+                    employees: vec![employee(4, Role::Developer, 80000)
+                        .named("Di")
+                        .reporting_to(99)],
+                    sub_departments: Vec::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_dot_export() {
+        let dot = team().to_dot(&ChartOptions::default()).unwrap();
+        let expected = "\
+digraph org {
+  rankdir=TB;
+  node [shape=box];
+  subgraph cluster_d {
+    label=\"R&D\";
+    e1 [label=\"Ada \\\"The Boss\\\"\\nManager\"];
+    subgraph cluster_d_0 {
+      label=\"Apps\";
+      e4 [label=\"Di\\nDeveloper\"];
+    }
+    subgraph cluster_d_1 {
+      label=\"Web\";
+      e2 [label=\"Bo\\nDesigner\"];
+      e3 [label=\"Cy\\nDeveloper\"];
+    }
+  }
+  e1 -> e2;
+  e1 -> e3;
+}
+";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn test_mermaid_export_with_salary_bands() {
+        let options = ChartOptions {
+            fields: vec![LabelField::Id, LabelField::SalaryBand],
+            clusters: false,
+            ..ChartOptions::default()
+        };
+        let mermaid = team().to_mermaid(&options).unwrap();
+        let expected = "\
+flowchart TD
+  e1[\"#35;1<br/>$150k-$175k\"]
+  e2[\"#35;2<br/>$75k-$100k\"]
+  e3[\"#35;3<br/>$75k-$100k\"]
+  e4[\"#35;4<br/>$75k-$100k\"]
+  e1 --> e2
+  e1 --> e3
+";
+        assert_eq!(mermaid, expected);
+        let mermaid = team().to_mermaid(&ChartOptions::default()).unwrap();
+        assert!(mermaid.contains("e1[\"Ada #quot;The Boss#quot;<br/>Manager\"]"));
+        assert!(mermaid.contains("subgraph d[\"R#amp;D\"]"));
+        assert_eq!(
+            mermaid_escape("<b>#1; \"x\""),
+            "#lt;b#gt;#35;1#59; #quot;x#quot;"
+        );
+    }
+
+    #[test]
+    fn test_line_breaks_and_bad_band_widths() {
+        let mut dept = team();
+        dept.employees[0].name = String::from("Ada\nLovelace");
+        let dot = dept.to_dot(&ChartOptions::default()).unwrap();
+        assert!(dot.contains("e1 [label=\"Ada\\nLovelace\\nManager\"];"));
+        let mermaid = dept.to_mermaid(&ChartOptions::default()).unwrap();
+        assert!(mermaid.contains("e1[\"Ada<br/>Lovelace<br/>Manager\"]"));
+
+        for band_width in [0, -5, i64::MAX] {
+            let options = ChartOptions {
+                fields: vec![LabelField::SalaryBand],
+                band_width,
+                ..ChartOptions::default()
+            };
+            assert_eq!(
+                team().to_dot(&options),
+                Err(ChartError::InvalidBandWidth(band_width))
+            );
+        }
+        // Without a salary band line, the width is never used.
+        let options = ChartOptions {
+            band_width: 0,
+            ..ChartOptions::default()
+        };
+        assert!(team().to_mermaid(&options).is_ok());
+    }
+
+    #[test]
+    fn test_output_is_stable_under_reordering() {
+        let mut shuffled = team();
+        shuffled.sub_departments.reverse();
+        shuffled.sub_departments[0].employees.reverse();
+        let options = ChartOptions::default();
+        assert_eq!(shuffled.to_dot(&options), team().to_dot(&options));
+        assert_eq!(shuffled.to_mermaid(&options), team().to_mermaid(&options));
+    }
+
+    #[test]
+    fn test_clusters_with_alike_names_stay_apart() {
+        let mut dept = team();
+        let mut twin = dept.sub_departments[0].clone();
+        twin.employees = vec![employee(5, Role::Developer, 80000)
+            .named("Ed")
+            .reporting_to(1)];
+        dept.sub_departments.push(twin);
+        dept.sub_departments[1].name = String::from("R D");
+        dept.sub_departments.push(Department {
+            name: String::from("R&D"),
+            employees: Vec::new(),
+            sub_departments: Vec::new(),
+        });
+        let dot = dept.to_dot(&ChartOptions::default()).unwrap();
+        // "R D" and "R&D", then two "Web" clusters, each with its own id.
+        for id in ["cluster_d_0", "cluster_d_1", "cluster_d_2", "cluster_d_3"] {
+            assert_eq!(dot.matches(&format!("subgraph {} {{", id)).count(), 1);
+        }
+        let first_web = dot.find("e2 [").unwrap();
+        assert!(first_web < dot.find("e5 [").unwrap());
+    }
+}
@@ -0,0 +1,485 @@
+// Effective-dated compensation: each employee's salary, role and level as a
+// list of changes that take effect on given dates. Changes can be back-dated
+// or corrected, and payroll for past periods can be worked out again from them.
+
+use crate::money::{Money, MoneyError};
+use crate::payroll::{PayAdjustment, PayPeriod, Payroll, PayrollError};
+use crate::{Department, Employee, Role};
+use chrono::{Duration, NaiveDate};
+use std::collections::HashMap;
+use std::fmt;
+
+// ----------------------------
+// Types
+// ----------------------------
+
+// What an employee is paid and does from `effective` until the next change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compensation {
+    pub effective: NaiveDate,
+    pub salary: Money,
+    pub role: Role,
+    pub level: u8,
+    // Why it changed, e.g. "Promotion".
+    pub reason: String,
+}
+
+// A record of one entry being replaced by `correct`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+    pub employee_id: i32,
+    pub before: Compensation,
+    pub after: Compensation,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    // Each employee's entries, oldest first, at most one per date.
+    entries: HashMap<i32, Vec<Compensation>>,
+    // Every correction made, oldest first.
+    pub corrections: Vec<Correction>,
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug)]
+pub enum HistoryError {
+    UnknownEmployee(i32),
+    // There is already an entry on that date; use `correct` to change it.
+    AlreadyRecorded {
+        employee_id: i32,
+        effective: NaiveDate,
+    },
+    // There is no entry on that date to correct.
+    NoEntry {
+        employee_id: i32,
+        effective: NaiveDate,
+    },
+    Money(MoneyError),
+    Payroll(PayrollError),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::UnknownEmployee(id) => write!(f, "no history for employee {}", id),
+            HistoryError::AlreadyRecorded {
+                employee_id,
+                effective,
+            } => write!(
+                f,
+                "employee {} already has a change effective {}",
+                employee_id, effective
+            ),
+            HistoryError::NoEntry {
+                employee_id,
+                effective,
+            } => write!(
+                f,
+                "employee {} has no change effective {} to correct",
+                employee_id, effective
+            ),
+            HistoryError::Money(e) => write!(f, "{}", e),
+            HistoryError::Payroll(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<MoneyError> for HistoryError {
+    fn from(e: MoneyError) -> Self {
+        HistoryError::Money(e)
+    }
+}
+
+impl From<PayrollError> for HistoryError {
+    fn from(e: PayrollError) -> Self {
+        HistoryError::Payroll(e)
+    }
+}
+
+// ----------------------------
+// Recording Changes
+// ----------------------------
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// Starts an employee's history with their current salary, role and level.
+    pub fn start(&mut self, emp: &Employee, effective: NaiveDate) -> Result<(), HistoryError> {
+        self.record(
+            emp.id,
+            Compensation {
+                effective,
+                salary: emp.salary,
+                role: emp.role.clone(),
+                level: emp.level,
+                reason: String::from("Start of history"),
+            },
+        )
+    }
+
+    /// Adds a change. It may take effect in the past; payroll already run for
+    /// the affected periods is fixed by `recalculate_payroll`.
+    pub fn record(&mut self, employee_id: i32, change: Compensation) -> Result<(), HistoryError> {
+        let entries = self.entries.entry(employee_id).or_default();
+        match entries.binary_search_by_key(&change.effective, |c| c.effective) {
+            Ok(_) => Err(HistoryError::AlreadyRecorded {
+                employee_id,
+                effective: change.effective,
+            }),
+            Err(index) => {
+                entries.insert(index, change);
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the entry effective on the same date as `change`, keeping the
+    /// old one in `corrections`, and returns the old entry.
+    pub fn correct(
+        &mut self,
+        employee_id: i32,
+        change: Compensation,
+    ) -> Result<Compensation, HistoryError> {
+        let entries = self
+            .entries
+            .get_mut(&employee_id)
+            .ok_or(HistoryError::UnknownEmployee(employee_id))?;
+        let index = entries
+            .binary_search_by_key(&change.effective, |c| c.effective)
+            .map_err(|_| HistoryError::NoEntry {
+                employee_id,
+                effective: change.effective,
+            })?;
+        let before = std::mem::replace(&mut entries[index], change.clone());
+        self.corrections.push(Correction {
+            employee_id,
+            before: before.clone(),
+            after: change,
+        });
+        Ok(before)
+    }
+}
+
+// ----------------------------
+// Queries
+// ----------------------------
+
+impl History {
+    /// Every change for an employee, oldest first.
+    pub fn entries(&self, employee_id: i32) -> &[Compensation] {
+        self.entries.get(&employee_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The entry in force on `date`, or `None` before the history starts.
+    pub fn as_of(&self, employee_id: i32, date: NaiveDate) -> Option<&Compensation> {
+        self.entries(employee_id)
+            .iter()
+            .rev()
+            .find(|c| c.effective <= date)
+    }
+
+    /// What the employee's annual salary was on `date`.
+    pub fn salary_on(&self, employee_id: i32, date: NaiveDate) -> Option<Money> {
+        self.as_of(employee_id, date).map(|c| c.salary)
+    }
+
+    /// The employee as they were on `date`.
+    pub fn employee_on(&self, emp: &Employee, date: NaiveDate) -> Option<Employee> {
+        let current = self.as_of(emp.id, date)?;
+        Some(Employee {
+            salary: current.salary,
+            role: current.role.clone(),
+            level: current.level,
+            ..emp.clone()
+        })
+    }
+
+    /// The employee as payroll should see them for `period`: role and level as
+    /// of its last day, and an annual salary weighted by the days each rate
+    /// was in force. Days before the history starts count as unpaid.
+    pub fn employee_for_period(
+        &self,
+        emp: &Employee,
+        period: &PayPeriod,
+    ) -> Result<Employee, HistoryError> {
+        let last = self
+            .as_of(emp.id, period.end)
+            .ok_or(HistoryError::UnknownEmployee(emp.id))?;
+        let period_days = (period.end - period.start).num_days() + 1;
+        let mut salary = Money::zero(last.salary.currency());
+        let entries = self.entries(emp.id);
+        for (i, entry) in entries.iter().enumerate() {
+            let from = entry.effective.max(period.start);
+            let to = entries
+                .get(i + 1)
+                .map_or(period.end, |next| next.effective - Duration::days(1))
+                .min(period.end);
+            if from > to {
+                continue;
+            }
+            let days = (to - from).num_days() + 1;
+            salary = salary.checked_add(entry.salary.mul_ratio(days, period_days)?)?;
+        }
+        Ok(Employee {
+            salary,
+            role: last.role.clone(),
+            level: last.level,
+            ..emp.clone()
+        })
+    }
+
+    /// Brings every employee in the tree up to date with their history as of
+    /// `date`. Employees without a history are left alone.
+    pub fn apply(&self, dept: &mut Department, date: NaiveDate) {
+        for emp in &mut dept.employees {
+            if let Some(updated) = self.employee_on(emp, date) {
+                *emp = updated;
+            }
+        }
+        for sub in &mut dept.sub_departments {
+            self.apply(sub, date);
+        }
+    }
+
+    /// Works out every period of `year` already paid to `emp` again from the
+    /// history, and returns the adjustments for periods whose pay changed.
+    /// Periods that end before the history starts are left as paid. If any
+    /// period fails, none of them are changed.
+    pub fn recalculate_payroll(
+        &self,
+        payroll: &mut Payroll,
+        emp: &Employee,
+        year: i32,
+    ) -> Result<Vec<PayAdjustment>, HistoryError> {
+        if self.entries(emp.id).is_empty() {
+            return Err(HistoryError::UnknownEmployee(emp.id));
+        }
+        // Each correction moves the year-to-date totals the next one builds
+        // on, so they are worked out on a copy that replaces the original
+        // only once every period has succeeded.
+        let mut updated = payroll.clone();
+        let mut adjustments = Vec::new();
+        for period in payroll.config().periods(year) {
+            if payroll.payslip(emp.id, &period).is_none()
+                || self.as_of(emp.id, period.end).is_none()
+            {
+                continue;
+            }
+            let as_paid = self.employee_for_period(emp, &period)?;
+            let adjustment = updated.recalculate(&as_paid, &period)?;
+            if !adjustment.is_zero() {
+                adjustments.push(adjustment);
+            }
+        }
+        *payroll = updated;
+        Ok(adjustments)
+    }
+}
+
+impl fmt::Display for Compensation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} L{} at {} ({})",
+            self.effective, self.role, self.level, self.salary, self.reason
+        )
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Currency;
+    use crate::payroll::PayrollConfig;
+    use crate::test_support::{date, employee, usd};
+
+    fn quinn() -> Employee {
+        employee(7, Role::Developer, 120000).named("Quinn")
+    }
+
+    fn change(effective: &str, salary: i64, role: Role, level: u8) -> Compensation {
+        Compensation {
+            effective: date(effective),
+            salary: usd(salary),
+            role,
+            level,
+            reason: String::from("Review"),
+        }
+    }
+
+    #[test]
+    fn test_salary_on_date() {
+        let mut history = History::new();
+        history.start(&quinn(), date("2024-01-01")).unwrap();
+        history
+            .record(7, change("2025-03-01", 132000, Role::Developer, 2))
+            .unwrap();
+        // Back-dated changes slot into place.
+        history
+            .record(7, change("2024-07-01", 126000, Role::Developer, 1))
+            .unwrap();
+
+        assert_eq!(history.salary_on(7, date("2023-12-31")), None);
+        assert_eq!(history.salary_on(7, date("2024-06-30")), Some(usd(120000)));
+        assert_eq!(history.salary_on(7, date("2024-07-01")), Some(usd(126000)));
+        assert_eq!(history.salary_on(7, date("2026-01-01")), Some(usd(132000)));
+        assert_eq!(history.entries(7).len(), 3);
+        assert!(matches!(
+            history.record(7, change("2024-07-01", 1, Role::Developer, 1)),
+            Err(HistoryError::AlreadyRecorded { employee_id: 7, .. })
+        ));
+
+        let mut team = Department {
+            name: String::from("Team"),
+            employees: vec![quinn()],
+            sub_departments: Vec::new(),
+        };
+        history.apply(&mut team, date("2025-06-01"));
+        assert_eq!(team.employees[0].salary, usd(132000));
+        assert_eq!(team.employees[0].level, 2);
+    }
+
+    #[test]
+    fn test_corrections_are_logged() {
+        let mut history = History::new();
+        history.start(&quinn(), date("2025-01-01")).unwrap();
+        history
+            .record(7, change("2025-03-01", 130000, Role::Developer, 2))
+            .unwrap();
+        let before = history
+            .correct(7, change("2025-03-01", 135000, Role::Manager, 1))
+            .unwrap();
+        assert_eq!(before.salary, usd(130000));
+        assert_eq!(history.salary_on(7, date("2025-03-01")), Some(usd(135000)));
+        assert_eq!(
+            history.as_of(7, date("2025-03-01")).unwrap().role,
+            Role::Manager
+        );
+        assert_eq!(history.corrections.len(), 1);
+        assert_eq!(history.corrections[0].before, before);
+
+        assert!(matches!(
+            history.correct(7, change("2025-02-01", 1, Role::Developer, 1)),
+            Err(HistoryError::NoEntry { .. })
+        ));
+        assert!(matches!(
+            history.correct(8, change("2025-03-01", 1, Role::Developer, 1)),
+            Err(HistoryError::UnknownEmployee(8))
+        ));
+    }
+
+    #[test]
+    fn test_back_dated_raise_recalculates_payroll() {
+        let config = PayrollConfig::from_toml(
+            r#"
+            currency = "USD"
+            frequency = "monthly"
+            first_period_start = "2025-01-01"
+
+            [[tax_brackets]]
+            from = "0"
+            rate = "10"
+            "#,
+        )
+        .unwrap();
+        let mut payroll = Payroll::new(config);
+        let emp = quinn();
+        let periods = payroll.config().periods(2025);
+        for period in &periods[..4] {
+            payroll.pay(&emp, period).unwrap();
+        }
+
+        let mut history = History::new();
+        history.start(&emp, date("2025-01-01")).unwrap();
+        // A raise to $132,000 from 16 March: April changes fully and March for
+        // 16 of its 31 days.
+        history
+            .record(7, change("2025-03-16", 132000, Role::Developer, 2))
+            .unwrap();
+        let march = history.employee_for_period(&emp, &periods[2]).unwrap();
+        assert_eq!(march.salary, Money::new(12619355, Currency::Usd));
+        assert_eq!(march.level, 2);
+
+        let adjustments = history
+            .recalculate_payroll(&mut payroll, &emp, 2025)
+            .unwrap();
+        let gross: Vec<Money> = adjustments.iter().map(|a| a.gross).collect();
+        assert_eq!(gross, vec![Money::new(51613, Currency::Usd), usd(1000)]);
+        assert_eq!(adjustments[1].net, usd(900));
+        let totals = payroll.year_to_date(7, 2025).unwrap();
+        assert_eq!(
+            totals.gross,
+            Money::new(4000000 + 51613 + 100000, Currency::Usd)
+        );
+
+        // Running it again finds nothing more to change.
+        let again = history
+            .recalculate_payroll(&mut payroll, &emp, 2025)
+            .unwrap();
+        assert!(again.is_empty());
+        assert!(matches!(
+            payroll.recalculate(&emp, &periods[5]),
+            Err(PayrollError::NotPaid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_recalculation_skips_early_periods_and_fails_as_a_whole() {
+        let config = PayrollConfig::from_toml(
+            r#"
+            currency = "USD"
+            frequency = "monthly"
+            first_period_start = "2025-01-01"
+
+            [[tax_brackets]]
+            from = "0"
+            rate = "10"
+            "#,
+        )
+        .unwrap();
+        let mut payroll = Payroll::new(config);
+        let emp = quinn();
+        let periods = payroll.config().periods(2025);
+        for period in &periods[..4] {
+            payroll.pay(&emp, period).unwrap();
+        }
+
+        // The history only starts in March, so January and February stay as
+        // they were paid.
+        let mut history = History::new();
+        history.start(&emp, date("2025-03-01")).unwrap();
+        history
+            .record(7, change("2025-03-16", 132000, Role::Developer, 2))
+            .unwrap();
+        let adjustments = history
+            .recalculate_payroll(&mut payroll.clone(), &emp, 2025)
+            .unwrap();
+        let numbers: Vec<u32> = adjustments
+            .iter()
+            .map(|a| a.corrected.period.number)
+            .collect();
+        assert_eq!(numbers, vec![3, 4]);
+
+        // April cannot be worked out, so March is not changed either.
+        let mut huge = change("2025-04-01", 0, Role::Developer, 2);
+        huge.salary = Money::new(i64::MAX, Currency::Usd);
+        history.record(7, huge).unwrap();
+        let before = payroll.year_to_date(7, 2025).copied();
+        assert!(history
+            .recalculate_payroll(&mut payroll, &emp, 2025)
+            .is_err());
+        assert_eq!(payroll.year_to_date(7, 2025).copied(), before);
+        assert_eq!(payroll.payslip(7, &periods[2]).unwrap().gross, usd(10000));
+    }
+}
@@ -0,0 +1,843 @@
+// Leave tracking: leave types, accrual rules by role and tenure read from a
+// TOML file, requests approved by the employee's manager, balances with
+// year-end carry-over caps, and a team absence calendar.
+
+use crate::{Department, Employee, Role};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// Leave is counted in minutes so monthly accrual is exact: a day is 480
+// minutes and a twelfth of a day's yearly allowance is 40.
+pub const MINUTES_PER_DAY: i64 = 480;
+
+fn days(minutes: i64) -> String {
+    format!("{:.2} days", minutes as f64 / MINUTES_PER_DAY as f64)
+}
+
+// ----------------------------
+// Policy
+// ----------------------------
+
+// The `LeaveType` enum lists the kinds of leave an employee can take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaveType {
+    Vacation,
+    Sick,
+    Parental,
+}
+
+impl fmt::Display for LeaveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LeaveType::Vacation => "vacation",
+            LeaveType::Sick => "sick",
+            LeaveType::Parental => "parental",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for LeaveType {
+    type Err = LeaveError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "vacation" => Ok(LeaveType::Vacation),
+            "sick" => Ok(LeaveType::Sick),
+            "parental" => Ok(LeaveType::Parental),
+            other => Err(LeaveError::Config(format!(
+                "unknown leave type {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+// How a yearly allowance is handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accrual {
+    // A twelfth on the first of each month worked.
+    Monthly,
+    // All of it on 1 January, or on the hire date in the first year.
+    Upfront,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaveRule {
+    pub leave: LeaveType,
+    // `None` applies to every role.
+    pub role: Option<Role>,
+    pub min_tenure_years: u32,
+    pub days_per_year: u32,
+    // The most unused leave that moves into the next year.
+    pub carry_over_days: u32,
+    pub accrual: Accrual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeavePolicy {
+    pub rules: Vec<LeaveRule>,
+}
+
+// The file layout, before names are checked.
+#[derive(Deserialize)]
+struct RawPolicy {
+    rules: Vec<RawRule>,
+}
+
+#[derive(Deserialize)]
+struct RawRule {
+    leave: String,
+    role: Option<String>,
+    #[serde(default)]
+    min_tenure_years: u32,
+    days_per_year: u32,
+    #[serde(default)]
+    carry_over_days: u32,
+    accrual: String,
+}
+
+// ----------------------------
+// Requests and Balances
+// ----------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+// A request for leave on the working days from `start` to `end`, inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaveRequest {
+    pub id: u32,
+    pub employee_id: i32,
+    pub leave: LeaveType,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub status: RequestStatus,
+}
+
+// One employee's leave of one type within a year, as of a date. Amounts are
+// in minutes; see `MINUTES_PER_DAY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Balance {
+    pub carried_over: i64,
+    pub accrued: i64,
+    // Approved leave on or before the date.
+    pub taken: i64,
+    // Approved leave later in the year.
+    pub booked: i64,
+    pub pending: i64,
+}
+
+impl Balance {
+    /// What is left to request: everything earned less everything approved or asked for.
+    pub fn available(&self) -> i64 {
+        self.carried_over + self.accrued - self.taken - self.booked - self.pending
+    }
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} available (carried over {}, accrued {}, taken {}, booked {}, pending {})",
+            days(self.available()),
+            days(self.carried_over),
+            days(self.accrued),
+            days(self.taken),
+            days(self.booked),
+            days(self.pending)
+        )
+    }
+}
+
+// Who is away on each working day that anyone is, for one department tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbsenceCalendar {
+    pub department: String,
+    pub days: Vec<(NaiveDate, Vec<(String, LeaveType)>)>,
+}
+
+impl fmt::Display for AbsenceCalendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Absences in {}", self.department)?;
+        for (day, absent) in &self.days {
+            let names: Vec<String> = absent
+                .iter()
+                .map(|(name, leave)| format!("{} ({})", name, leave))
+                .collect();
+            write!(f, "\n  {} {}  {}", day, day.weekday(), names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug)]
+pub enum LeaveError {
+    Io(std::io::Error),
+    Config(String),
+    UnknownEmployee(i32),
+    // The employee has no hire date on record.
+    NotEnrolled(i32),
+    UnknownRequest(u32),
+    InvalidDates { start: NaiveDate, end: NaiveDate },
+    Overlaps { request_id: u32 },
+    InsufficientBalance { needed: i64, available: i64 },
+    NotPending(u32),
+    // Only the employee's own manager may decide on their requests.
+    NotManager { approver_id: i32, employee_id: i32 },
+    NoManager(i32),
+}
+
+impl fmt::Display for LeaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaveError::Io(e) => write!(f, "cannot read leave policy: {}", e),
+            LeaveError::Config(message) => write!(f, "invalid leave policy: {}", message),
+            LeaveError::UnknownEmployee(id) => write!(f, "no employee with id {}", id),
+            LeaveError::NotEnrolled(id) => write!(f, "employee {} has no hire date", id),
+            LeaveError::UnknownRequest(id) => write!(f, "no leave request {}", id),
+            LeaveError::InvalidDates { start, end } => {
+                write!(f, "{} to {} contains no working days", start, end)
+            }
+            LeaveError::Overlaps { request_id } => {
+                write!(f, "overlaps leave request {}", request_id)
+            }
+            LeaveError::InsufficientBalance { needed, available } => write!(
+                f,
+                "needs {} but only {} available",
+                days(*needed),
+                days(*available)
+            ),
+            LeaveError::NotPending(id) => write!(f, "leave request {} is already decided", id),
+            LeaveError::NotManager {
+                approver_id,
+                employee_id,
+            } => write!(
+                f,
+                "employee {} is not the manager of employee {}",
+                approver_id, employee_id
+            ),
+            LeaveError::NoManager(id) => {
+                write!(f, "employee {} has no manager to approve leave", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LeaveError {}
+
+impl From<std::io::Error> for LeaveError {
+    fn from(e: std::io::Error) -> Self {
+        LeaveError::Io(e)
+    }
+}
+
+// ----------------------------
+// Loading and Rule Lookup
+// ----------------------------
+
+impl LeavePolicy {
+    /// Reads and checks a TOML leave policy file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LeaveError> {
+        LeavePolicy::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, LeaveError> {
+        let raw: RawPolicy =
+            toml::from_str(text).map_err(|e| LeaveError::Config(e.message().to_string()))?;
+        let mut rules = Vec::new();
+        for rule in raw.rules {
+            let accrual = match rule.accrual.as_str() {
+                "monthly" => Accrual::Monthly,
+                "upfront" => Accrual::Upfront,
+                other => return Err(LeaveError::Config(format!("unknown accrual {:?}", other))),
+            };
+            rules.push(LeaveRule {
+                leave: rule.leave.parse()?,
+                role: rule.role.as_deref().map(Role::from_name),
+                min_tenure_years: rule.min_tenure_years,
+                days_per_year: rule.days_per_year,
+                carry_over_days: rule.carry_over_days,
+                accrual,
+            });
+        }
+        Ok(LeavePolicy { rules })
+    }
+
+    /// The rule for `leave` that fits the role and tenure most closely: one
+    /// naming the role beats one that does not, then the longest tenure wins.
+    pub fn rule_for(&self, leave: LeaveType, role: &Role, tenure_years: u32) -> Option<&LeaveRule> {
+        self.rules
+            .iter()
+            .filter(|r| r.leave == leave && r.min_tenure_years <= tenure_years)
+            .filter(|r| r.role.as_ref().is_none_or(|rule_role| rule_role == role))
+            .max_by_key(|r| (r.role.is_some(), r.min_tenure_years))
+    }
+}
+
+/// Whole years from `hired` to `date`.
+fn tenure_years(hired: NaiveDate, date: NaiveDate) -> u32 {
+    let mut years = date.year() - hired.year();
+    if (date.month(), date.day()) < (hired.month(), hired.day()) {
+        years -= 1;
+    }
+    years.max(0) as u32
+}
+
+fn working_days(start: NaiveDate, end: NaiveDate) -> i64 {
+    start
+        .iter_days()
+        .take_while(|d| *d <= end)
+        .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+        .count() as i64
+}
+
+fn year_start(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 1, 1).expect("valid year")
+}
+
+fn year_end(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 12, 31).expect("valid year")
+}
+
+// ----------------------------
+// Leave Book
+// ----------------------------
+
+// The `LeaveBook` struct holds hire dates and every request made under one policy.
+#[derive(Debug, Clone)]
+pub struct LeaveBook {
+    policy: LeavePolicy,
+    hired: HashMap<i32, NaiveDate>,
+    pub requests: Vec<LeaveRequest>,
+}
+
+impl LeaveBook {
+    pub fn new(policy: LeavePolicy) -> Self {
+        LeaveBook {
+            policy,
+            hired: HashMap::new(),
+            requests: Vec::new(),
+        }
+    }
+
+    /// Records when an employee was hired; accrual and tenure count from then.
+    pub fn enroll(&mut self, employee_id: i32, hired: NaiveDate) {
+        self.hired.insert(employee_id, hired);
+    }
+
+    fn hire_date(&self, employee_id: i32) -> Result<NaiveDate, LeaveError> {
+        self.hired
+            .get(&employee_id)
+            .copied()
+            .ok_or(LeaveError::NotEnrolled(employee_id))
+    }
+
+    /// Leave earned in `year` up to and including `through`.
+    fn accrued(
+        &self,
+        emp: &Employee,
+        leave: LeaveType,
+        hired: NaiveDate,
+        year: i32,
+        through: NaiveDate,
+    ) -> i64 {
+        let rule_on = |date| {
+            self.policy
+                .rule_for(leave, &emp.role, tenure_years(hired, date))
+        };
+        let mut total = 0;
+        // Upfront allowances are granted once, on the first day employed in the year.
+        let grant_day = year_start(year).max(hired);
+        if grant_day <= through {
+            if let Some(rule) = rule_on(grant_day).filter(|r| r.accrual == Accrual::Upfront) {
+                total += rule.days_per_year as i64 * MINUTES_PER_DAY;
+            }
+        }
+        for month in 1..=12 {
+            let first = NaiveDate::from_ymd_opt(year, month, 1).expect("valid month");
+            if first < hired || first > through {
+                continue;
+            }
+            if let Some(rule) = rule_on(first).filter(|r| r.accrual == Accrual::Monthly) {
+                total += rule.days_per_year as i64 * MINUTES_PER_DAY / 12;
+            }
+        }
+        total
+    }
+
+    /// Minutes of leave in the employee's requests with `status` that fall from `from` to `to`.
+    fn requested(
+        &self,
+        employee_id: i32,
+        leave: LeaveType,
+        status: RequestStatus,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> i64 {
+        self.requests
+            .iter()
+            .filter(|r| r.employee_id == employee_id && r.leave == leave && r.status == status)
+            .filter(|r| r.start <= to && r.end >= from)
+            .map(|r| working_days(r.start.max(from), r.end.min(to)) * MINUTES_PER_DAY)
+            .sum()
+    }
+
+    /// The employee's balance of `leave` for the year containing `date`, as of
+    /// that date. Unused leave moves into each new year up to the carry-over
+    /// cap in force on 31 December; an overdrawn balance moves in full.
+    pub fn balance(
+        &self,
+        emp: &Employee,
+        leave: LeaveType,
+        date: NaiveDate,
+    ) -> Result<Balance, LeaveError> {
+        let hired = self.hire_date(emp.id)?;
+        let mut carried_over = 0;
+        for year in hired.year()..date.year() {
+            let closing = carried_over + self.accrued(emp, leave, hired, year, year_end(year))
+                - self.requested(
+                    emp.id,
+                    leave,
+                    RequestStatus::Approved,
+                    year_start(year),
+                    year_end(year),
+                );
+            let cap = self
+                .policy
+                .rule_for(leave, &emp.role, tenure_years(hired, year_end(year)))
+                .map_or(0, |r| r.carry_over_days as i64 * MINUTES_PER_DAY);
+            carried_over = closing.min(cap);
+        }
+        let year = date.year();
+        let later = date + Duration::days(1);
+        Ok(Balance {
+            carried_over,
+            accrued: self.accrued(emp, leave, hired, year, date),
+            taken: self.requested(
+                emp.id,
+                leave,
+                RequestStatus::Approved,
+                year_start(year),
+                date,
+            ),
+            booked: if later.year() == year {
+                self.requested(
+                    emp.id,
+                    leave,
+                    RequestStatus::Approved,
+                    later,
+                    year_end(year),
+                )
+            } else {
+                0
+            },
+            pending: self.requested(
+                emp.id,
+                leave,
+                RequestStatus::Pending,
+                year_start(year),
+                year_end(year),
+            ),
+        })
+    }
+
+    /// Asks for leave. The request must cover at least one working day, must not
+    /// overlap another open request, and each calendar year's part must fit in
+    /// that year's balance as of the day the part starts.
+    pub fn request(
+        &mut self,
+        emp: &Employee,
+        leave: LeaveType,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<u32, LeaveError> {
+        let needed = if start <= end {
+            working_days(start, end)
+        } else {
+            0
+        } * MINUTES_PER_DAY;
+        if needed == 0 {
+            return Err(LeaveError::InvalidDates { start, end });
+        }
+        if let Some(other) = self.requests.iter().find(|r| {
+            r.employee_id == emp.id
+                && r.status != RequestStatus::Rejected
+                && r.start <= end
+                && r.end >= start
+        }) {
+            return Err(LeaveError::Overlaps {
+                request_id: other.id,
+            });
+        }
+        // Each year's part is paid from that year's balance.
+        for year in start.year()..=end.year() {
+            let from = start.max(year_start(year));
+            let part = working_days(from, end.min(year_end(year))) * MINUTES_PER_DAY;
+            if part > 0 {
+                self.check_balance(emp, leave, from, part)?;
+            }
+        }
+        let id = self.requests.len() as u32 + 1;
+        self.requests.push(LeaveRequest {
+            id,
+            employee_id: emp.id,
+            leave,
+            start,
+            end,
+            status: RequestStatus::Pending,
+        });
+        Ok(id)
+    }
+
+    fn check_balance(
+        &self,
+        emp: &Employee,
+        leave: LeaveType,
+        on: NaiveDate,
+        needed: i64,
+    ) -> Result<(), LeaveError> {
+        let available = self.balance(emp, leave, on)?.available();
+        if needed > available {
+            return Err(LeaveError::InsufficientBalance { needed, available });
+        }
+        Ok(())
+    }
+
+    fn decide(
+        &mut self,
+        company: &Department,
+        request_id: u32,
+        approver_id: i32,
+        status: RequestStatus,
+    ) -> Result<(), LeaveError> {
+        let request = self
+            .requests
+            .iter_mut()
+            .find(|r| r.id == request_id)
+            .ok_or(LeaveError::UnknownRequest(request_id))?;
+        if request.status != RequestStatus::Pending {
+            return Err(LeaveError::NotPending(request_id));
+        }
+        let emp = company
+            .find_employee(request.employee_id)
+            .ok_or(LeaveError::UnknownEmployee(request.employee_id))?;
+        match emp.manager_id {
+            None => return Err(LeaveError::NoManager(emp.id)),
+            Some(manager) if manager != approver_id => {
+                return Err(LeaveError::NotManager {
+                    approver_id,
+                    employee_id: emp.id,
+                })
+            }
+            Some(_) => {}
+        }
+        request.status = status;
+        Ok(())
+    }
+
+    /// Approves a pending request on behalf of the employee's manager.
+    pub fn approve(
+        &mut self,
+        company: &Department,
+        request_id: u32,
+        approver_id: i32,
+    ) -> Result<(), LeaveError> {
+        self.decide(company, request_id, approver_id, RequestStatus::Approved)
+    }
+
+    /// Turns down a pending request on behalf of the employee's manager.
+    pub fn reject(
+        &mut self,
+        company: &Department,
+        request_id: u32,
+        approver_id: i32,
+    ) -> Result<(), LeaveError> {
+        self.decide(company, request_id, approver_id, RequestStatus::Rejected)
+    }
+
+    /// Approved absences of everyone in the department tree, for each working
+    /// day from `from` to `to` on which anyone is away. People are listed by ID.
+    pub fn calendar(&self, dept: &Department, from: NaiveDate, to: NaiveDate) -> AbsenceCalendar {
+        let mut employees = dept.all_employees();
+        employees.sort_by_key(|e| e.id);
+        let days = from
+            .iter_days()
+            .take_while(|d| *d <= to)
+            .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+            .filter_map(|day| {
+                let absent: Vec<(String, LeaveType)> = employees
+                    .iter()
+                    .filter_map(|emp| {
+                        self.requests
+                            .iter()
+                            .find(|r| {
+                                r.employee_id == emp.id
+                                    && r.status == RequestStatus::Approved
+                                    && r.start <= day
+                                    && r.end >= day
+                            })
+                            .map(|r| (emp.name.clone(), r.leave))
+                    })
+                    .collect();
+                (!absent.is_empty()).then_some((day, absent))
+            })
+            .collect();
+        AbsenceCalendar {
+            department: dept.name.clone(),
+            days,
+        }
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{date, employee};
+
+    const POLICY: &str = r#"
+        [[rules]]
+        leave = "vacation"
+        days_per_year = 12
+        carry_over_days = 3
+        accrual = "monthly"
+
+        [[rules]]
+        leave = "vacation"
+        min_tenure_years = 2
+        days_per_year = 24
+        carry_over_days = 3
+        accrual = "monthly"
+
+        [[rules]]
+        leave = "vacation"
+        role = "Manager"
+        days_per_year = 18
+        carry_over_days = 3
+        accrual = "monthly"
+
+        [[rules]]
+        leave = "sick"
+        days_per_year = 5
+        accrual = "upfront"
+    "#;
+
+    fn team() -> Department {
+        Department {
+            name: String::from("Web"),
+            employees: vec![
+                employee(1, Role::Manager, 90000).named("Ada"),
+                employee(2, Role::Developer, 90000)
+                    .named("Bo")
+                    .reporting_to(1),
+                employee(3, Role::Developer, 90000)
+                    .named("Cy")
+                    .reporting_to(1),
+            ],
+            sub_departments: Vec::new(),
+        }
+    }
+
+    fn book() -> LeaveBook {
+        let mut book = LeaveBook::new(LeavePolicy::from_toml(POLICY).unwrap());
+        book.enroll(1, date("2020-01-01"));
+        book.enroll(2, date("2024-03-15"));
+        book.enroll(3, date("2022-06-01"));
+        book
+    }
+
+    #[test]
+    fn test_accrual_rules_and_carry_over() {
+        let policy = LeavePolicy::from_toml(POLICY).unwrap();
+        let rule = |role: Role, tenure| {
+            policy
+                .rule_for(LeaveType::Vacation, &role, tenure)
+                .unwrap()
+                .days_per_year
+        };
+        assert_eq!(rule(Role::Developer, 0), 12);
+        assert_eq!(rule(Role::Developer, 3), 24);
+        // A rule naming the role wins over a longer tenure rule.
+        assert_eq!(rule(Role::Manager, 10), 18);
+        assert!(policy
+            .rule_for(LeaveType::Parental, &Role::Developer, 5)
+            .is_none());
+        assert!(LeavePolicy::from_toml(
+            "[[rules]]\nleave = \"nap\"\ndays_per_year = 1\naccrual = \"monthly\""
+        )
+        .is_err());
+
+        let book = book();
+        let bo = employee(2, Role::Developer, 90000)
+            .named("Bo")
+            .reporting_to(1);
+        // Hired mid-March 2024: April to December is nine months of one day.
+        let end_2024 = book
+            .balance(&bo, LeaveType::Vacation, date("2024-12-31"))
+            .unwrap();
+        assert_eq!(end_2024.accrued, 9 * MINUTES_PER_DAY);
+        // Only three days carry over, then two months of 2025 are earned.
+        let feb = book
+            .balance(&bo, LeaveType::Vacation, date("2025-02-10"))
+            .unwrap();
+        assert_eq!(feb.carried_over, 3 * MINUTES_PER_DAY);
+        assert_eq!(feb.available(), 5 * MINUTES_PER_DAY);
+        // Sick leave arrives in full on the hire date.
+        let sick = book
+            .balance(&bo, LeaveType::Sick, date("2024-03-15"))
+            .unwrap();
+        assert_eq!(sick.available(), 5 * MINUTES_PER_DAY);
+        // Two years in, on 1 June 2024, the monthly rate doubles.
+        let cy = employee(3, Role::Developer, 90000)
+            .named("Cy")
+            .reporting_to(1);
+        let july = book
+            .balance(&cy, LeaveType::Vacation, date("2024-07-01"))
+            .unwrap();
+        assert_eq!(july.accrued, (5 + 2 * 2) * MINUTES_PER_DAY);
+    }
+
+    #[test]
+    fn test_requests_need_balance_and_the_manager() {
+        let company = team();
+        let mut book = book();
+        let bo = company.find_employee(2).unwrap().clone();
+
+        // Mon 3 to Fri 7 March 2025: five working days of the six available.
+        let id = book
+            .request(
+                &bo,
+                LeaveType::Vacation,
+                date("2025-03-03"),
+                date("2025-03-09"),
+            )
+            .unwrap();
+        let balance = book
+            .balance(&bo, LeaveType::Vacation, date("2025-03-01"))
+            .unwrap();
+        assert_eq!(balance.pending, 5 * MINUTES_PER_DAY);
+        assert!(matches!(
+            book.request(&bo, LeaveType::Sick, date("2025-03-07"), date("2025-03-07")),
+            Err(LeaveError::Overlaps { request_id: 1 })
+        ));
+        assert!(matches!(
+            book.request(
+                &bo,
+                LeaveType::Vacation,
+                date("2025-03-10"),
+                date("2025-03-11")
+            ),
+            Err(LeaveError::InsufficientBalance { .. })
+        ));
+        assert!(matches!(
+            book.request(
+                &bo,
+                LeaveType::Vacation,
+                date("2025-03-08"),
+                date("2025-03-09")
+            ),
+            Err(LeaveError::InvalidDates { .. })
+        ));
+
+        assert!(matches!(
+            book.approve(&company, id, 3),
+            Err(LeaveError::NotManager {
+                approver_id: 3,
+                employee_id: 2
+            })
+        ));
+        book.approve(&company, id, 1).unwrap();
+        assert!(matches!(
+            book.reject(&company, id, 1),
+            Err(LeaveError::NotPending(1))
+        ));
+        let later = book
+            .balance(&bo, LeaveType::Vacation, date("2025-03-05"))
+            .unwrap();
+        assert_eq!(
+            (later.taken, later.booked),
+            (3 * MINUTES_PER_DAY, 2 * MINUTES_PER_DAY)
+        );
+
+        let ada = company.find_employee(1).unwrap().clone();
+        let own = book
+            .request(
+                &ada,
+                LeaveType::Sick,
+                date("2025-03-03"),
+                date("2025-03-03"),
+            )
+            .unwrap();
+        assert!(matches!(
+            book.approve(&company, own, 1),
+            Err(LeaveError::NoManager(1))
+        ));
+
+        // Tue 31 December 2024 to Thu 1 January 2026: one day from 2024's sick
+        // leave fits, but all 261 working days of 2025 come from 2025's.
+        let cy = company.find_employee(3).unwrap().clone();
+        assert!(matches!(
+            book.request(
+                &cy,
+                LeaveType::Sick,
+                date("2024-12-31"),
+                date("2026-01-01")
+            ),
+            Err(LeaveError::InsufficientBalance { needed, available })
+                if needed == 261 * MINUTES_PER_DAY && available == 5 * MINUTES_PER_DAY
+        ));
+    }
+
+    #[test]
+    fn test_team_absence_calendar() {
+        let company = team();
+        let mut book = book();
+        let bo = company.find_employee(2).unwrap().clone();
+        let cy = company.find_employee(3).unwrap().clone();
+        let a = book
+            .request(
+                &bo,
+                LeaveType::Vacation,
+                date("2025-03-06"),
+                date("2025-03-10"),
+            )
+            .unwrap();
+        let b = book
+            .request(&cy, LeaveType::Sick, date("2025-03-10"), date("2025-03-10"))
+            .unwrap();
+        let c = book
+            .request(
+                &cy,
+                LeaveType::Vacation,
+                date("2025-03-12"),
+                date("2025-03-12"),
+            )
+            .unwrap();
+        book.approve(&company, a, 1).unwrap();
+        book.approve(&company, b, 1).unwrap();
+        book.reject(&company, c, 1).unwrap();
+
+        let calendar = book.calendar(&company, date("2025-03-01"), date("2025-03-14"));
+        let expected = "\
+Absences in Web
+  2025-03-06 Thu  Bo (vacation)
+  2025-03-07 Fri  Bo (vacation)
+  2025-03-10 Mon  Bo (vacation), Cy (sick)";
+        assert_eq!(calendar.to_string(), expected);
+    }
+}
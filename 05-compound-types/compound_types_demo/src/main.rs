@@ -1,10 +1,34 @@
 // This program provides an advanced demonstration of Rust's compound types,
 // including tuples, arrays, structs, enums, and pattern matching.
-// All functionalities are contained within a single file for simplicity.
+// The employee model lives in this file; supporting types have their own modules.
+
+mod chart;
+mod history;
+mod leave;
+mod money;
+mod org;
+mod payroll;
+mod records;
+mod roles;
+mod staffing;
+mod stats;
+#[cfg(test)]
+mod test_support;
 
 // Importing necessary standard library features
+use chart::{ChartOptions, LabelField};
+use history::{Compensation, History};
+use leave::{LeaveBook, LeavePolicy, LeaveType, MINUTES_PER_DAY};
+use money::{Currency, Locale, Money};
+use payroll::{PayFrequency, Payroll, PayrollConfig};
+use roles::RoleCatalog;
+use staffing::{Assignment, Staffing};
+use stats::{stats_by_department, stats_by_role, SalaryStats};
 use std::fmt;
 
+// The role catalogue the demonstrations read roles and descriptions from.
+const ROLE_CATALOG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/roles.toml");
+
 // ----------------------------
 // Struct Definitions
 // ----------------------------
@@ -15,14 +39,20 @@ struct Employee {
     id: i32,
     name: String,
     role: Role,
-    salary: f64,
+    salary: Money,
+    // The ID of the employee this one reports to, if any.
+    manager_id: Option<i32>,
+    // Career level within the role, starting at 1.
+    level: u8,
 }
 
-// The `Department` struct represents a department containing a list of employees.
+// The `Department` struct represents a department containing a list of employees
+// and any departments nested inside it.
 #[derive(Debug)]
 struct Department {
     name: String,
     employees: Vec<Employee>,
+    sub_departments: Vec<Department>,
 }
 
 // The `Project` struct is a tuple struct representing a project name and its duration in months.
@@ -34,21 +64,39 @@ struct Project(String, i32);
 // ----------------------------
 
 // The `Role` enum defines different roles an employee can have.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Role {
     Developer,
     Manager,
     Designer,
+    // A role defined in a role catalogue rather than built in.
+    Custom(String),
 }
 
 // Implementing methods for `Role` to provide descriptive text.
 impl Role {
-    // Returns a description of the role.
-    fn description(&self) -> &str {
-        match self {
-            Role::Developer => "Writes and maintains code.",
-            Role::Manager => "Oversees team operations.",
-            Role::Designer => "Designs user interfaces and experiences.",
+    // Returns the role's description. A catalogue's wording is used when it
+    // describes the role; otherwise the built-in text is.
+    fn description<'a>(&self, catalog: Option<&'a RoleCatalog>) -> &'a str {
+        catalog
+            .and_then(|catalog| catalog.role(self))
+            .map(|definition| definition.description.as_str())
+            .filter(|description| !description.is_empty())
+            .unwrap_or(match self {
+                Role::Developer => "Writes and maintains code.",
+                Role::Manager => "Oversees team operations.",
+                Role::Designer => "Designs user interfaces and experiences.",
+                Role::Custom(_) => "Not described in the role catalogue.",
+            })
+    }
+
+    // Returns the built-in role with this name, or a custom one.
+    fn from_name(name: &str) -> Role {
+        match name {
+            "Developer" => Role::Developer,
+            "Manager" => Role::Manager,
+            "Designer" => Role::Designer,
+            other => Role::Custom(other.to_string()),
         }
     }
 }
@@ -60,6 +108,7 @@ impl fmt::Display for Role {
             Role::Developer => "Developer",
             Role::Manager => "Manager",
             Role::Designer => "Designer",
+            Role::Custom(name) => name,
         };
         write!(f, "{}", role_str)
     }
@@ -69,14 +118,25 @@ impl fmt::Display for Role {
 // Helper Functions
 // ----------------------------
 
-/// Calculates the average salary of a list of employees.
-/// Returns `None` if the list is empty.
-fn calculate_average_salary(employees: &Vec<Employee>) -> Option<f64> {
-    if employees.is_empty() {
-        return None;
+/// A whole-dollar amount for the demo data, which is always small enough to hold.
+fn usd(major: i64) -> Money {
+    Money::from_major(major, Currency::Usd).expect("demo amounts fit in an i64")
+}
+
+/// Calculates the average salary of a list of employees, rounded half to even.
+/// Returns `None` if the list is empty or salaries are in different currencies.
+fn calculate_average_salary(employees: &[Employee]) -> Option<Money> {
+    SalaryStats::from_employees(employees).ok().map(|stats| stats.mean)
+}
+
+// Implementing payroll totals for `Department`.
+impl Department {
+    /// Adds up the salaries of everyone in the department.
+    /// Returns `None` for an empty department or one paid in mixed currencies.
+    fn total_salary(&self) -> Option<Money> {
+        let first = self.employees.first()?;
+        Money::sum(self.employees.iter().map(|e| &e.salary), first.salary.currency()).ok()
     }
-    let total: f64 = employees.iter().map(|e| e.salary).sum();
-    Some(total / (employees.len() as f64))
 }
 
 /// Prints detailed information about a department, including its employees.
@@ -84,7 +144,7 @@ fn print_department_info(dept: &Department) {
     println!("Department: {}", dept.name);
     for emp in &dept.employees {
         println!(
-            " - ID: {}, Name: {}, Role: {}, Salary: ${}",
+            " - ID: {}, Name: {}, Role: {}, Salary: {}",
             emp.id, emp.name, emp.role, emp.salary
         );
     }
@@ -151,14 +211,18 @@ fn demonstrate_structs() {
         id: 201,
         name: String::from("Bob"),
         role: Role::Developer,
-        salary: 85000.0,
+        salary: usd(85000),
+        manager_id: None,
+        level: 1,
     };
 
     let emp2 = Employee {
         id: 202,
         name: String::from("Carol"),
         role: Role::Manager,
-        salary: 95000.0,
+        salary: usd(95000),
+        manager_id: None,
+        level: 1,
     };
 
     // Printing the created employees.
@@ -169,6 +233,7 @@ fn demonstrate_structs() {
     let engineering = Department {
         name: String::from("Engineering"),
         employees: vec![emp1, emp2],
+        sub_departments: Vec::new(),
     };
 
     // Printing department information.
@@ -184,22 +249,26 @@ fn demonstrate_enums() {
     let role2 = Role::Manager;
 
     // Matching on the first role.
-    match role1 {
+    match &role1 {
         Role::Developer => println!("Role: Developer"),
         Role::Manager => println!("Role: Manager"),
         Role::Designer => println!("Role: Designer"),
+        Role::Custom(name) => println!("Role: {}", name),
     }
 
     // Matching on the second role.
-    match role2 {
+    match &role2 {
         Role::Developer => println!("Role: Developer"),
         Role::Manager => println!("Role: Manager"),
         Role::Designer => println!("Role: Designer"),
+        Role::Custom(name) => println!("Role: {}", name),
     }
 
-    // Printing role descriptions.
-    println!("{}: {}", role1, role1.description());
-    println!("{}: {}", role2, role2.description());
+    // Printing role descriptions, from the role catalogue when it loads.
+    let catalog = RoleCatalog::load(ROLE_CATALOG).ok();
+    for role in [&role1, &role2] {
+        println!("{}: {}", role, role.description(catalog.as_ref()));
+    }
 }
 
 /// Demonstrates pattern matching with structs and enums, including `if let`.
@@ -211,7 +280,9 @@ fn demonstrate_pattern_matching() {
         id: 301,
         name: String::from("Dave"),
         role: Role::Manager,
-        salary: 105000.0,
+        salary: usd(105000),
+        manager_id: None,
+        level: 1,
     };
 
     // Matching on the employee's role to print appropriate messages.
@@ -219,6 +290,7 @@ fn demonstrate_pattern_matching() {
         Role::Developer => println!("{} is a Developer.", emp.name),
         Role::Manager => println!("{} is a Manager.", emp.name),
         Role::Designer => println!("{} is a Designer.", emp.name),
+        Role::Custom(ref name) => println!("{} is a {}.", emp.name, name),
     }
 
     // Using `if let` to handle an `Option<Department>`.
@@ -229,9 +301,12 @@ fn demonstrate_pattern_matching() {
                 id: 302,
                 name: String::from("Eve"),
                 role: Role::Designer,
-                salary: 90000.0,
+                salary: usd(90000),
+                manager_id: None,
+                level: 1,
             },
         ],
+        sub_departments: Vec::new(),
     });
 
     if let Some(dept) = department_option {
@@ -255,15 +330,20 @@ fn combine_compound_types() {
                     id: 401,
                     name: String::from("Frank"),
                     role: Role::Developer,
-                    salary: 80000.0,
+                    salary: usd(80000),
+                    manager_id: None,
+                    level: 1,
                 },
                 Employee {
                     id: 402,
                     name: String::from("Grace"),
                     role: Role::Developer,
-                    salary: 82000.0,
+                    salary: usd(82000),
+                    manager_id: None,
+                    level: 1,
                 },
             ],
+            sub_departments: Vec::new(),
         },
         Department {
             name: String::from("Human Resources"),
@@ -272,15 +352,21 @@ fn combine_compound_types() {
                     id: 403,
                     name: String::from("Heidi"),
                     role: Role::Manager,
-                    salary: 90000.0,
+                    salary: usd(90000),
+                    manager_id: None,
+                    level: 1,
                 },
             ],
+            sub_departments: Vec::new(),
         },
     ];
 
     // Iterating over each department and printing its information.
     for dept in &departments {
         print_department_info(dept);
+        if let Some(total) = dept.total_salary() {
+            println!("Total payroll for {}: {}", dept.name, total);
+        }
     }
 
     // Calculating and printing the average salary for all employees.
@@ -289,12 +375,649 @@ fn combine_compound_types() {
         .collect();
     
     if let Some(avg_salary) = calculate_average_salary(&all_employees) {
-        println!("Average Salary across all departments: ${}", avg_salary);
+        println!("Average Salary across all departments: {}", avg_salary);
     } else {
         println!("No employees to calculate average salary.");
     }
 }
 
+/// Demonstrates exact money arithmetic: currencies, rounding, allocation and formatting.
+fn demonstrate_money() {
+    println!("\n--- Money Demonstration ---");
+
+    // Parsing amounts and currency codes from text.
+    let currency: Currency = "eur".parse().unwrap_or(Currency::Usd);
+    let salary = Money::parse("61250.50", currency).unwrap_or(Money::zero(currency));
+    println!("Salary: {} / {} / {}", salary, salary.format(&Locale::de_de()), salary.format(&Locale::fr_fr()));
+    if let Err(e) = "XYZ".parse::<Currency>() {
+        println!("Rejected: {}", e);
+    }
+    if let Err(e) = Money::parse("10.005", Currency::Usd) {
+        println!("Rejected: {}", e);
+    }
+
+    // Checked arithmetic refuses to mix currencies.
+    let bonus = usd(500);
+    match salary.checked_add(bonus) {
+        Ok(total) => println!("Total: {}", total),
+        Err(e) => println!("Cannot add bonus: {}", e),
+    }
+
+    // A monthly salary, a 7.65% deduction rounded half to even, and the net amount.
+    let monthly = usd(85000).checked_div(12).unwrap();
+    let deduction = monthly.mul_ratio(765, 10_000).unwrap();
+    let net = monthly.checked_sub(deduction).unwrap();
+    println!("Monthly: {}, deduction: {}, net: {}", monthly, deduction, net);
+    println!("Yearly net: {}", net.checked_mul(12).unwrap());
+
+    // Splitting $100 three ways keeps every cent.
+    let parts = usd(100).split(3).unwrap();
+    let shown: Vec<String> = parts.iter().map(|p| p.to_string()).collect();
+    println!("$100 split three ways: {}", shown.join(" + "));
+    let shares = Money::new(1001, Currency::Usd).allocate(&[70, 20, 10]).unwrap();
+    let cents: Vec<i64> = shares.iter().map(|p| p.minor()).collect();
+    println!("$10.01 split 70/20/10 in cents: {:?}", cents);
+}
+
+/// Demonstrates running payroll for a department from a config file.
+fn demonstrate_payroll() {
+    println!("\n--- Payroll Demonstration ---");
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/payroll.toml");
+    let config = match PayrollConfig::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let frequency = match config.frequency {
+        PayFrequency::Monthly => "monthly",
+        PayFrequency::Biweekly => "every two weeks",
+    };
+    println!("Paying {} in {}, starting {}", frequency, config.currency, config.first_period_start);
+
+    let engineering = Department {
+        name: String::from("Engineering"),
+        employees: vec![
+            Employee {
+                id: 701,
+                name: String::from("Liam"),
+                role: Role::Developer,
+                salary: usd(85000),
+                manager_id: Some(702),
+                level: 1,
+            },
+            Employee {
+                id: 702,
+                name: String::from("Mia"),
+                role: Role::Manager,
+                salary: usd(120000),
+                manager_id: None,
+                level: 1,
+            },
+        ],
+        sub_departments: Vec::new(),
+    };
+
+    // Pay the first quarter, then print the March payslip and department summary.
+    let mut payroll = Payroll::new(config);
+    let periods = payroll.config().periods(2025);
+    for period in &periods[..2] {
+        if let Err(e) = payroll.run_department(&engineering, period) {
+            println!("Payroll failed: {}", e);
+        }
+    }
+    match payroll.run_department(&engineering, &periods[2]) {
+        Ok(run) => {
+            println!("{}", run.payslips[0]);
+            println!("{}", run);
+        }
+        Err(e) => println!("Payroll failed: {}", e),
+    }
+
+    // Running the same period twice is refused.
+    if let Err(e) = payroll.pay(&engineering.employees[1], &periods[2]) {
+        println!("Refused: {}", e);
+    }
+    if let Some(ytd) = payroll.year_to_date(702, 2025) {
+        println!("Mia's pay so far this year: {} gross, {} net", ytd.gross, ytd.net);
+    }
+}
+
+/// Builds a small company with nested departments and reporting lines.
+fn sample_company() -> Department {
+    let employee = |id: i32,
+                    name: &str,
+                    role: Role,
+                    level: u8,
+                    salary: i64,
+                    manager_id: Option<i32>| Employee {
+        id,
+        name: String::from(name),
+        role,
+        salary: usd(salary),
+        manager_id,
+        level,
+    };
+    Department {
+        name: String::from("Acme"),
+        employees: vec![employee(801, "Nora", Role::Manager, 2, 210000, None)],
+        sub_departments: vec![
+            Department {
+                name: String::from("Engineering"),
+                employees: vec![
+                    employee(802, "Oscar", Role::Manager, 2, 160000, Some(801)),
+                    employee(803, "Priya", Role::Developer, 2, 118000, Some(802)),
+                    employee(804, "Quinn", Role::Developer, 1, 97000, Some(802)),
+                ],
+                sub_departments: vec![Department {
+                    name: String::from("Design"),
+                    employees: vec![
+                        employee(805, "Rosa", Role::Designer, 1, 92000, Some(802)),
+                        employee(806, "Sam", Role::Designer, 1, 78000, Some(805)),
+                    ],
+                    sub_departments: Vec::new(),
+                }],
+            },
+            Department {
+                name: String::from("Operations"),
+                employees: vec![
+                    employee(807, "Tara", Role::Manager, 1, 125000, Some(801)),
+                    employee(808, "Umar", Role::Developer, 2, 88000, Some(807)),
+                ],
+                sub_departments: Vec::new(),
+            },
+        ],
+    }
+}
+
+/// Demonstrates reporting lines, nested departments and rollups.
+fn demonstrate_org_hierarchy() {
+    println!("\n--- Organisation Hierarchy Demonstration ---");
+
+    let mut company = sample_company();
+    if let Err(e) = company.validate() {
+        println!("Invalid organisation: {}", e);
+        return;
+    }
+    println!("{} has {} employees", company.name, company.all_employees().len());
+
+    // Direct and transitive reports, and span of control.
+    let names = |people: Vec<&Employee>| -> String {
+        people.iter().map(|e| e.name.as_str()).collect::<Vec<_>>().join(", ")
+    };
+    println!("Oscar's direct reports: {}", names(company.direct_reports(802)));
+    println!("Everyone under Oscar: {}", names(company.all_reports(802)));
+    println!("Nora's span of control: {}", company.span_of_control(801));
+
+    // Chain of command from one employee to the top.
+    match company.chain_of_command(806) {
+        Ok(chain) => println!("Sam reports up through: {}", names(chain)),
+        Err(e) => println!("{}", e),
+    }
+
+    // Moving a manager below their own report is refused.
+    if let Err(e) = company.set_manager(802, Some(806)) {
+        println!("Refused: {}", e);
+    }
+    if company.set_manager(808, Some(802)).is_ok() {
+        println!("Umar now reports to Oscar; Tara manages {}", company.span_of_control(807));
+    }
+
+    // Headcount and salary rolled up per department and per team.
+    match company.rollup(Currency::Usd) {
+        Ok(rollup) => println!("{}", rollup),
+        Err(e) => println!("{}", e),
+    }
+    if let Ok((headcount, salary)) = company.team_rollup(802, Currency::Usd) {
+        println!("Oscar's team: {} people, {}", headcount, salary);
+    }
+    if let Some(design) = company.find_department("Design") {
+        print_department_info(design);
+    }
+}
+
+/// Demonstrates exporting the org chart as Graphviz DOT and Mermaid text.
+fn demonstrate_org_chart() {
+    println!("\n--- Org Chart Export Demonstration ---");
+
+    let company = sample_company();
+
+    // The whole company as a Mermaid flowchart, with departments as subgraphs.
+    match company.to_mermaid(&ChartOptions::default()) {
+        Ok(mermaid) => print!("{}", mermaid),
+        Err(e) => println!("{}", e),
+    }
+
+    // One department as DOT, labelled with IDs and salary bands instead of roles.
+    let options = ChartOptions {
+        fields: vec![LabelField::Name, LabelField::Id, LabelField::SalaryBand],
+        band_width: 50000,
+        clusters: false,
+    };
+    if let Some(engineering) = company.find_department("Engineering") {
+        match engineering.to_dot(&options) {
+            Ok(dot) => print!("{}", dot),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Demonstrates salary statistics across the company, by role and by department.
+fn demonstrate_salary_statistics() {
+    println!("\n--- Salary Statistics Demonstration ---");
+
+    let company = sample_company();
+    let everyone: Vec<Employee> = company.all_employees().into_iter().cloned().collect();
+
+    match SalaryStats::from_employees(&everyone) {
+        Ok(stats) => {
+            println!("Company: {}", stats);
+            println!(
+                "p25 {}, p75 {}, p90 {}",
+                stats.percentile(25),
+                stats.percentile(75),
+                stats.percentile(90)
+            );
+            println!("Compression ratio (p10/p90): {:.2}", stats.compression_ratio());
+            match stats.histogram(usd(25000)) {
+                Ok(buckets) => buckets.iter().for_each(|b| println!("{}", b)),
+                Err(e) => println!("Histogram failed: {}", e),
+            }
+        }
+        Err(e) => println!("No statistics: {}", e),
+    }
+
+    // The same summary broken down by role and by department.
+    if let Ok(by_role) = stats_by_role(&everyone) {
+        for (role, stats) in by_role {
+            println!("{:<10} {} (ratio {:.2})", role, stats, stats.compression_ratio());
+        }
+    }
+    if let Ok(by_department) = stats_by_department(&company) {
+        for (name, stats) in by_department {
+            println!("{:<12} median {}, sd {}", name, stats.median, stats.std_dev);
+        }
+    }
+}
+
+/// Demonstrates a role catalogue loaded from a file, with career levels and
+/// salary bands, and checking everyone's pay against it.
+fn demonstrate_role_catalog() {
+    println!("\n--- Role Catalogue Demonstration ---");
+
+    let catalog = match RoleCatalog::load(ROLE_CATALOG) {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    for definition in &catalog.roles {
+        println!("{}: {}", definition.role, definition.description);
+        for level in &definition.levels {
+            println!(
+                "  L{} {:<22} {} / {} / {}",
+                level.number, level.title, level.band.min, level.band.mid, level.band.max
+            );
+        }
+    }
+
+    // A role that only exists in the catalogue, not in the `Role` enum.
+    let hire = Employee {
+        id: 809,
+        name: String::from("Vera"),
+        role: Role::from_name("Data Scientist"),
+        salary: usd(165000),
+        manager_id: Some(801),
+        level: 2,
+    };
+    if let (Some(definition), Some(level)) =
+        (catalog.role(&hire.role), catalog.level(&hire.role, hire.level))
+    {
+        println!("{} is a {}: {}", hire.name, level.title, definition.description);
+    }
+
+    let mut company = sample_company();
+    company.employees.push(hire);
+    let issues = catalog.validate(company.all_employees());
+    println!("{} employee(s) paid outside their band:", issues.len());
+    for issue in issues {
+        println!("  {}", issue);
+    }
+}
+
+/// Demonstrates importing a department tree from CSV, exporting it as JSON,
+/// and the line-numbered errors reported for bad input.
+fn demonstrate_import_export() {
+    println!("\n--- Import and Export Demonstration ---");
+
+    // Every role in the catalogue is accepted, including custom ones.
+    let roles: Vec<Role> = match RoleCatalog::load(ROLE_CATALOG) {
+        Ok(catalog) => catalog.roles.into_iter().map(|d| d.role).collect(),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/data/company.csv");
+    let company = match Department::load(path, &roles) {
+        Ok(company) => company,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    println!(
+        "Imported {} with {} employees",
+        company.name,
+        company.all_employees().len()
+    );
+
+    // Exporting and importing again gives back the same tree.
+    let json = company.to_json();
+    match Department::from_json(&json, &roles) {
+        Ok(again) => println!(
+            "JSON round trip unchanged: {}",
+            again.to_csv().ok() == company.to_csv().ok()
+        ),
+        Err(e) => println!("{}", e),
+    }
+    if let Some(research) = company.find_department("Research") {
+        print!("{}", research.to_csv().unwrap_or_default());
+    }
+
+    // Problems are reported with the line they were found on.
+    let bad = "department,id,name,role,level,salary,currency,manager_id
+Initech,1,Bill,Manager,1,120000,USD,
+Initech,2,Peter,Developer,1,-5,USD,1
+";
+    if let Err(e) = Department::from_csv(bad, &roles) {
+        println!("{}", e);
+    }
+    let duplicate = r#"{"name": "Initech", "employees": [
+  {"id": 1, "name": "Bill", "role": "Manager", "level": 1,
+   "salary": "120000", "currency": "USD", "manager_id": null},
+  {"id": 1, "name": "Milton", "role": "Developer", "level": 1,
+   "salary": "0", "currency": "USD", "manager_id": 1}
+]}"#;
+    if let Err(e) = Department::from_json(duplicate, &roles) {
+        println!("{}", e);
+    }
+}
+
+/// Demonstrates staffing projects: assignments, over-allocation, remaining
+/// capacity and labour cost.
+fn demonstrate_project_staffing() {
+    println!("\n--- Project Staffing Demonstration ---");
+
+    let company = sample_company();
+    let date =
+        |y: i32, m: u32, d: u32| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap_or_default();
+    let mut staffing = Staffing::new();
+    let projects = [
+        (Project(String::from("Apollo"), 12), date(2025, 1, 1)),
+        (Project(String::from("Gemini"), 6), date(2025, 3, 1)),
+    ];
+    for (project, start) in projects {
+        if let Err(e) = staffing.add_project(project, start) {
+            println!("{}", e);
+        }
+    }
+
+    let assignments = [
+        (803, "Apollo", 60, date(2025, 1, 1), date(2025, 12, 31)),
+        (803, "Gemini", 50, date(2025, 3, 1), date(2025, 5, 31)),
+        (804, "Apollo", 100, date(2025, 1, 1), date(2025, 6, 30)),
+        (805, "Gemini", 50, date(2025, 3, 1), date(2025, 8, 31)),
+        // Rejected: Gemini ends in August.
+        (806, "Gemini", 50, date(2025, 6, 1), date(2025, 12, 31)),
+    ];
+    for (employee_id, project, allocation, start, end) in assignments {
+        let assignment = Assignment {
+            employee_id,
+            project: String::from(project),
+            allocation,
+            start,
+            end,
+        };
+        if let Err(e) = staffing.assign(assignment) {
+            println!("Cannot assign {} to {}: {}", employee_id, project, e);
+        }
+    }
+
+    for planned in &staffing.projects {
+        println!(
+            "{} ({} months, {} to {})",
+            planned.project.0,
+            planned.project.1,
+            planned.start,
+            planned.end()
+        );
+    }
+    for over in staffing.over_allocations() {
+        println!("Over-allocated: {}", over);
+    }
+
+    let (from, to) = (date(2025, 4, 1), date(2025, 6, 30));
+    println!(
+        "Priya is booked at most {}% in Q2, leaving {}%",
+        staffing.peak_allocation(803, from, to),
+        staffing.remaining_capacity(803, from, to)
+    );
+    for (role, free) in staffing.capacity_by_role(company.all_employees(), from, to) {
+        println!("Free {} capacity in Q2: {:.1} people", role, free as f64 / 100.0);
+    }
+    for name in ["Apollo", "Gemini"] {
+        match staffing.labour_cost(name, &company) {
+            Ok(cost) => println!("{} labour cost: {}", name, cost),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+/// Demonstrates effective-dated pay history: a back-dated promotion, a
+/// correction to it, and the payroll adjustments each one causes.
+fn demonstrate_compensation_history() {
+    println!("\n--- Compensation History Demonstration ---");
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/payroll.toml");
+    let mut payroll = match PayrollConfig::load(path) {
+        Ok(config) => Payroll::new(config),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let date =
+        |y: i32, m: u32, d: u32| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap_or_default();
+
+    let mut company = sample_company();
+    let mut history = History::new();
+    for emp in company.all_employees() {
+        if let Err(e) = history.start(emp, date(2025, 1, 1)) {
+            println!("{}", e);
+        }
+    }
+    let Some(quinn) = company.find_employee(804).cloned() else {
+        return;
+    };
+
+    // January to April are paid at the old rate.
+    for period in payroll.config().periods(2025).iter().take(4) {
+        if let Err(e) = payroll.pay(&quinn, period) {
+            println!("{}", e);
+        }
+    }
+
+    // In May, a promotion is recorded that took effect in mid-March.
+    let promotion = Compensation {
+        effective: date(2025, 3, 16),
+        salary: usd(105000),
+        role: Role::Developer,
+        level: 2,
+        reason: String::from("Promotion"),
+    };
+    let corrected = Compensation {
+        salary: usd(107000),
+        reason: String::from("Promotion, corrected"),
+        ..promotion.clone()
+    };
+    if let Err(e) = history.record(quinn.id, promotion) {
+        println!("{}", e);
+    }
+    let show = |adjustments: Result<Vec<_>, _>| match adjustments {
+        Ok(adjustments) => {
+            for adjustment in adjustments {
+                println!("  {}", adjustment);
+            }
+        }
+        Err(e) => println!("  {}", e),
+    };
+    println!("Back pay after the promotion:");
+    show(history.recalculate_payroll(&mut payroll, &quinn, 2025));
+
+    // The raise was keyed wrongly; correcting it adjusts the same periods again.
+    if let Err(e) = history.correct(quinn.id, corrected) {
+        println!("{}", e);
+    }
+    println!("Adjustments after the correction:");
+    show(history.recalculate_payroll(&mut payroll, &quinn, 2025));
+
+    for entry in history.entries(quinn.id) {
+        println!("{}", entry);
+    }
+    for correction in &history.corrections {
+        println!(
+            "Corrected {} -> {} for employee {}",
+            correction.before.salary, correction.after.salary, correction.employee_id
+        );
+    }
+    for day in [date(2025, 3, 15), date(2025, 3, 16)] {
+        if let Some(salary) = history.salary_on(quinn.id, day) {
+            println!("Quinn's salary on {}: {}", day, salary);
+        }
+    }
+    if let Some(ytd) = payroll.year_to_date(quinn.id, 2025) {
+        println!("Quinn's pay so far this year: {} gross, {} net", ytd.gross, ytd.net);
+    }
+
+    // Bring the company's current records in line with the history.
+    history.apply(&mut company, date(2025, 5, 1));
+    if let Some(quinn) = company.find_employee(804) {
+        println!(
+            "Now: {} is a level {} {} on {}",
+            quinn.name, quinn.level, quinn.role, quinn.salary
+        );
+    }
+}
+
+/// Demonstrates leave: accrual by role and tenure, requests approved by the
+/// employee's manager, balances with carry-over, and a team absence calendar.
+fn demonstrate_leave() {
+    println!("\n--- Leave Demonstration ---");
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/leave.toml");
+    let mut book = match LeavePolicy::load(path) {
+        Ok(policy) => LeaveBook::new(policy),
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let date =
+        |y: i32, m: u32, d: u32| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap_or_default();
+
+    let company = sample_company();
+    let hired = [
+        (801, date(2015, 4, 1)),
+        (802, date(2018, 1, 8)),
+        (803, date(2019, 9, 2)),
+        (804, date(2023, 2, 1)),
+        (805, date(2021, 5, 3)),
+        (806, date(2024, 6, 17)),
+        (807, date(2020, 10, 5)),
+        (808, date(2022, 3, 1)),
+    ];
+    for (id, day) in hired {
+        book.enroll(id, day);
+    }
+
+    // (employee, leave, first day, last day, approver); `None` means turned down.
+    let requests = [
+        (803, LeaveType::Vacation, date(2025, 7, 14), date(2025, 7, 18), Some(802)),
+        (804, LeaveType::Sick, date(2025, 7, 16), date(2025, 7, 16), Some(802)),
+        // Sam reports to Rosa, so Oscar cannot approve this one.
+        (806, LeaveType::Vacation, date(2025, 7, 15), date(2025, 7, 17), Some(802)),
+        (805, LeaveType::Parental, date(2025, 7, 21), date(2025, 8, 29), Some(802)),
+        (808, LeaveType::Vacation, date(2025, 8, 1), date(2025, 8, 29), Some(807)),
+        (807, LeaveType::Vacation, date(2025, 7, 14), date(2025, 7, 15), None),
+    ];
+    for (employee_id, leave, start, end, approver) in requests {
+        let Some(emp) = company.find_employee(employee_id) else {
+            continue;
+        };
+        let id = match book.request(emp, leave, start, end) {
+            Ok(id) => id,
+            Err(e) => {
+                println!("{} cannot take {} leave: {}", emp.name, leave, e);
+                continue;
+            }
+        };
+        let decision = match approver {
+            Some(manager) => book.approve(&company, id, manager),
+            None => emp
+                .manager_id
+                .map_or(Ok(()), |manager| book.reject(&company, id, manager)),
+        };
+        if let Err(e) = decision {
+            println!("Request {} by {}: {}", id, emp.name, e);
+        }
+    }
+    // Rosa approves Sam's request herself.
+    if let Some(request) = book.requests.iter().find(|r| r.employee_id == 806) {
+        let id = request.id;
+        if let Err(e) = book.approve(&company, id, 805) {
+            println!("{}", e);
+        }
+    }
+    for request in &book.requests {
+        println!(
+            "Request {}: employee {} {} {} to {}, {:?}",
+            request.id,
+            request.employee_id,
+            request.leave,
+            request.start,
+            request.end,
+            request.status
+        );
+    }
+
+    let today = date(2025, 7, 15);
+    for id in [803, 806] {
+        if let Some(emp) = company.find_employee(id) {
+            match book.balance(emp, LeaveType::Vacation, today) {
+                Ok(balance) => println!("{}'s vacation on {}: {}", emp.name, today, balance),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+    if let Some(umar) = company.find_employee(808) {
+        if let Ok(balance) = book.balance(umar, LeaveType::Vacation, today) {
+            println!(
+                "{} has {} whole vacation days to use",
+                umar.name,
+                balance.available() / MINUTES_PER_DAY
+            );
+        }
+    }
+    if let Some(engineering) = company.find_department("Engineering") {
+        println!("{}", book.calendar(engineering, date(2025, 7, 14), date(2025, 7, 25)));
+    }
+}
+
 fn main() {
     // Demonstrating Tuples
     demonstrate_tuples();
@@ -313,6 +1036,36 @@ fn main() {
 
     // Demonstrating Combination of Compound Types
     combine_compound_types();
+
+    // Demonstrating Exact Money Arithmetic
+    demonstrate_money();
+
+    // Demonstrating Payroll
+    demonstrate_payroll();
+
+    // Demonstrating Reporting Lines and Nested Departments
+    demonstrate_org_hierarchy();
+
+    // Demonstrating Org Chart Export
+    demonstrate_org_chart();
+
+    // Demonstrating Salary Statistics
+    demonstrate_salary_statistics();
+
+    // Demonstrating the Role Catalogue
+    demonstrate_role_catalog();
+
+    // Demonstrating Import and Export
+    demonstrate_import_export();
+
+    // Demonstrating Project Staffing
+    demonstrate_project_staffing();
+
+    // Demonstrating Compensation History
+    demonstrate_compensation_history();
+
+    // Demonstrating Leave
+    demonstrate_leave();
 }
 
 // Implementing Clone for Department to allow cloning in pattern matching.
@@ -321,6 +1074,7 @@ impl Clone for Department {
         Department {
             name: self.name.clone(),
             employees: self.employees.clone(),
+            sub_departments: self.sub_departments.clone(),
         }
     }
 }
@@ -340,18 +1094,22 @@ mod tests {
                 id: 501,
                 name: String::from("Ivan"),
                 role: Role::Developer,
-                salary: 70000.0,
+                salary: usd(70000),
+                manager_id: None,
+                level: 1,
             },
             Employee {
                 id: 502,
                 name: String::from("Judy"),
                 role: Role::Designer,
-                salary: 75000.0,
+                salary: usd(75000),
+                manager_id: None,
+                level: 1,
             },
         ];
         let avg = calculate_average_salary(&employees);
         assert!(avg.is_some());
-        assert_eq!(avg.unwrap(), 72500.0);
+        assert_eq!(avg.unwrap(), usd(72500));
     }
 
     #[test]
@@ -360,11 +1118,14 @@ mod tests {
             id: 601,
             name: String::from("Karl"),
             role: Role::Manager,
-            salary: 95000.0,
+            salary: usd(95000),
+            manager_id: None,
+            level: 1,
         };
         let dept = Department {
             name: String::from("Marketing"),
             employees: vec![emp],
+            sub_departments: Vec::new(),
         };
         // This test ensures that the function runs without panicking.
         print_department_info(&dept);
@@ -375,9 +1136,54 @@ mod tests {
         let role_dev = Role::Developer;
         let role_mgr = Role::Manager;
         let role_des = Role::Designer;
+        let role_data = Role::from_name("Data Scientist");
+
+        // Without a catalogue, built-in roles keep their own text.
+        assert_eq!(role_dev.description(None), "Writes and maintains code.");
+        assert_eq!(role_mgr.description(None), "Oversees team operations.");
+        assert_eq!(
+            role_des.description(None),
+            "Designs user interfaces and experiences."
+        );
+
+        let catalog = RoleCatalog::from_toml(
+            r#"
+currency = "USD"
 
-        assert_eq!(role_dev.description(), "Writes and maintains code.");
-        assert_eq!(role_mgr.description(), "Oversees team operations.");
-        assert_eq!(role_des.description(), "Designs user interfaces and experiences.");
+[[roles]]
+name = "Developer"
+description = "Ships features."
+
+[[roles.levels]]
+title = "Developer"
+min = "70000"
+mid = "85000"
+max = "100000"
+
+[[roles]]
+name = "Data Scientist"
+description = "Builds models from company data."
+
+[[roles.levels]]
+title = "Data Scientist"
+min = "80000"
+mid = "100000"
+max = "120000"
+"#,
+        )
+        .unwrap();
+        assert_eq!(role_dev.description(Some(&catalog)), "Ships features.");
+        assert_eq!(
+            role_mgr.description(Some(&catalog)),
+            "Oversees team operations."
+        );
+        assert_eq!(
+            role_data.description(Some(&catalog)),
+            "Builds models from company data."
+        );
+        assert_eq!(
+            Role::from_name("Astronaut").description(Some(&catalog)),
+            "Not described in the role catalogue."
+        );
     }
 }
\ No newline at end of file
@@ -0,0 +1,463 @@
+// Exact amounts of money, stored as a whole number of minor units (cents)
+// together with their currency, so salaries never pick up float rounding.
+
+use std::fmt;
+use std::str::FromStr;
+
+// ----------------------------
+// Currencies and Locales
+// ----------------------------
+
+// The `Currency` enum lists the ISO 4217 currencies the demo knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Usd,
+    Eur,
+    Gbp,
+    Jpy,
+}
+
+impl Currency {
+    /// The three-letter ISO 4217 code, e.g. "USD".
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Eur => "EUR",
+            Currency::Gbp => "GBP",
+            Currency::Jpy => "JPY",
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::Usd => "$",
+            Currency::Eur => "€",
+            Currency::Gbp => "£",
+            Currency::Jpy => "¥",
+        }
+    }
+
+    /// How many digits follow the decimal point: 2 for cents, 0 for yen.
+    pub fn minor_digits(&self) -> u32 {
+        match self {
+            Currency::Jpy => 0,
+            _ => 2,
+        }
+    }
+
+    /// Minor units in one major unit, e.g. 100 cents in a dollar.
+    pub fn minor_per_major(&self) -> i64 {
+        10_i64.pow(self.minor_digits())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        match code.trim().to_ascii_uppercase().as_str() {
+            "USD" => Ok(Currency::Usd),
+            "EUR" => Ok(Currency::Eur),
+            "GBP" => Ok(Currency::Gbp),
+            "JPY" => Ok(Currency::Jpy),
+            _ => Err(MoneyError::UnknownCurrency(code.to_string())),
+        }
+    }
+}
+
+// The `Locale` struct describes how a region writes amounts of money.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub group_separator: &'static str,
+    pub decimal_separator: &'static str,
+    pub symbol_first: bool,
+    // Whether a space separates the symbol from the number.
+    pub symbol_spaced: bool,
+}
+
+impl Locale {
+    /// `$1,234.56`
+    pub fn en_us() -> Self {
+        Locale {
+            group_separator: ",",
+            decimal_separator: ".",
+            symbol_first: true,
+            symbol_spaced: false,
+        }
+    }
+
+    /// `1.234,56 €`
+    pub fn de_de() -> Self {
+        Locale {
+            group_separator: ".",
+            decimal_separator: ",",
+            symbol_first: false,
+            symbol_spaced: true,
+        }
+    }
+
+    /// `1 234,56 €`, grouped with a narrow no-break space.
+    pub fn fr_fr() -> Self {
+        Locale {
+            group_separator: "\u{202f}",
+            decimal_separator: ",",
+            symbol_first: false,
+            symbol_spaced: true,
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::en_us()
+    }
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+// The `MoneyError` enum lists everything that can go wrong with money arithmetic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    CurrencyMismatch(Currency, Currency),
+    Overflow,
+    DivisionByZero,
+    UnknownCurrency(String),
+    InvalidAmount(String),
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::CurrencyMismatch(a, b) => write!(f, "cannot combine {} with {}", a, b),
+            MoneyError::Overflow => write!(f, "amount is too large"),
+            MoneyError::DivisionByZero => write!(f, "division by zero"),
+            MoneyError::UnknownCurrency(code) => write!(f, "unknown currency {:?}", code),
+            MoneyError::InvalidAmount(text) => write!(f, "invalid amount {:?}", text),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+// ----------------------------
+// Money
+// ----------------------------
+
+// The `Money` struct is an amount in minor units of one currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    /// An amount given in minor units, e.g. `Money::new(1999, Currency::Usd)` is $19.99.
+    pub fn new(minor: i64, currency: Currency) -> Self {
+        Money { minor, currency }
+    }
+
+    /// An amount given in whole major units, e.g. dollars. Fails with
+    /// `Overflow` if it is too large to hold in minor units.
+    pub fn from_major(major: i64, currency: Currency) -> Result<Self, MoneyError> {
+        Money::new(major, currency).checked_mul(currency.minor_per_major())
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Parses a plain decimal such as "72500" or "-19.99" in the given currency.
+    /// More decimal places than the currency has are rejected.
+    pub fn parse(text: &str, currency: Currency) -> Result<Self, MoneyError> {
+        let invalid = || MoneyError::InvalidAmount(text.to_string());
+        let trimmed = text.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+        let places = currency.minor_digits() as usize;
+        if fraction.len() > places {
+            return Err(invalid());
+        }
+        let whole: i64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: i64 = format!("{:0<width$}", fraction, width = places)
+            .parse()
+            .unwrap_or(0);
+        let minor = whole
+            .checked_mul(currency.minor_per_major())
+            .and_then(|m| m.checked_add(fraction))
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(if negative { -minor } else { minor }, currency))
+    }
+
+    /// The amount as a plain decimal such as "72500.00", the form `parse` reads.
+    pub fn to_decimal(self) -> String {
+        let per_major = self.currency.minor_per_major().unsigned_abs();
+        let magnitude = self.minor.unsigned_abs();
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.currency.minor_digits() as usize;
+        if digits == 0 {
+            return format!("{}{}", sign, magnitude);
+        }
+        format!(
+            "{}{}.{:0width$}",
+            sign,
+            magnitude / per_major,
+            magnitude % per_major,
+            width = digits
+        )
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor < 0
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch(self.currency, other.currency))
+        }
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let minor = self
+            .minor
+            .checked_add(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let minor = self
+            .minor
+            .checked_sub(other.minor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money, MoneyError> {
+        let minor = self.minor.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    /// Multiplies by `numerator / denominator`, rounding half to even.
+    /// A 7.65% rate is `mul_ratio(765, 10_000)`.
+    pub fn mul_ratio(self, numerator: i64, denominator: i64) -> Result<Money, MoneyError> {
+        if denominator == 0 {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let exact = self.minor as i128 * numerator as i128;
+        let minor = round_half_even(exact, denominator as i128);
+        let minor = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::new(minor, self.currency))
+    }
+
+    /// Divides into a single amount, rounding half to even. Use `allocate`
+    /// when the parts must add back up to the whole.
+    pub fn checked_div(self, divisor: i64) -> Result<Money, MoneyError> {
+        self.mul_ratio(1, divisor)
+    }
+
+    /// Splits the amount in proportion to `ratios` without losing a cent: the
+    /// leftover minor units go one each to the first parts with a non-zero
+    /// ratio, so a zero ratio always gets nothing.
+    pub fn allocate(self, ratios: &[u32]) -> Result<Vec<Money>, MoneyError> {
+        let total: i128 = ratios.iter().map(|&r| r as i128).sum();
+        if total == 0 {
+            return Err(MoneyError::DivisionByZero);
+        }
+        let mut parts: Vec<i64> = ratios
+            .iter()
+            .map(|&r| (self.minor as i128 * r as i128 / total) as i64)
+            .collect();
+        let mut remainder = self.minor - parts.iter().sum::<i64>();
+        let step = remainder.signum();
+        for (part, _) in parts.iter_mut().zip(ratios).filter(|(_, &r)| r > 0) {
+            if remainder == 0 {
+                break;
+            }
+            *part += step;
+            remainder -= step;
+        }
+        Ok(parts
+            .into_iter()
+            .map(|minor| Money::new(minor, self.currency))
+            .collect())
+    }
+
+    /// Splits into `parts` amounts as equal as possible.
+    pub fn split(self, parts: usize) -> Result<Vec<Money>, MoneyError> {
+        self.allocate(&vec![1; parts])
+    }
+
+    /// Adds up amounts that must all be in `currency`.
+    pub fn sum<'a, I>(amounts: I, currency: Currency) -> Result<Money, MoneyError>
+    where
+        I: IntoIterator<Item = &'a Money>,
+    {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency), |total, amount| {
+                total.checked_add(*amount)
+            })
+    }
+
+    /// Formats the amount the way `locale` writes money, with symbol and grouping.
+    pub fn format(&self, locale: &Locale) -> String {
+        let per_major = self.currency.minor_per_major().unsigned_abs();
+        let magnitude = self.minor.unsigned_abs();
+        let whole = (magnitude / per_major).to_string();
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.push_str(locale.group_separator);
+            }
+            grouped.push(digit);
+        }
+        let digits = self.currency.minor_digits() as usize;
+        if digits > 0 {
+            grouped.push_str(locale.decimal_separator);
+            grouped.push_str(&format!(
+                "{:0width$}",
+                magnitude % per_major,
+                width = digits
+            ));
+        }
+
+        let space = if locale.symbol_spaced { " " } else { "" };
+        let sign = if self.is_negative() { "-" } else { "" };
+        let symbol = self.currency.symbol();
+        if locale.symbol_first {
+            format!("{}{}{}{}", sign, symbol, space, grouped)
+        } else {
+            format!("{}{}{}{}", sign, grouped, space, symbol)
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(&Locale::en_us()))
+    }
+}
+
+/// `numerator / denominator` rounded to the nearest integer, ties to even.
+fn round_half_even(numerator: i128, denominator: i128) -> i128 {
+    let (numerator, denominator) = if denominator < 0 {
+        (-numerator, -denominator)
+    } else {
+        (numerator, denominator)
+    };
+    let quotient = numerator.div_euclid(denominator);
+    let remainder = numerator.rem_euclid(denominator);
+    match (2 * remainder).cmp(&denominator) {
+        std::cmp::Ordering::Less => quotient,
+        std::cmp::Ordering::Greater => quotient + 1,
+        std::cmp::Ordering::Equal => quotient + (quotient & 1),
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding_is_half_to_even() {
+        let usd = |minor| Money::new(minor, Currency::Usd);
+        assert_eq!(usd(5).checked_div(2), Ok(usd(2)));
+        assert_eq!(usd(15).checked_div(2), Ok(usd(8)));
+        assert_eq!(usd(-5).checked_div(2), Ok(usd(-2)));
+        assert_eq!(usd(-15).checked_div(2), Ok(usd(-8)));
+        assert_eq!(usd(1001).mul_ratio(1, 3), Ok(usd(334)));
+    }
+
+    #[test]
+    fn test_allocation_keeps_every_cent() {
+        let hundred = Money::new(100, Currency::Usd);
+        let parts: Vec<i64> = hundred
+            .split(3)
+            .unwrap()
+            .iter()
+            .map(|m| m.minor())
+            .collect();
+        assert_eq!(parts, vec![34, 33, 33]);
+
+        let owed = Money::new(-100, Currency::Usd);
+        let parts = owed.allocate(&[1, 1, 1]).unwrap();
+        assert_eq!(Money::sum(&parts, Currency::Usd), Ok(owed));
+        assert_eq!(hundred.allocate(&[]), Err(MoneyError::DivisionByZero));
+
+        let parts: Vec<i64> = Money::new(101, Currency::Usd)
+            .allocate(&[0, 1, 1])
+            .unwrap()
+            .iter()
+            .map(|m| m.minor())
+            .collect();
+        assert_eq!(parts, vec![0, 51, 50]);
+    }
+
+    #[test]
+    fn test_checked_arithmetic_and_formatting() {
+        let dollars = Money::parse("72500", Currency::Usd).unwrap();
+        let euros = Money::parse("1234567.5", Currency::Eur).unwrap();
+        assert_eq!(
+            dollars.checked_add(euros),
+            Err(MoneyError::CurrencyMismatch(Currency::Usd, Currency::Eur))
+        );
+        assert_eq!(
+            Money::new(i64::MAX, Currency::Usd).checked_add(Money::new(1, Currency::Usd)),
+            Err(MoneyError::Overflow)
+        );
+        assert!(Money::parse("1.999", Currency::Usd).is_err());
+
+        assert_eq!(dollars.to_string(), "$72,500.00");
+        assert_eq!(euros.format(&Locale::de_de()), "1.234.567,50 €");
+        assert_eq!(
+            euros.format(&Locale::fr_fr()),
+            "1\u{202f}234\u{202f}567,50 €"
+        );
+        assert_eq!(
+            Money::from_major(-1500, Currency::Jpy).unwrap().to_string(),
+            "-¥1,500"
+        );
+        assert_eq!(Money::new(-5, Currency::Usd).to_decimal(), "-0.05");
+        assert_eq!(euros.to_decimal(), "1234567.50");
+        assert_eq!(
+            Money::from_major(1500, Currency::Jpy).unwrap().to_decimal(),
+            "1500"
+        );
+        assert_eq!(
+            Money::from_major(i64::MAX / 10, Currency::Usd),
+            Err(MoneyError::Overflow)
+        );
+    }
+}
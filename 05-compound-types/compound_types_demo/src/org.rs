@@ -0,0 +1,377 @@
+// Reporting lines and nested departments: who reports to whom, chains of
+// command, and headcount and salary totals for any part of the organisation.
+
+use crate::money::{Currency, Money, MoneyError};
+use crate::{Department, Employee};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrgError {
+    UnknownEmployee(i32),
+    DuplicateEmployee(i32),
+    // An employee cannot manage themselves.
+    SelfManagement(i32),
+    // Following managers from the first ID leads back to it.
+    Cycle(Vec<i32>),
+    Money(MoneyError),
+}
+
+impl fmt::Display for OrgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrgError::UnknownEmployee(id) => write!(f, "no employee with ID {}", id),
+            OrgError::DuplicateEmployee(id) => write!(f, "employee ID {} appears twice", id),
+            OrgError::SelfManagement(id) => write!(f, "employee {} cannot manage themselves", id),
+            OrgError::Cycle(ids) => {
+                let path: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "reporting cycle: {}", path.join(" -> "))
+            }
+            OrgError::Money(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OrgError {}
+
+impl From<MoneyError> for OrgError {
+    fn from(e: MoneyError) -> Self {
+        OrgError::Money(e)
+    }
+}
+
+// ----------------------------
+// Rollups
+// ----------------------------
+
+// Headcount and salary for a department including everything nested inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rollup {
+    pub name: String,
+    pub headcount: usize,
+    pub salary: Money,
+    pub sub_departments: Vec<Rollup>,
+}
+
+impl Rollup {
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(
+            f,
+            "{:indent$}{}: {} people, {}",
+            "",
+            self.name,
+            self.headcount,
+            self.salary,
+            indent = depth * 2
+        )?;
+        for child in &self.sub_departments {
+            writeln!(f)?;
+            child.write_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rollup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+// ----------------------------
+// Queries on the Department Tree
+// ----------------------------
+
+// A department's methods treat it as the top of the organisation: reporting
+// lines can cross into any department nested inside it.
+impl Department {
+    /// Everyone in this department and its sub-departments, department by department.
+    pub fn all_employees(&self) -> Vec<&Employee> {
+        let mut all: Vec<&Employee> = self.employees.iter().collect();
+        for sub in &self.sub_departments {
+            all.extend(sub.all_employees());
+        }
+        all
+    }
+
+    pub fn find_employee(&self, id: i32) -> Option<&Employee> {
+        self.all_employees().into_iter().find(|e| e.id == id)
+    }
+
+    fn find_employee_mut(&mut self, id: i32) -> Option<&mut Employee> {
+        if let Some(emp) = self.employees.iter_mut().find(|e| e.id == id) {
+            return Some(emp);
+        }
+        self.sub_departments
+            .iter_mut()
+            .find_map(|sub| sub.find_employee_mut(id))
+    }
+
+    /// The department with this name, searching nested departments too.
+    pub fn find_department(&self, name: &str) -> Option<&Department> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.sub_departments
+            .iter()
+            .find_map(|sub| sub.find_department(name))
+    }
+
+    /// People who report straight to `manager_id`, ordered by ID.
+    pub fn direct_reports(&self, manager_id: i32) -> Vec<&Employee> {
+        let mut reports: Vec<&Employee> = self
+            .all_employees()
+            .into_iter()
+            .filter(|e| e.manager_id == Some(manager_id))
+            .collect();
+        reports.sort_by_key(|e| e.id);
+        reports
+    }
+
+    /// Everyone below `manager_id`, nearest first.
+    pub fn all_reports(&self, manager_id: i32) -> Vec<&Employee> {
+        let mut by_manager: HashMap<i32, Vec<&Employee>> = HashMap::new();
+        for emp in self.all_employees() {
+            if let Some(manager) = emp.manager_id {
+                by_manager.entry(manager).or_default().push(emp);
+            }
+        }
+        let mut seen = HashSet::from([manager_id]);
+        let mut queue = VecDeque::from([manager_id]);
+        let mut reports = Vec::new();
+        while let Some(id) = queue.pop_front() {
+            let mut direct = by_manager.remove(&id).unwrap_or_default();
+            direct.sort_by_key(|e| e.id);
+            for emp in direct {
+                // A cycle would otherwise bring the manager back round.
+                if seen.insert(emp.id) {
+                    queue.push_back(emp.id);
+                    reports.push(emp);
+                }
+            }
+        }
+        reports
+    }
+
+    /// How many people report straight to `manager_id`.
+    pub fn span_of_control(&self, manager_id: i32) -> usize {
+        self.direct_reports(manager_id).len()
+    }
+
+    /// The managers above an employee, from their own manager to the top.
+    pub fn chain_of_command(&self, employee_id: i32) -> Result<Vec<&Employee>, OrgError> {
+        let mut current = self
+            .find_employee(employee_id)
+            .ok_or(OrgError::UnknownEmployee(employee_id))?;
+        let mut path = vec![employee_id];
+        let mut chain = Vec::new();
+        while let Some(manager_id) = current.manager_id {
+            if path.contains(&manager_id) {
+                path.push(manager_id);
+                return Err(OrgError::Cycle(path));
+            }
+            current = self
+                .find_employee(manager_id)
+                .ok_or(OrgError::UnknownEmployee(manager_id))?;
+            path.push(manager_id);
+            chain.push(current);
+        }
+        Ok(chain)
+    }
+
+    /// Makes `employee_id` report to `manager_id`, or to nobody with `None`.
+    /// Changes that would create a reporting cycle are refused.
+    pub fn set_manager(
+        &mut self,
+        employee_id: i32,
+        manager_id: Option<i32>,
+    ) -> Result<(), OrgError> {
+        if self.find_employee(employee_id).is_none() {
+            return Err(OrgError::UnknownEmployee(employee_id));
+        }
+        if let Some(manager_id) = manager_id {
+            if manager_id == employee_id {
+                return Err(OrgError::SelfManagement(employee_id));
+            }
+            // The new manager must not already sit below the employee.
+            let mut path = vec![employee_id, manager_id];
+            let mut current = self
+                .find_employee(manager_id)
+                .ok_or(OrgError::UnknownEmployee(manager_id))?;
+            while let Some(next) = current.manager_id {
+                if path.contains(&next) {
+                    path.push(next);
+                    return Err(OrgError::Cycle(path));
+                }
+                path.push(next);
+                current = self
+                    .find_employee(next)
+                    .ok_or(OrgError::UnknownEmployee(next))?;
+            }
+        }
+        if let Some(emp) = self.find_employee_mut(employee_id) {
+            emp.manager_id = manager_id;
+        }
+        Ok(())
+    }
+
+    /// Checks the whole tree: IDs are unique, managers exist and nobody
+    /// reports to themselves, directly or round a loop.
+    pub fn validate(&self) -> Result<(), OrgError> {
+        let mut ids = HashSet::new();
+        for emp in self.all_employees() {
+            if !ids.insert(emp.id) {
+                return Err(OrgError::DuplicateEmployee(emp.id));
+            }
+        }
+        for emp in self.all_employees() {
+            match emp.manager_id {
+                Some(manager) if manager == emp.id => return Err(OrgError::SelfManagement(emp.id)),
+                Some(manager) if !ids.contains(&manager) => {
+                    return Err(OrgError::UnknownEmployee(manager))
+                }
+                _ => {}
+            }
+            self.chain_of_command(emp.id)?;
+        }
+        Ok(())
+    }
+
+    /// Headcount and salary for this department and each nested one.
+    pub fn rollup(&self, currency: Currency) -> Result<Rollup, OrgError> {
+        let sub_departments = self
+            .sub_departments
+            .iter()
+            .map(|sub| sub.rollup(currency))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut salary = Money::sum(self.employees.iter().map(|e| &e.salary), currency)?;
+        let mut headcount = self.employees.len();
+        for sub in &sub_departments {
+            salary = salary.checked_add(sub.salary)?;
+            headcount += sub.headcount;
+        }
+        Ok(Rollup {
+            name: self.name.clone(),
+            headcount,
+            salary,
+            sub_departments,
+        })
+    }
+
+    /// Headcount and salary of a manager's whole team, the manager included.
+    pub fn team_rollup(
+        &self,
+        manager_id: i32,
+        currency: Currency,
+    ) -> Result<(usize, Money), OrgError> {
+        let manager = self
+            .find_employee(manager_id)
+            .ok_or(OrgError::UnknownEmployee(manager_id))?;
+        let mut team = self.all_reports(manager_id);
+        team.push(manager);
+        let salary = Money::sum(team.iter().map(|e| &e.salary), currency)?;
+        Ok((team.len(), salary))
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{employee, usd};
+    use crate::Role;
+
+    // 1 runs the company; 2 leads engineering, where 3 leads a platform team of 4 and 5.
+    fn company() -> Department {
+        Department {
+            name: String::from("Company"),
+            employees: vec![employee(1, Role::Manager, 200000)],
+            sub_departments: vec![Department {
+                name: String::from("Engineering"),
+                employees: vec![
+                    employee(2, Role::Manager, 150000).reporting_to(1),
+                    employee(6, Role::Designer, 90000).reporting_to(2),
+                ],
+                sub_departments: vec![Department {
+                    name: String::from("Platform"),
+                    employees: vec![
+                        employee(3, Role::Manager, 130000).reporting_to(2),
+                        employee(4, Role::Developer, 100000).reporting_to(3),
+                        employee(5, Role::Developer, 95000).reporting_to(3),
+                    ],
+                    sub_departments: Vec::new(),
+                }],
+            }],
+        }
+    }
+
+    fn ids(employees: &[&Employee]) -> Vec<i32> {
+        employees.iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn test_reporting_queries() {
+        let org = company();
+        assert!(org.validate().is_ok());
+        assert_eq!(ids(&org.direct_reports(2)), vec![3, 6]);
+        assert_eq!(ids(&org.all_reports(1)), vec![2, 3, 6, 4, 5]);
+        assert_eq!(org.span_of_control(3), 2);
+        assert_eq!(org.span_of_control(4), 0);
+        assert_eq!(ids(&org.chain_of_command(5).unwrap()), vec![3, 2, 1]);
+        assert!(matches!(
+            org.chain_of_command(99),
+            Err(OrgError::UnknownEmployee(99))
+        ));
+    }
+
+    #[test]
+    fn test_set_manager_refuses_cycles() {
+        let mut org = company();
+        assert_eq!(
+            org.set_manager(2, Some(4)),
+            Err(OrgError::Cycle(vec![2, 4, 3, 2]))
+        );
+        assert_eq!(
+            org.set_manager(3, Some(3)),
+            Err(OrgError::SelfManagement(3))
+        );
+        assert_eq!(
+            org.set_manager(3, Some(42)),
+            Err(OrgError::UnknownEmployee(42))
+        );
+
+        // Moving 5 under 6 is fine and shows up in the queries.
+        org.set_manager(5, Some(6)).unwrap();
+        assert_eq!(ids(&org.direct_reports(6)), vec![5]);
+        assert_eq!(ids(&org.chain_of_command(5).unwrap()), vec![6, 2, 1]);
+
+        // Data built by hand can still contain a loop; validation finds it.
+        org.find_employee_mut(1).unwrap().manager_id = Some(5);
+        assert!(matches!(org.validate(), Err(OrgError::Cycle(_))));
+        assert_eq!(org.all_reports(1).len(), 5);
+    }
+
+    #[test]
+    fn test_rollups() {
+        let org = company();
+        let rollup = org.rollup(Currency::Usd).unwrap();
+        assert_eq!(rollup.headcount, 6);
+        assert_eq!(rollup.salary, usd(765000));
+        let platform = &rollup.sub_departments[0].sub_departments[0];
+        assert_eq!(platform.headcount, 3);
+        assert_eq!(platform.salary, usd(325000));
+        assert_eq!(org.team_rollup(3, Currency::Usd), Ok((3, usd(325000))));
+        assert!(org.find_department("Platform").is_some());
+        assert!(matches!(
+            org.rollup(Currency::Eur),
+            Err(OrgError::Money(MoneyError::CurrencyMismatch(..)))
+        ));
+    }
+}
@@ -0,0 +1,879 @@
+// Runs payroll for employees: pay periods, gross-to-net pay with progressive
+// tax brackets and deductions read from a TOML file, year-to-date totals,
+// payslips and department summaries.
+
+use crate::money::{Currency, Money, MoneyError};
+use crate::{Department, Employee};
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+// ----------------------------
+// Configuration
+// ----------------------------
+
+// The `PayFrequency` enum says how often employees are paid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayFrequency {
+    Monthly,
+    Biweekly,
+}
+
+// A single pay period. Biweekly periods belong to the year their last day falls in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayPeriod {
+    pub year: i32,
+    // 1-based position within the year.
+    pub number: u32,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+// How much a deduction takes: a fixed amount each period or a share of gross pay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeductionAmount {
+    Fixed(Money),
+    // In basis points: 765 is 7.65%.
+    Percent(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deduction {
+    pub name: String,
+    pub amount: DeductionAmount,
+    // Pre-tax deductions reduce taxable pay.
+    pub pre_tax: bool,
+}
+
+// Income from `from` up to the next bracket is taxed at `rate` basis points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaxBracket {
+    pub from: Money,
+    pub rate: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayrollConfig {
+    pub currency: Currency,
+    pub frequency: PayFrequency,
+    // Start of any one biweekly period; the schedule repeats every 14 days from it.
+    pub first_period_start: NaiveDate,
+    pub tax_brackets: Vec<TaxBracket>,
+    pub deductions: Vec<Deduction>,
+}
+
+// The file layout, before amounts and rates are checked.
+#[derive(Deserialize)]
+struct RawConfig {
+    currency: String,
+    frequency: String,
+    first_period_start: String,
+    tax_brackets: Vec<RawBracket>,
+    #[serde(default)]
+    deductions: Vec<RawDeduction>,
+}
+
+#[derive(Deserialize)]
+struct RawBracket {
+    from: String,
+    rate: String,
+}
+
+#[derive(Deserialize)]
+struct RawDeduction {
+    name: String,
+    amount: Option<String>,
+    percent: Option<String>,
+    #[serde(default)]
+    pre_tax: bool,
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug)]
+pub enum PayrollError {
+    Io(std::io::Error),
+    Config(String),
+    Money(MoneyError),
+    AlreadyPaid { employee_id: i32, period: PayPeriod },
+    NotPaid { employee_id: i32, period: PayPeriod },
+    // The period is not one of the config's periods for its year.
+    InvalidPeriod(PayPeriod),
+    // The same employee id appears twice in one run.
+    DuplicateEmployee(i32),
+}
+
+impl fmt::Display for PayrollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayrollError::Io(e) => write!(f, "cannot read payroll config: {}", e),
+            PayrollError::Config(message) => write!(f, "invalid payroll config: {}", message),
+            PayrollError::Money(e) => write!(f, "{}", e),
+            PayrollError::AlreadyPaid {
+                employee_id,
+                period,
+            } => write!(
+                f,
+                "employee {} was already paid for period {} of {}",
+                employee_id, period.number, period.year
+            ),
+            PayrollError::NotPaid {
+                employee_id,
+                period,
+            } => write!(
+                f,
+                "employee {} has not been paid for period {} of {}",
+                employee_id, period.number, period.year
+            ),
+            PayrollError::InvalidPeriod(period) => write!(
+                f,
+                "period {} of {} ({} to {}) is not on the pay schedule",
+                period.number, period.year, period.start, period.end
+            ),
+            PayrollError::DuplicateEmployee(id) => {
+                write!(f, "employee {} is listed more than once", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PayrollError {}
+
+impl From<std::io::Error> for PayrollError {
+    fn from(e: std::io::Error) -> Self {
+        PayrollError::Io(e)
+    }
+}
+
+impl From<MoneyError> for PayrollError {
+    fn from(e: MoneyError) -> Self {
+        PayrollError::Money(e)
+    }
+}
+
+/// Parses a percentage such as "7.65" into basis points (765).
+fn parse_rate(text: &str) -> Result<i64, PayrollError> {
+    let invalid = || PayrollError::Config(format!("invalid rate {:?}", text));
+    let (whole, fraction) = text.trim().split_once('.').unwrap_or((text.trim(), ""));
+    // Digits only, so signs and negative rates are rejected.
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || fraction.len() > 2 || !all_digits(whole) || !all_digits(fraction) {
+        return Err(invalid());
+    }
+    let whole: i64 = whole.parse().map_err(|_| invalid())?;
+    let fraction: i64 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+    let rate = whole.checked_mul(100).ok_or_else(invalid)? + fraction;
+    if rate > 10_000 {
+        return Err(invalid());
+    }
+    Ok(rate)
+}
+
+impl PayrollConfig {
+    /// Reads and checks a TOML payroll config file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PayrollError> {
+        PayrollConfig::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, PayrollError> {
+        let raw: RawConfig =
+            toml::from_str(text).map_err(|e| PayrollError::Config(e.message().to_string()))?;
+        let currency: Currency = raw.currency.parse()?;
+        let frequency = match raw.frequency.as_str() {
+            "monthly" => PayFrequency::Monthly,
+            "biweekly" => PayFrequency::Biweekly,
+            other => {
+                return Err(PayrollError::Config(format!(
+                    "unknown pay frequency {:?}",
+                    other
+                )))
+            }
+        };
+        let first_period_start = NaiveDate::parse_from_str(&raw.first_period_start, "%Y-%m-%d")
+            .map_err(|e| PayrollError::Config(format!("first_period_start: {}", e)))?;
+
+        let mut tax_brackets = Vec::new();
+        for bracket in &raw.tax_brackets {
+            tax_brackets.push(TaxBracket {
+                from: Money::parse(&bracket.from, currency)?,
+                rate: parse_rate(&bracket.rate)?,
+            });
+        }
+        if tax_brackets.first().is_none_or(|b| b.from.minor() != 0) {
+            return Err(PayrollError::Config(
+                "the first tax bracket must start at 0".to_string(),
+            ));
+        }
+        if tax_brackets
+            .windows(2)
+            .any(|w| w[0].from.minor() >= w[1].from.minor())
+        {
+            return Err(PayrollError::Config(
+                "tax brackets must be in increasing order".to_string(),
+            ));
+        }
+
+        let mut deductions = Vec::new();
+        for deduction in raw.deductions {
+            let amount = match (&deduction.amount, &deduction.percent) {
+                (Some(amount), None) => DeductionAmount::Fixed(Money::parse(amount, currency)?),
+                (None, Some(percent)) => DeductionAmount::Percent(parse_rate(percent)?),
+                _ => {
+                    return Err(PayrollError::Config(format!(
+                        "deduction {:?} needs exactly one of `amount` or `percent`",
+                        deduction.name
+                    )))
+                }
+            };
+            deductions.push(Deduction {
+                name: deduction.name,
+                amount,
+                pre_tax: deduction.pre_tax,
+            });
+        }
+
+        Ok(PayrollConfig {
+            currency,
+            frequency,
+            first_period_start,
+            tax_brackets,
+            deductions,
+        })
+    }
+
+    /// All pay periods of `year`, in order.
+    pub fn periods(&self, year: i32) -> Vec<PayPeriod> {
+        match self.frequency {
+            PayFrequency::Monthly => (1..=12)
+                .map(|month| {
+                    let start = NaiveDate::from_ymd_opt(year, month, 1).expect("valid month");
+                    let next = if month == 12 {
+                        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+                    } else {
+                        NaiveDate::from_ymd_opt(year, month + 1, 1)
+                    };
+                    PayPeriod {
+                        year,
+                        number: month,
+                        start,
+                        end: next.expect("valid month") - Duration::days(1),
+                    }
+                })
+                .collect(),
+            PayFrequency::Biweekly => {
+                let new_year = NaiveDate::from_ymd_opt(year, 1, 1).expect("valid year");
+                let fortnight = Duration::days(14);
+                // Step back to a period ending before the year, then forward again.
+                let mut start = self.first_period_start;
+                while start + Duration::days(13) >= new_year {
+                    start -= fortnight;
+                }
+                while start + Duration::days(13) < new_year {
+                    start += fortnight;
+                }
+                let mut periods = Vec::new();
+                while (start + Duration::days(13)).year() == year {
+                    periods.push(PayPeriod {
+                        year,
+                        number: periods.len() as u32 + 1,
+                        start,
+                        end: start + Duration::days(13),
+                    });
+                    start += fortnight;
+                }
+                periods
+            }
+        }
+    }
+
+    /// Tax on a year's taxable pay, bracket by bracket.
+    fn annual_tax(&self, annual: Money) -> Result<Money, MoneyError> {
+        let mut tax = Money::zero(self.currency);
+        for (i, bracket) in self.tax_brackets.iter().enumerate() {
+            if annual.minor() <= bracket.from.minor() {
+                break;
+            }
+            let top = match self.tax_brackets.get(i + 1) {
+                Some(next) if next.from.minor() < annual.minor() => next.from,
+                _ => annual,
+            };
+            let portion = top.checked_sub(bracket.from)?;
+            tax = tax.checked_add(portion.mul_ratio(bracket.rate, 10_000)?)?;
+        }
+        Ok(tax)
+    }
+}
+
+// ----------------------------
+// Payslips and Summaries
+// ----------------------------
+
+// Running totals for one employee within one year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YearToDate {
+    pub gross: Money,
+    pub tax: Money,
+    pub deductions: Money,
+    pub net: Money,
+}
+
+impl YearToDate {
+    fn zero(currency: Currency) -> Self {
+        YearToDate {
+            gross: Money::zero(currency),
+            tax: Money::zero(currency),
+            deductions: Money::zero(currency),
+            net: Money::zero(currency),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payslip {
+    pub employee_id: i32,
+    pub name: String,
+    pub period: PayPeriod,
+    pub gross: Money,
+    pub pre_tax: Vec<(String, Money)>,
+    pub taxable: Money,
+    pub tax: Money,
+    pub post_tax: Vec<(String, Money)>,
+    pub net: Money,
+    // Totals for the year including this payslip.
+    pub year_to_date: YearToDate,
+}
+
+impl fmt::Display for Payslip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Payslip: {} (ID {}), period {} of {}, {} to {}",
+            self.name,
+            self.employee_id,
+            self.period.number,
+            self.period.year,
+            self.period.start,
+            self.period.end
+        )?;
+        writeln!(f, "  {:<20}{:>14}", "Gross pay", self.gross.to_string())?;
+        for (name, amount) in &self.pre_tax {
+            writeln!(f, "  {:<20}{:>14}", name, format!("-{}", amount))?;
+        }
+        writeln!(f, "  {:<20}{:>14}", "Taxable pay", self.taxable.to_string())?;
+        writeln!(f, "  {:<20}{:>14}", "Income tax", format!("-{}", self.tax))?;
+        for (name, amount) in &self.post_tax {
+            writeln!(f, "  {:<20}{:>14}", name, format!("-{}", amount))?;
+        }
+        writeln!(f, "  {:<20}{:>14}", "Net pay", self.net.to_string())?;
+        write!(
+            f,
+            "  Year to date: gross {}, tax {}, deductions {}, net {}",
+            self.year_to_date.gross,
+            self.year_to_date.tax,
+            self.year_to_date.deductions,
+            self.year_to_date.net
+        )
+    }
+}
+
+// The payslips for a department in one period, with totals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepartmentPayroll {
+    pub department: String,
+    pub period: PayPeriod,
+    pub payslips: Vec<Payslip>,
+    pub gross: Money,
+    pub tax: Money,
+    pub deductions: Money,
+    pub net: Money,
+}
+
+impl fmt::Display for DepartmentPayroll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Payroll for {}, period {} of {} ({} employees)",
+            self.department,
+            self.period.number,
+            self.period.year,
+            self.payslips.len()
+        )?;
+        for slip in &self.payslips {
+            writeln!(
+                f,
+                "  {:<8} gross {:>12}  tax {:>11}  net {:>12}",
+                slip.name,
+                slip.gross.to_string(),
+                slip.tax.to_string(),
+                slip.net.to_string()
+            )?;
+        }
+        write!(
+            f,
+            "  Total    gross {:>12}  tax {:>11}  deductions {}  net {}",
+            self.gross.to_string(),
+            self.tax.to_string(),
+            self.deductions,
+            self.net
+        )
+    }
+}
+
+// The difference between a payslip as issued and as recalculated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayAdjustment {
+    pub previous: Payslip,
+    pub corrected: Payslip,
+    // Corrected minus previous; positive means the employee is owed more.
+    pub gross: Money,
+    pub tax: Money,
+    pub net: Money,
+}
+
+impl PayAdjustment {
+    pub fn is_zero(&self) -> bool {
+        self.gross.minor() == 0 && self.tax.minor() == 0 && self.net.minor() == 0
+    }
+}
+
+impl fmt::Display for PayAdjustment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let signed = |m: Money| {
+            if m.is_negative() {
+                m.to_string()
+            } else {
+                format!("+{}", m)
+            }
+        };
+        write!(
+            f,
+            "{} (ID {}), period {} of {}: gross {}, tax {}, net {}",
+            self.corrected.name,
+            self.corrected.employee_id,
+            self.corrected.period.number,
+            self.corrected.period.year,
+            signed(self.gross),
+            signed(self.tax),
+            signed(self.net)
+        )
+    }
+}
+
+// ----------------------------
+// Payroll
+// ----------------------------
+
+// The `Payroll` struct pays employees under one config and remembers what it has paid.
+#[derive(Debug, Clone)]
+pub struct Payroll {
+    config: PayrollConfig,
+    year_to_date: HashMap<(i32, i32), YearToDate>,
+    // The payslip issued to each employee for each (year, period).
+    paid: HashMap<(i32, i32, u32), Payslip>,
+}
+
+impl Payroll {
+    pub fn new(config: PayrollConfig) -> Self {
+        Payroll {
+            config,
+            year_to_date: HashMap::new(),
+            paid: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PayrollConfig {
+        &self.config
+    }
+
+    /// Totals paid to an employee so far in `year`.
+    pub fn year_to_date(&self, employee_id: i32, year: i32) -> Option<&YearToDate> {
+        self.year_to_date.get(&(employee_id, year))
+    }
+
+    /// Works out an employee's pay for `period` without recording it.
+    fn calculate(&self, employee: &Employee, period: &PayPeriod) -> Result<Payslip, PayrollError> {
+        let currency = self.config.currency;
+        if employee.salary.currency() != currency {
+            return Err(MoneyError::CurrencyMismatch(currency, employee.salary.currency()).into());
+        }
+        // Split the annual salary across the year's periods so it is paid to the cent.
+        let schedule = self.config.periods(period.year);
+        let index = (period.number as usize).wrapping_sub(1);
+        if schedule.get(index) != Some(period) {
+            return Err(PayrollError::InvalidPeriod(*period));
+        }
+        let periods = schedule.len();
+        let gross = employee.salary.split(periods)?[index];
+
+        // Takes each deduction in turn out of `available`, never more than is
+        // left, so low pay is never pushed below zero. Returns the lines as
+        // taken and what remains.
+        type Lines = Vec<(String, Money)>;
+        let deduct = |pre_tax: bool, mut available: Money| -> Result<(Lines, Money), MoneyError> {
+            let mut lines = Vec::new();
+            for d in self
+                .config
+                .deductions
+                .iter()
+                .filter(|d| d.pre_tax == pre_tax)
+            {
+                let amount = match d.amount {
+                    DeductionAmount::Fixed(amount) => amount,
+                    DeductionAmount::Percent(rate) => gross.mul_ratio(rate, 10_000)?,
+                };
+                let amount = if amount.minor() > available.minor() {
+                    available
+                } else {
+                    amount
+                };
+                available = available.checked_sub(amount)?;
+                lines.push((d.name.clone(), amount));
+            }
+            Ok((lines, available))
+        };
+        let total = |lines: &[(String, Money)]| Money::sum(lines.iter().map(|(_, m)| m), currency);
+
+        let (pre_tax, taxable) = deduct(true, gross)?;
+        // Tax each period as if the year were paid at this rate throughout.
+        let annual = taxable.checked_mul(periods as i64)?;
+        let tax = self
+            .config
+            .annual_tax(annual)?
+            .checked_div(periods as i64)?;
+        let (post_tax, net) = deduct(false, taxable.checked_sub(tax)?)?;
+
+        let previous = self
+            .year_to_date(employee.id, period.year)
+            .copied()
+            .unwrap_or(YearToDate::zero(currency));
+        let deductions = total(&pre_tax)?.checked_add(total(&post_tax)?)?;
+        let year_to_date = YearToDate {
+            gross: previous.gross.checked_add(gross)?,
+            tax: previous.tax.checked_add(tax)?,
+            deductions: previous.deductions.checked_add(deductions)?,
+            net: previous.net.checked_add(net)?,
+        };
+
+        Ok(Payslip {
+            employee_id: employee.id,
+            name: employee.name.clone(),
+            period: *period,
+            gross,
+            pre_tax,
+            taxable,
+            tax,
+            post_tax,
+            net,
+            year_to_date,
+        })
+    }
+
+    /// Pays an employee for `period` and adds it to their year-to-date totals.
+    /// Each employee can be paid once per period.
+    pub fn pay(
+        &mut self,
+        employee: &Employee,
+        period: &PayPeriod,
+    ) -> Result<Payslip, PayrollError> {
+        if self
+            .paid
+            .contains_key(&(employee.id, period.year, period.number))
+        {
+            return Err(PayrollError::AlreadyPaid {
+                employee_id: employee.id,
+                period: *period,
+            });
+        }
+        let slip = self.calculate(employee, period)?;
+        self.paid
+            .insert((employee.id, period.year, period.number), slip.clone());
+        self.year_to_date
+            .insert((employee.id, period.year), slip.year_to_date);
+        Ok(slip)
+    }
+
+    /// The payslip issued to an employee for `period`, if they have been paid.
+    pub fn payslip(&self, employee_id: i32, period: &PayPeriod) -> Option<&Payslip> {
+        self.paid.get(&(employee_id, period.year, period.number))
+    }
+
+    /// Works out an already-paid period again for `employee` as they should
+    /// have been paid, replaces the payslip, and moves the year-to-date totals
+    /// by the difference. The corrected payslip carries the updated totals.
+    pub fn recalculate(
+        &mut self,
+        employee: &Employee,
+        period: &PayPeriod,
+    ) -> Result<PayAdjustment, PayrollError> {
+        let key = (employee.id, period.year, period.number);
+        let previous = self.paid.get(&key).cloned().ok_or(PayrollError::NotPaid {
+            employee_id: employee.id,
+            period: *period,
+        })?;
+        let mut corrected = self.calculate(employee, period)?;
+
+        let gross = corrected.gross.checked_sub(previous.gross)?;
+        let tax = corrected.tax.checked_sub(previous.tax)?;
+        let net = corrected.net.checked_sub(previous.net)?;
+        let deductions = gross.checked_sub(tax)?.checked_sub(net)?;
+        let totals = self
+            .year_to_date(employee.id, period.year)
+            .copied()
+            .unwrap_or(YearToDate::zero(self.config.currency));
+        corrected.year_to_date = YearToDate {
+            gross: totals.gross.checked_add(gross)?,
+            tax: totals.tax.checked_add(tax)?,
+            deductions: totals.deductions.checked_add(deductions)?,
+            net: totals.net.checked_add(net)?,
+        };
+
+        self.year_to_date
+            .insert((employee.id, period.year), corrected.year_to_date);
+        self.paid.insert(key, corrected.clone());
+        Ok(PayAdjustment {
+            previous,
+            corrected,
+            gross,
+            tax,
+            net,
+        })
+    }
+
+    /// Pays everyone in the department for `period`. Nobody is paid if
+    /// anyone's pay cannot be worked out or an employee id is repeated.
+    pub fn run_department(
+        &mut self,
+        dept: &Department,
+        period: &PayPeriod,
+    ) -> Result<DepartmentPayroll, PayrollError> {
+        let mut seen = HashSet::new();
+        for emp in &dept.employees {
+            if !seen.insert(emp.id) {
+                return Err(PayrollError::DuplicateEmployee(emp.id));
+            }
+            if self
+                .paid
+                .contains_key(&(emp.id, period.year, period.number))
+            {
+                return Err(PayrollError::AlreadyPaid {
+                    employee_id: emp.id,
+                    period: *period,
+                });
+            }
+            self.calculate(emp, period)?;
+        }
+        let mut payslips = Vec::new();
+        for emp in &dept.employees {
+            payslips.push(self.pay(emp, period)?);
+        }
+
+        let currency = self.config.currency;
+        let sum = |amount: fn(&Payslip) -> Money| {
+            Money::sum(
+                payslips.iter().map(amount).collect::<Vec<_>>().iter(),
+                currency,
+            )
+        };
+        let gross = sum(|s| s.gross)?;
+        let tax = sum(|s| s.tax)?;
+        let net = sum(|s| s.net)?;
+        let deductions = gross.checked_sub(tax)?.checked_sub(net)?;
+        Ok(DepartmentPayroll {
+            department: dept.name.clone(),
+            period: *period,
+            payslips,
+            gross,
+            tax,
+            deductions,
+            net,
+        })
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::employee;
+    use crate::Role;
+
+    const CONFIG: &str = r#"
+        currency = "USD"
+        frequency = "monthly"
+        first_period_start = "2025-01-01"
+
+        [[tax_brackets]]
+        from = "0"
+        rate = "10"
+
+        [[tax_brackets]]
+        from = "50000"
+        rate = "20"
+
+        [[deductions]]
+        name = "Pension"
+        percent = "5"
+        pre_tax = true
+
+        [[deductions]]
+        name = "Union dues"
+        amount = "25.00"
+    "#;
+
+    #[test]
+    fn test_config_is_validated() {
+        let config = PayrollConfig::from_toml(CONFIG).unwrap();
+        assert_eq!(config.tax_brackets[1].rate, 2000);
+        assert_eq!(config.deductions[0].amount, DeductionAmount::Percent(500));
+        assert!(!config.deductions[1].pre_tax);
+
+        let both = CONFIG.replace("amount = \"25.00\"", "amount = \"25.00\"\npercent = \"1\"");
+        assert!(matches!(
+            PayrollConfig::from_toml(&both),
+            Err(PayrollError::Config(_))
+        ));
+        let weekly = CONFIG.replace("\"monthly\"", "\"weekly\"");
+        assert!(matches!(
+            PayrollConfig::from_toml(&weekly),
+            Err(PayrollError::Config(_))
+        ));
+        for rate in ["-5", "-0.5", "+5"] {
+            assert!(matches!(parse_rate(rate), Err(PayrollError::Config(_))));
+        }
+    }
+
+    #[test]
+    fn test_gross_to_net_and_year_to_date() {
+        let config = PayrollConfig::from_toml(CONFIG).unwrap();
+        let periods = config.periods(2025);
+        let mut payroll = Payroll::new(config);
+        let emp = employee(1, Role::Developer, 80000);
+
+        let slip = payroll.pay(&emp, &periods[0]).unwrap();
+        assert_eq!(slip.gross, Money::new(666667, Currency::Usd));
+        assert_eq!(slip.taxable, Money::new(633334, Currency::Usd));
+        // Annualised taxable pay is $76,000.08: 10% of $50,000 plus 20% of the
+        // rest is $10,200.02 a year, or $850.00 a month.
+        assert_eq!(slip.tax, Money::new(85000, Currency::Usd));
+        assert_eq!(slip.net, Money::new(545834, Currency::Usd));
+        assert!(matches!(
+            payroll.pay(&emp, &periods[0]),
+            Err(PayrollError::AlreadyPaid { employee_id: 1, .. })
+        ));
+        for number in [0, 13] {
+            let made_up = PayPeriod {
+                number,
+                ..periods[1]
+            };
+            assert!(matches!(
+                payroll.pay(&emp, &made_up),
+                Err(PayrollError::InvalidPeriod(_))
+            ));
+        }
+
+        for period in &periods[1..] {
+            payroll.pay(&emp, period).unwrap();
+        }
+        let ytd = payroll.year_to_date(1, 2025).unwrap();
+        assert_eq!(ytd.gross, emp.salary);
+        assert_eq!(
+            ytd.gross.checked_sub(ytd.tax).unwrap(),
+            ytd.net.checked_add(ytd.deductions).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deductions_stop_at_zero_net_pay() {
+        let config = PayrollConfig::from_toml(CONFIG).unwrap();
+        let periods = config.periods(2025);
+        let mut payroll = Payroll::new(config);
+        // $10.00 a month: $0.50 pension, $0.95 tax, and the $25.00 union
+        // dues can only take the $8.55 that is left.
+        let slip = payroll
+            .pay(&employee(1, Role::Developer, 120), &periods[0])
+            .unwrap();
+        assert_eq!(slip.net, Money::zero(Currency::Usd));
+        assert_eq!(slip.post_tax[0].1, Money::new(855, Currency::Usd));
+        let ytd = slip.year_to_date;
+        assert_eq!(
+            ytd.gross.checked_sub(ytd.tax).unwrap(),
+            ytd.net.checked_add(ytd.deductions).unwrap()
+        );
+
+        // Taken before tax, the dues leave nothing to tax at all.
+        let pre_tax = CONFIG.replace("amount = \"25.00\"", "amount = \"25.00\"\npre_tax = true");
+        let mut payroll = Payroll::new(PayrollConfig::from_toml(&pre_tax).unwrap());
+        let slip = payroll
+            .pay(&employee(1, Role::Developer, 120), &periods[0])
+            .unwrap();
+        assert_eq!(slip.taxable, Money::zero(Currency::Usd));
+        assert_eq!(slip.tax, Money::zero(Currency::Usd));
+        assert_eq!(slip.pre_tax[1].1, Money::new(950, Currency::Usd));
+        assert_eq!(slip.net, Money::zero(Currency::Usd));
+    }
+
+    #[test]
+    fn test_biweekly_periods_and_department_run() {
+        let config = PayrollConfig::from_toml(
+            &CONFIG
+                .replace("\"monthly\"", "\"biweekly\"")
+                .replace("2025-01-01", "2024-12-27"),
+        )
+        .unwrap();
+        let periods = config.periods(2025);
+        assert_eq!(periods.len(), 26);
+        assert_eq!(
+            periods[0].start,
+            NaiveDate::from_ymd_opt(2024, 12, 27).unwrap()
+        );
+        assert!(periods
+            .windows(2)
+            .all(|w| w[1].start == w[0].end + Duration::days(1)));
+
+        let mut payroll = Payroll::new(config);
+        let mut euro = employee(3, Role::Developer, 60000);
+        euro.salary = Money::from_major(60000, Currency::Eur).unwrap();
+        let dept = Department {
+            name: String::from("Engineering"),
+            employees: vec![
+                employee(1, Role::Developer, 80000),
+                employee(2, Role::Developer, 52000),
+                euro,
+            ],
+            sub_departments: Vec::new(),
+        };
+        assert!(matches!(
+            payroll.run_department(&dept, &periods[0]),
+            Err(PayrollError::Money(MoneyError::CurrencyMismatch(..)))
+        ));
+        // A failed run pays nobody.
+        assert!(payroll.year_to_date(1, 2025).is_none());
+        let twice = Department {
+            employees: vec![
+                employee(1, Role::Developer, 80000),
+                employee(1, Role::Developer, 80000),
+            ],
+            ..dept.clone()
+        };
+        assert!(matches!(
+            payroll.run_department(&twice, &periods[0]),
+            Err(PayrollError::DuplicateEmployee(1))
+        ));
+        assert!(payroll.year_to_date(1, 2025).is_none());
+
+        let dept = Department {
+            employees: dept.employees[..2].to_vec(),
+            ..dept
+        };
+        let run = payroll.run_department(&dept, &periods[0]).unwrap();
+        assert_eq!(run.payslips.len(), 2);
+        assert_eq!(run.gross, Money::new(507693, Currency::Usd));
+    }
+}
@@ -0,0 +1,564 @@
+// Reading and writing department trees as CSV and JSON files, checking each
+// employee on the way in and reporting problems by line number.
+
+use crate::money::{Currency, Money};
+use crate::{Department, Employee, Role};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// ----------------------------
+// File Layouts
+// ----------------------------
+
+// One employee as it appears in a file. Salaries are decimal strings, e.g.
+// "118000.00", so no precision is lost on the way through JSON.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmployeeRecord {
+    id: i32,
+    name: String,
+    role: String,
+    level: u8,
+    salary: String,
+    currency: String,
+    manager_id: Option<i32>,
+}
+
+// One CSV row: an employee and the path of their department, such as
+// "Acme/Engineering". A row with only a path records an empty department.
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    department: String,
+    id: Option<i32>,
+    name: String,
+    role: String,
+    level: Option<u8>,
+    salary: String,
+    currency: String,
+    manager_id: Option<i32>,
+}
+
+// A department as read from JSON. Employees are kept as raw text until
+// they are checked so their line numbers can be found.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonDepartmentIn<'a> {
+    name: String,
+    #[serde(default, borrow)]
+    employees: Vec<&'a RawValue>,
+    #[serde(default, borrow)]
+    sub_departments: Vec<JsonDepartmentIn<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonDepartmentOut {
+    name: String,
+    employees: Vec<EmployeeRecord>,
+    sub_departments: Vec<JsonDepartmentOut>,
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(std::io::Error),
+    // A row or value that could not be read at all.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    DuplicateId {
+        line: usize,
+        id: i32,
+        first_line: usize,
+    },
+    UnknownRole {
+        line: usize,
+        role: String,
+    },
+    NegativeSalary {
+        line: usize,
+        salary: Money,
+    },
+    // The tree cannot be written in the requested format.
+    Unrepresentable(String),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "cannot read employee file: {}", e),
+            RecordError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            RecordError::DuplicateId {
+                line,
+                id,
+                first_line,
+            } => write!(
+                f,
+                "line {}: employee id {} is already used on line {}",
+                line, id, first_line
+            ),
+            RecordError::UnknownRole { line, role } => {
+                write!(f, "line {}: unknown role {:?}", line, role)
+            }
+            RecordError::NegativeSalary { line, salary } => {
+                write!(f, "line {}: salary {} is negative", line, salary)
+            }
+            RecordError::Unrepresentable(message) => write!(f, "cannot export: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<std::io::Error> for RecordError {
+    fn from(e: std::io::Error) -> Self {
+        RecordError::Io(e)
+    }
+}
+
+fn csv_error(e: csv::Error) -> RecordError {
+    let line = e.position().map_or(1, |p| p.line() as usize);
+    let message = match e.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => e.to_string(),
+    };
+    RecordError::Parse { line, message }
+}
+
+// ----------------------------
+// Validation
+// ----------------------------
+
+// Checks employees as they are read, remembering which line each id came from.
+struct Checker<'r> {
+    roles: &'r [Role],
+    seen: HashMap<i32, usize>,
+}
+
+impl<'r> Checker<'r> {
+    fn new(roles: &'r [Role]) -> Self {
+        Checker {
+            roles,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn employee(&mut self, record: EmployeeRecord, line: usize) -> Result<Employee, RecordError> {
+        let parse = |message: String| RecordError::Parse { line, message };
+        let currency = record
+            .currency
+            .parse::<Currency>()
+            .map_err(|e| parse(e.to_string()))?;
+        let salary = Money::parse(&record.salary, currency).map_err(|e| parse(e.to_string()))?;
+        if salary.is_negative() {
+            return Err(RecordError::NegativeSalary { line, salary });
+        }
+        let role = Role::from_name(&record.role);
+        if !self.roles.contains(&role) {
+            return Err(RecordError::UnknownRole {
+                line,
+                role: record.role,
+            });
+        }
+        if record.level == 0 {
+            return Err(parse(String::from("level must be at least 1")));
+        }
+        if let Some(&first_line) = self.seen.get(&record.id) {
+            return Err(RecordError::DuplicateId {
+                line,
+                id: record.id,
+                first_line,
+            });
+        }
+        self.seen.insert(record.id, line);
+        Ok(Employee {
+            id: record.id,
+            name: record.name,
+            role,
+            salary,
+            manager_id: record.manager_id,
+            level: record.level,
+        })
+    }
+}
+
+fn to_record(emp: &Employee) -> EmployeeRecord {
+    EmployeeRecord {
+        id: emp.id,
+        name: emp.name.clone(),
+        role: emp.role.to_string(),
+        level: emp.level,
+        salary: emp.salary.to_decimal(),
+        currency: emp.salary.currency().code().to_string(),
+        manager_id: emp.manager_id,
+    }
+}
+
+// ----------------------------
+// CSV
+// ----------------------------
+
+/// The department at `path` below `dept`, creating any that are missing. A
+/// path names exactly one department, which is why `to_csv` refuses sibling
+/// departments that share a name.
+fn department_at<'d>(dept: &'d mut Department, path: &[&str]) -> &'d mut Department {
+    let Some((first, rest)) = path.split_first() else {
+        return dept;
+    };
+    let index = match dept.sub_departments.iter().position(|d| d.name == *first) {
+        Some(index) => index,
+        None => {
+            dept.sub_departments.push(Department {
+                name: first.to_string(),
+                employees: Vec::new(),
+                sub_departments: Vec::new(),
+            });
+            dept.sub_departments.len() - 1
+        }
+    };
+    department_at(&mut dept.sub_departments[index], rest)
+}
+
+fn csv_rows(dept: &Department, parent: &str, rows: &mut Vec<CsvRow>) -> Result<(), RecordError> {
+    if dept.name.is_empty() || dept.name.contains('/') {
+        return Err(RecordError::Unrepresentable(format!(
+            "department name {:?} cannot be part of a CSV path",
+            dept.name
+        )));
+    }
+    let path = if parent.is_empty() {
+        dept.name.clone()
+    } else {
+        format!("{}/{}", parent, dept.name)
+    };
+    if dept.employees.is_empty() {
+        rows.push(CsvRow {
+            department: path.clone(),
+            id: None,
+            name: String::new(),
+            role: String::new(),
+            level: None,
+            salary: String::new(),
+            currency: String::new(),
+            manager_id: None,
+        });
+    }
+    for emp in &dept.employees {
+        let record = to_record(emp);
+        rows.push(CsvRow {
+            department: path.clone(),
+            id: Some(record.id),
+            name: record.name,
+            role: record.role,
+            level: Some(record.level),
+            salary: record.salary,
+            currency: record.currency,
+            manager_id: record.manager_id,
+        });
+    }
+    for (i, sub) in dept.sub_departments.iter().enumerate() {
+        if dept.sub_departments[..i].iter().any(|d| d.name == sub.name) {
+            return Err(RecordError::Unrepresentable(format!(
+                "{:?} has two sub-departments named {:?}, which CSV cannot tell apart",
+                path, sub.name
+            )));
+        }
+        csv_rows(sub, &path, rows)?;
+    }
+    Ok(())
+}
+
+// ----------------------------
+// JSON
+// ----------------------------
+
+fn line_of(text: &str, part: &str) -> usize {
+    let offset = part.as_ptr() as usize - text.as_ptr() as usize;
+    text[..offset].matches('\n').count() + 1
+}
+
+fn json_department(
+    text: &str,
+    raw: JsonDepartmentIn,
+    checker: &mut Checker,
+) -> Result<Department, RecordError> {
+    let mut employees = Vec::new();
+    for value in raw.employees {
+        let line = line_of(text, value.get());
+        let record: EmployeeRecord =
+            serde_json::from_str(value.get()).map_err(|e| RecordError::Parse {
+                line: line + e.line() - 1,
+                message: e.to_string(),
+            })?;
+        employees.push(checker.employee(record, line)?);
+    }
+    let mut sub_departments = Vec::new();
+    for sub in raw.sub_departments {
+        sub_departments.push(json_department(text, sub, checker)?);
+    }
+    Ok(Department {
+        name: raw.name,
+        employees,
+        sub_departments,
+    })
+}
+
+fn json_out(dept: &Department) -> JsonDepartmentOut {
+    JsonDepartmentOut {
+        name: dept.name.clone(),
+        employees: dept.employees.iter().map(to_record).collect(),
+        sub_departments: dept.sub_departments.iter().map(json_out).collect(),
+    }
+}
+
+// ----------------------------
+// Import and Export
+// ----------------------------
+
+impl Department {
+    /// Reads a department tree from a file, as JSON if the name ends in `.json`
+    /// and as CSV otherwise. Only employees whose role is in `roles` are accepted.
+    pub fn load(path: impl AsRef<Path>, roles: &[Role]) -> Result<Department, RecordError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Department::from_json(&text, roles)
+        } else {
+            Department::from_csv(&text, roles)
+        }
+    }
+
+    /// Reads CSV with a header row. Every department path must start with the
+    /// same top-level department, which is returned.
+    pub fn from_csv(text: &str, roles: &[Role]) -> Result<Department, RecordError> {
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let headers = reader.headers().map_err(csv_error)?.clone();
+        let mut checker = Checker::new(roles);
+        let mut root: Option<Department> = None;
+        for result in reader.records() {
+            let record = result.map_err(csv_error)?;
+            let line = record.position().map_or(1, |p| p.line() as usize);
+            let row: CsvRow = record.deserialize(Some(&headers)).map_err(csv_error)?;
+
+            let path: Vec<&str> = row.department.split('/').collect();
+            if path.iter().any(|name| name.is_empty()) {
+                return Err(RecordError::Parse {
+                    line,
+                    message: format!("invalid department path {:?}", row.department),
+                });
+            }
+            let root = root.get_or_insert_with(|| Department {
+                name: path[0].to_string(),
+                employees: Vec::new(),
+                sub_departments: Vec::new(),
+            });
+            if root.name != path[0] {
+                return Err(RecordError::Parse {
+                    line,
+                    message: format!("department path must start with {:?}", root.name),
+                });
+            }
+            let dept = department_at(root, &path[1..]);
+
+            // A row with every employee column empty only records that the
+            // department exists.
+            let Some(id) = row.id else {
+                let blank = row.name.is_empty()
+                    && row.role.is_empty()
+                    && row.level.is_none()
+                    && row.salary.is_empty()
+                    && row.currency.is_empty()
+                    && row.manager_id.is_none();
+                if blank {
+                    continue;
+                }
+                return Err(RecordError::Parse {
+                    line,
+                    message: String::from("missing id"),
+                });
+            };
+            let level = row.level.ok_or_else(|| RecordError::Parse {
+                line,
+                message: String::from("missing level"),
+            })?;
+            let record = EmployeeRecord {
+                id,
+                name: row.name,
+                role: row.role,
+                level,
+                salary: row.salary,
+                currency: row.currency,
+                manager_id: row.manager_id,
+            };
+            dept.employees.push(checker.employee(record, line)?);
+        }
+        root.ok_or(RecordError::Parse {
+            line: 1,
+            message: String::from("no departments"),
+        })
+    }
+
+    /// Writes the tree as CSV that `from_csv` reads back unchanged.
+    pub fn to_csv(&self) -> Result<String, RecordError> {
+        let mut rows = Vec::new();
+        csv_rows(self, "", &mut rows)?;
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in rows {
+            writer
+                .serialize(row)
+                .map_err(|e| RecordError::Unrepresentable(e.to_string()))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| RecordError::Unrepresentable(e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| RecordError::Unrepresentable(e.to_string()))
+    }
+
+    /// Reads a JSON object with `name`, `employees` and `sub_departments`.
+    pub fn from_json(text: &str, roles: &[Role]) -> Result<Department, RecordError> {
+        let raw: JsonDepartmentIn = serde_json::from_str(text).map_err(|e| RecordError::Parse {
+            line: e.line(),
+            message: e.to_string(),
+        })?;
+        json_department(text, raw, &mut Checker::new(roles))
+    }
+
+    /// Writes the tree as indented JSON that `from_json` reads back unchanged.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&json_out(self)).unwrap_or_default()
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLES: [Role; 3] = [Role::Developer, Role::Manager, Role::Designer];
+
+    const CSV: &str = "\
+department,id,name,role,level,salary,currency,manager_id
+Acme,1,Ada,Manager,2,150000.00,USD,
+Acme/Web,2,\"Bo, Jr.\",Developer,1,85000.50,USD,1
+Acme/Web/Mobile,,,,,,,
+Acme/Apps,3,Cy,Designer,1,75000,EUR,1
+";
+
+    #[test]
+    fn test_csv_round_trip() {
+        let dept = Department::from_csv(CSV, &ROLES).unwrap();
+        assert_eq!(dept.name, "Acme");
+        let web = dept.find_department("Web").unwrap();
+        assert_eq!(web.employees[0].name, "Bo, Jr.");
+        assert_eq!(web.employees[0].salary, Money::new(8500050, Currency::Usd));
+        assert_eq!(web.employees[0].manager_id, Some(1));
+        assert!(dept.find_department("Mobile").unwrap().employees.is_empty());
+        let cy = dept.find_employee(3).unwrap();
+        assert_eq!(cy.salary.currency(), Currency::Eur);
+
+        let exported = dept.to_csv().unwrap();
+        let again = Department::from_csv(&exported, &ROLES).unwrap();
+        assert_eq!(again.to_csv().unwrap(), exported);
+        assert_eq!(again.to_json(), dept.to_json());
+    }
+
+    #[test]
+    fn test_json_round_trip_and_line_numbers() {
+        let dept = Department::from_csv(CSV, &ROLES).unwrap();
+        let json = dept.to_json();
+        let again = Department::from_json(&json, &ROLES).unwrap();
+        assert_eq!(again.to_json(), json);
+        assert_eq!(again.to_csv().unwrap(), dept.to_csv().unwrap());
+
+        // Bo's record starts on the line holding its opening brace.
+        let bo_line = json
+            .lines()
+            .position(|l| l.contains("\"Bo, Jr.\""))
+            .unwrap()
+            + 1;
+        let negative = json.replace("\"85000.50\"", "\"-1.00\"");
+        assert!(matches!(
+            Department::from_json(&negative, &ROLES),
+            Err(RecordError::NegativeSalary { line, .. }) if line == bo_line - 2
+        ));
+        let typo = json.replace(
+            "\"level\": 1,\n          \"salary\": \"85000.50\"",
+            "\"level\": \"one\",\n          \"salary\": \"85000.50\"",
+        );
+        assert!(matches!(
+            Department::from_json(&typo, &ROLES),
+            Err(RecordError::Parse { line, .. }) if line == bo_line + 2
+        ));
+    }
+
+    #[test]
+    fn test_csv_validation_errors() {
+        let duplicate = CSV.replace("Acme/Apps,3", "Acme/Apps,2");
+        assert!(matches!(
+            Department::from_csv(&duplicate, &ROLES),
+            Err(RecordError::DuplicateId {
+                line: 5,
+                id: 2,
+                first_line: 3
+            })
+        ));
+        let unknown = CSV.replace("Designer", "Astronaut");
+        assert!(matches!(
+            Department::from_csv(&unknown, &ROLES),
+            Err(RecordError::UnknownRole { line: 5, .. })
+        ));
+        let negative = CSV.replace("150000.00", "-150000.00");
+        let err = Department::from_csv(&negative, &ROLES).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: salary -$150,000.00 is negative");
+        let bad_id = CSV.replace(",3,Cy", ",three,Cy");
+        assert!(matches!(
+            Department::from_csv(&bad_id, &ROLES),
+            Err(RecordError::Parse { line: 5, .. })
+        ));
+        let two_roots = CSV.replace("Acme/Apps", "Other/Apps");
+        assert!(matches!(
+            Department::from_csv(&two_roots, &ROLES),
+            Err(RecordError::Parse { line: 5, .. })
+        ));
+
+        let no_id = CSV.replace(",3,Cy", ",,Cy");
+        let err = Department::from_csv(&no_id, &ROLES).unwrap_err();
+        assert_eq!(err.to_string(), "line 5: missing id");
+
+        let mut slash = Department::from_csv(CSV, &ROLES).unwrap();
+        slash.name = String::from("A/B");
+        assert!(matches!(
+            slash.to_csv(),
+            Err(RecordError::Unrepresentable(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_keeps_sibling_departments_apart() {
+        let mut dept = Department::from_csv(CSV, &ROLES).unwrap();
+        dept.sub_departments[1].name = String::from("Web");
+        let json = dept.to_json();
+        let again = Department::from_json(&json, &ROLES).unwrap();
+        assert_eq!(again.sub_departments.len(), 2);
+        assert_eq!(again.to_json(), json);
+        assert!(matches!(
+            dept.to_csv(),
+            Err(RecordError::Unrepresentable(_))
+        ));
+
+        let typo = json.replacen("\"sub_departments\"", "\"sub_department\"", 1);
+        assert!(matches!(
+            Department::from_json(&typo, &ROLES),
+            Err(RecordError::Parse { .. })
+        ));
+    }
+}
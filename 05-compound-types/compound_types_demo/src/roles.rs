@@ -0,0 +1,451 @@
+// Roles defined in data: a catalogue read from TOML or JSON that gives each
+// role a description, career levels and a salary band per level, and checks
+// employees' pay against those bands.
+
+use crate::money::{Currency, Money, MoneyError};
+use crate::{Employee, Role};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+// ----------------------------
+// Catalogue
+// ----------------------------
+
+// The pay range for one level: nobody should earn less than `min` or more
+// than `max`, and `mid` is the target for someone fully in the job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SalaryBand {
+    pub min: Money,
+    pub mid: Money,
+    pub max: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    // 1-based, in the order the levels are listed.
+    pub number: u8,
+    pub title: String,
+    pub band: SalaryBand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleDefinition {
+    pub role: Role,
+    pub description: String,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleCatalog {
+    pub currency: Currency,
+    pub roles: Vec<RoleDefinition>,
+}
+
+// The file layout, before amounts are parsed and checked.
+#[derive(Deserialize)]
+struct RawCatalog {
+    currency: String,
+    roles: Vec<RawRole>,
+}
+
+#[derive(Deserialize)]
+struct RawRole {
+    name: String,
+    #[serde(default)]
+    description: String,
+    levels: Vec<RawLevel>,
+}
+
+#[derive(Deserialize)]
+struct RawLevel {
+    title: String,
+    min: String,
+    mid: String,
+    max: String,
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug)]
+pub enum RoleError {
+    Io(std::io::Error),
+    Config(String),
+    Money(MoneyError),
+}
+
+impl fmt::Display for RoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoleError::Io(e) => write!(f, "cannot read role catalogue: {}", e),
+            RoleError::Config(message) => write!(f, "invalid role catalogue: {}", message),
+            RoleError::Money(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RoleError {}
+
+impl From<std::io::Error> for RoleError {
+    fn from(e: std::io::Error) -> Self {
+        RoleError::Io(e)
+    }
+}
+
+impl From<MoneyError> for RoleError {
+    fn from(e: MoneyError) -> Self {
+        RoleError::Money(e)
+    }
+}
+
+// The `BandIssue` enum describes why an employee's pay does not fit the catalogue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BandIssue {
+    UnknownRole {
+        employee_id: i32,
+        role: Role,
+    },
+    UnknownLevel {
+        employee_id: i32,
+        role: Role,
+        level: u8,
+    },
+    BelowBand {
+        employee_id: i32,
+        salary: Money,
+        min: Money,
+    },
+    AboveBand {
+        employee_id: i32,
+        salary: Money,
+        max: Money,
+    },
+    // Pay in another currency cannot be compared with the band.
+    CurrencyMismatch {
+        employee_id: i32,
+        salary: Money,
+        band: Currency,
+    },
+}
+
+impl fmt::Display for BandIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BandIssue::UnknownRole { employee_id, role } => {
+                write!(
+                    f,
+                    "employee {}: role {} is not in the catalogue",
+                    employee_id, role
+                )
+            }
+            BandIssue::UnknownLevel {
+                employee_id,
+                role,
+                level,
+            } => write!(
+                f,
+                "employee {}: {} has no level {}",
+                employee_id, role, level
+            ),
+            BandIssue::BelowBand {
+                employee_id,
+                salary,
+                min,
+            } => write!(
+                f,
+                "employee {}: {} is below the band minimum of {}",
+                employee_id, salary, min
+            ),
+            BandIssue::AboveBand {
+                employee_id,
+                salary,
+                max,
+            } => write!(
+                f,
+                "employee {}: {} is above the band maximum of {}",
+                employee_id, salary, max
+            ),
+            BandIssue::CurrencyMismatch {
+                employee_id,
+                salary,
+                band,
+            } => write!(
+                f,
+                "employee {}: {} is paid in {} but the band is in {}",
+                employee_id,
+                salary,
+                salary.currency().code(),
+                band.code()
+            ),
+        }
+    }
+}
+
+// ----------------------------
+// Loading
+// ----------------------------
+
+impl RoleCatalog {
+    /// Reads a catalogue file, as JSON if the name ends in `.json` and as TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RoleError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            RoleCatalog::from_json(&text)
+        } else {
+            RoleCatalog::from_toml(&text)
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, RoleError> {
+        let raw: RawCatalog =
+            toml::from_str(text).map_err(|e| RoleError::Config(e.message().to_string()))?;
+        RoleCatalog::from_raw(raw)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, RoleError> {
+        let raw: RawCatalog =
+            serde_json::from_str(text).map_err(|e| RoleError::Config(e.to_string()))?;
+        RoleCatalog::from_raw(raw)
+    }
+
+    fn from_raw(raw: RawCatalog) -> Result<Self, RoleError> {
+        let currency: Currency = raw.currency.parse()?;
+        let mut seen = HashSet::new();
+        let mut roles = Vec::new();
+        for role in raw.roles {
+            if !seen.insert(role.name.clone()) {
+                return Err(RoleError::Config(format!(
+                    "role {:?} is listed twice",
+                    role.name
+                )));
+            }
+            if role.levels.is_empty() || role.levels.len() > u8::MAX as usize {
+                return Err(RoleError::Config(format!(
+                    "role {:?} must have between 1 and {} levels",
+                    role.name,
+                    u8::MAX
+                )));
+            }
+            let mut levels = Vec::new();
+            for (i, level) in role.levels.into_iter().enumerate() {
+                let band = SalaryBand {
+                    min: Money::parse(&level.min, currency)?,
+                    mid: Money::parse(&level.mid, currency)?,
+                    max: Money::parse(&level.max, currency)?,
+                };
+                if band.min.minor() > band.mid.minor() || band.mid.minor() > band.max.minor() {
+                    return Err(RoleError::Config(format!(
+                        "{:?}: the band must satisfy min <= mid <= max",
+                        level.title
+                    )));
+                }
+                levels.push(Level {
+                    number: i as u8 + 1,
+                    title: level.title,
+                    band,
+                });
+            }
+            roles.push(RoleDefinition {
+                role: Role::from_name(&role.name),
+                description: role.description,
+                levels,
+            });
+        }
+        Ok(RoleCatalog { currency, roles })
+    }
+}
+
+// ----------------------------
+// Lookup and Validation
+// ----------------------------
+
+impl RoleCatalog {
+    pub fn role(&self, role: &Role) -> Option<&RoleDefinition> {
+        self.roles.iter().find(|r| &r.role == role)
+    }
+
+    pub fn level(&self, role: &Role, level: u8) -> Option<&Level> {
+        self.role(role)?.levels.iter().find(|l| l.number == level)
+    }
+
+    /// Checks one employee's salary against the band for their role and level.
+    pub fn check(&self, emp: &Employee) -> Option<BandIssue> {
+        let Some(definition) = self.role(&emp.role) else {
+            return Some(BandIssue::UnknownRole {
+                employee_id: emp.id,
+                role: emp.role.clone(),
+            });
+        };
+        let Some(level) = definition.levels.iter().find(|l| l.number == emp.level) else {
+            return Some(BandIssue::UnknownLevel {
+                employee_id: emp.id,
+                role: emp.role.clone(),
+                level: emp.level,
+            });
+        };
+        let band = level.band;
+        if emp.salary.currency() != band.min.currency() {
+            Some(BandIssue::CurrencyMismatch {
+                employee_id: emp.id,
+                salary: emp.salary,
+                band: band.min.currency(),
+            })
+        } else if emp.salary.minor() < band.min.minor() {
+            Some(BandIssue::BelowBand {
+                employee_id: emp.id,
+                salary: emp.salary,
+                min: band.min,
+            })
+        } else if emp.salary.minor() > band.max.minor() {
+            Some(BandIssue::AboveBand {
+                employee_id: emp.id,
+                salary: emp.salary,
+                max: band.max,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Every employee whose pay falls outside their band, in the order given.
+    pub fn validate<'a>(
+        &self,
+        employees: impl IntoIterator<Item = &'a Employee>,
+    ) -> Vec<BandIssue> {
+        employees
+            .into_iter()
+            .filter_map(|e| self.check(e))
+            .collect()
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{employee, usd};
+
+    const CATALOG: &str = r#"
+currency = "USD"
+
+[[roles]]
+name = "Developer"
+description = "Writes code."
+
+[[roles.levels]]
+title = "Developer"
+min = "70000"
+mid = "85000"
+max = "100000"
+
+[[roles.levels]]
+title = "Senior Developer"
+min = "90000"
+mid = "110000"
+max = "130000"
+
+[[roles]]
+name = "Data Scientist"
+
+[[roles.levels]]
+title = "Data Scientist"
+min = "80000"
+mid = "100000"
+max = "120000"
+"#;
+
+    #[test]
+    fn test_load_toml_and_json() {
+        let catalog = RoleCatalog::from_toml(CATALOG).unwrap();
+        assert_eq!(catalog.roles.len(), 2);
+        let senior = catalog.level(&Role::Developer, 2).unwrap();
+        assert_eq!(senior.title, "Senior Developer");
+        assert_eq!(senior.band.mid, usd(110000));
+
+        // Names that are not built in become custom roles.
+        let custom = Role::Custom(String::from("Data Scientist"));
+        assert!(catalog.role(&custom).is_some());
+        assert_eq!(custom.to_string(), "Data Scientist");
+
+        let json = r#"{"currency": "EUR", "roles": [{"name": "Manager",
+            "levels": [{"title": "Lead", "min": "60000", "mid": "70000", "max": "80000"}]}]}"#;
+        let catalog = RoleCatalog::from_json(json).unwrap();
+        assert_eq!(catalog.roles[0].role, Role::Manager);
+        assert_eq!(catalog.currency, Currency::Eur);
+    }
+
+    #[test]
+    fn test_rejects_bad_catalogues() {
+        let inverted = CATALOG.replace("mid = \"85000\"", "mid = \"65000\"");
+        assert!(matches!(
+            RoleCatalog::from_toml(&inverted),
+            Err(RoleError::Config(_))
+        ));
+
+        let twice = format!(
+            "{}\n[[roles]]\nname = \"Developer\"\n[[roles.levels]]\ntitle = \"X\"\nmin = \"1\"\nmid = \"2\"\nmax = \"3\"\n",
+            CATALOG
+        );
+        assert!(matches!(
+            RoleCatalog::from_toml(&twice),
+            Err(RoleError::Config(_))
+        ));
+
+        let bad_amount = CATALOG.replace("\"70000\"", "\"seventy\"");
+        assert!(matches!(
+            RoleCatalog::from_toml(&bad_amount),
+            Err(RoleError::Money(_))
+        ));
+    }
+
+    #[test]
+    fn test_flags_pay_outside_band() {
+        let catalog = RoleCatalog::from_toml(CATALOG).unwrap();
+        let mut staff = vec![
+            employee(1, Role::Developer, 70000),
+            employee(2, Role::Developer, 85000).at_level(2),
+            employee(3, Role::Developer, 100001),
+            employee(4, Role::Designer, 80000),
+            employee(5, Role::Developer, 150000).at_level(3),
+            employee(6, Role::Developer, 80000),
+        ];
+        staff[5].salary = Money::from_major(80000, Currency::Eur).unwrap();
+        let issues = catalog.validate(&staff);
+        assert_eq!(
+            issues,
+            vec![
+                BandIssue::BelowBand {
+                    employee_id: 2,
+                    salary: usd(85000),
+                    min: usd(90000),
+                },
+                BandIssue::AboveBand {
+                    employee_id: 3,
+                    salary: usd(100001),
+                    max: usd(100000),
+                },
+                BandIssue::UnknownRole {
+                    employee_id: 4,
+                    role: Role::Designer,
+                },
+                BandIssue::UnknownLevel {
+                    employee_id: 5,
+                    role: Role::Developer,
+                    level: 3,
+                },
+                BandIssue::CurrencyMismatch {
+                    employee_id: 6,
+                    salary: Money::from_major(80000, Currency::Eur).unwrap(),
+                    band: Currency::Usd,
+                },
+            ]
+        );
+    }
+}
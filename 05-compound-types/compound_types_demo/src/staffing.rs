@@ -0,0 +1,409 @@
+// Project staffing: people are assigned to projects for part of their time
+// over a date range. From the assignments we find who is over-allocated,
+// how much capacity is left per person and per role, and what each project
+// costs in salary.
+
+use crate::money::{Currency, Money, MoneyError};
+use crate::{Department, Employee, Project};
+use chrono::{Duration, Months, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// ----------------------------
+// Types
+// ----------------------------
+
+// A project with a start date; it runs for its month count from there.
+#[derive(Debug)]
+pub struct PlannedProject {
+    pub project: Project,
+    pub start: NaiveDate,
+}
+
+// Part of one employee's time given to one project, both dates inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub employee_id: i32,
+    pub project: String,
+    // Percentage of a full-time week, 1 to 100.
+    pub allocation: u32,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+// A stretch of days during which an employee is booked for more than 100%.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverAllocation {
+    pub employee_id: i32,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub total: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Staffing {
+    pub projects: Vec<PlannedProject>,
+    pub assignments: Vec<Assignment>,
+}
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StaffingError {
+    UnknownProject(String),
+    DuplicateProject(String),
+    UnknownEmployee(i32),
+    InvalidAllocation(u32),
+    InvalidDates { start: NaiveDate, end: NaiveDate },
+    // The assignment runs outside the dates the project runs.
+    OutsideProject(String),
+    Money(MoneyError),
+}
+
+impl fmt::Display for StaffingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaffingError::UnknownProject(name) => write!(f, "no project named {:?}", name),
+            StaffingError::DuplicateProject(name) => {
+                write!(f, "project {:?} is already planned", name)
+            }
+            StaffingError::UnknownEmployee(id) => write!(f, "no employee with id {}", id),
+            StaffingError::InvalidAllocation(percent) => {
+                write!(f, "allocation must be 1-100%, not {}%", percent)
+            }
+            StaffingError::InvalidDates { start, end } => {
+                write!(f, "assignment ends ({}) before it starts ({})", end, start)
+            }
+            StaffingError::OutsideProject(name) => {
+                write!(f, "assignment falls outside the dates of {:?}", name)
+            }
+            StaffingError::Money(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StaffingError {}
+
+impl From<MoneyError> for StaffingError {
+    fn from(e: MoneyError) -> Self {
+        StaffingError::Money(e)
+    }
+}
+
+// ----------------------------
+// Planning
+// ----------------------------
+
+impl PlannedProject {
+    /// The last day of the project, `months` after it starts.
+    pub fn end(&self) -> NaiveDate {
+        let months = Months::new(self.project.1.max(0) as u32);
+        self.start
+            .checked_add_months(months)
+            .unwrap_or(NaiveDate::MAX)
+            - Duration::days(1)
+    }
+}
+
+impl Staffing {
+    pub fn new() -> Self {
+        Staffing::default()
+    }
+
+    pub fn add_project(&mut self, project: Project, start: NaiveDate) -> Result<(), StaffingError> {
+        if self.project(&project.0).is_some() {
+            return Err(StaffingError::DuplicateProject(project.0));
+        }
+        self.projects.push(PlannedProject { project, start });
+        Ok(())
+    }
+
+    fn project(&self, name: &str) -> Option<&PlannedProject> {
+        self.projects.iter().find(|p| p.project.0 == name)
+    }
+
+    /// Adds an assignment after checking it fits the project. Over-allocation is
+    /// allowed here so plans can be drafted; `over_allocations` reports it.
+    pub fn assign(&mut self, assignment: Assignment) -> Result<(), StaffingError> {
+        let project = self
+            .project(&assignment.project)
+            .ok_or_else(|| StaffingError::UnknownProject(assignment.project.clone()))?;
+        if assignment.allocation == 0 || assignment.allocation > 100 {
+            return Err(StaffingError::InvalidAllocation(assignment.allocation));
+        }
+        if assignment.end < assignment.start {
+            return Err(StaffingError::InvalidDates {
+                start: assignment.start,
+                end: assignment.end,
+            });
+        }
+        if assignment.start < project.start || assignment.end > project.end() {
+            return Err(StaffingError::OutsideProject(assignment.project));
+        }
+        self.assignments.push(assignment);
+        Ok(())
+    }
+
+    /// Ranges of days on which the employee's allocation changes, with the total
+    /// for each, in date order. Days with no assignments are left out.
+    fn load(&self, employee_id: i32) -> Vec<(NaiveDate, NaiveDate, u32)> {
+        // +allocation on the first day, -allocation the day after the last.
+        let mut changes: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+        for a in self
+            .assignments
+            .iter()
+            .filter(|a| a.employee_id == employee_id)
+        {
+            *changes.entry(a.start).or_default() += a.allocation as i64;
+            *changes.entry(a.end + Duration::days(1)).or_default() -= a.allocation as i64;
+        }
+        let mut ranges = Vec::new();
+        let mut total = 0;
+        let days: Vec<(NaiveDate, i64)> = changes.into_iter().collect();
+        for pair in days.windows(2) {
+            total += pair[0].1;
+            if total > 0 {
+                ranges.push((pair[0].0, pair[1].0 - Duration::days(1), total as u32));
+            }
+        }
+        ranges
+    }
+
+    /// The employee's highest total allocation on any day from `from` to `to`.
+    pub fn peak_allocation(&self, employee_id: i32, from: NaiveDate, to: NaiveDate) -> u32 {
+        self.load(employee_id)
+            .into_iter()
+            .filter(|&(start, end, _)| start <= to && end >= from)
+            .map(|(_, _, total)| total)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The share of a full-time week still free on every day from `from` to `to`.
+    pub fn remaining_capacity(&self, employee_id: i32, from: NaiveDate, to: NaiveDate) -> u32 {
+        100u32.saturating_sub(self.peak_allocation(employee_id, from, to))
+    }
+
+    /// Every stretch in which someone is booked for more than 100%, by
+    /// employee and then date.
+    pub fn over_allocations(&self) -> Vec<OverAllocation> {
+        let mut ids: Vec<i32> = self.assignments.iter().map(|a| a.employee_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .flat_map(|employee_id| {
+                self.load(employee_id)
+                    .into_iter()
+                    .filter(|&(_, _, total)| total > 100)
+                    .map(move |(start, end, total)| OverAllocation {
+                        employee_id,
+                        start,
+                        end,
+                        total,
+                    })
+            })
+            .collect()
+    }
+
+    /// Free capacity per role from `from` to `to`, in percent of one full-time
+    /// person (250 means two and a half people), ordered by role name.
+    pub fn capacity_by_role<'a>(
+        &self,
+        employees: impl IntoIterator<Item = &'a Employee>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Vec<(String, u32)> {
+        let mut by_role: BTreeMap<String, u32> = BTreeMap::new();
+        for emp in employees {
+            *by_role.entry(emp.role.to_string()).or_default() +=
+                self.remaining_capacity(emp.id, from, to);
+        }
+        by_role.into_iter().collect()
+    }
+
+    /// The salary cost of a project: each assignment costs the person's annual
+    /// salary times their allocation, for the share of a 365-day year it lasts.
+    pub fn labour_cost(&self, project: &str, company: &Department) -> Result<Money, StaffingError> {
+        let planned = self
+            .project(project)
+            .ok_or_else(|| StaffingError::UnknownProject(project.to_string()))?;
+        let mut costs = Vec::new();
+        for a in self
+            .assignments
+            .iter()
+            .filter(|a| a.project == planned.project.0)
+        {
+            let emp = company
+                .find_employee(a.employee_id)
+                .ok_or(StaffingError::UnknownEmployee(a.employee_id))?;
+            let days = (a.end - a.start).num_days() + 1;
+            costs.push(
+                emp.salary
+                    .mul_ratio(a.allocation as i64 * days, 100 * 365)?,
+            );
+        }
+        // A project nobody is on yet costs nothing in the company's currency.
+        let currency = costs
+            .first()
+            .map(|c| c.currency())
+            .or_else(|| company.all_employees().first().map(|e| e.salary.currency()))
+            .unwrap_or(Currency::Usd);
+        Ok(Money::sum(&costs, currency)?)
+    }
+}
+
+impl fmt::Display for OverAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "employee {} is booked {}% from {} to {}",
+            self.employee_id, self.total, self.start, self.end
+        )
+    }
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Currency;
+    use crate::test_support::{date, employee, usd};
+    use crate::Role;
+
+    fn assignment(
+        employee_id: i32,
+        project: &str,
+        allocation: u32,
+        start: &str,
+        end: &str,
+    ) -> Assignment {
+        Assignment {
+            employee_id,
+            project: String::from(project),
+            allocation,
+            start: date(start),
+            end: date(end),
+        }
+    }
+
+    fn plan() -> Staffing {
+        let mut staffing = Staffing::new();
+        staffing
+            .add_project(Project(String::from("Apollo"), 12), date("2025-01-01"))
+            .unwrap();
+        staffing
+            .add_project(Project(String::from("Gemini"), 6), date("2025-03-01"))
+            .unwrap();
+        staffing
+            .assign(assignment(1, "Apollo", 60, "2025-01-01", "2025-12-31"))
+            .unwrap();
+        staffing
+            .assign(assignment(1, "Gemini", 50, "2025-03-01", "2025-04-30"))
+            .unwrap();
+        staffing
+            .assign(assignment(2, "Gemini", 100, "2025-03-01", "2025-08-31"))
+            .unwrap();
+        staffing
+    }
+
+    #[test]
+    fn test_over_allocation_and_capacity() {
+        let staffing = plan();
+        assert_eq!(
+            staffing.over_allocations(),
+            vec![OverAllocation {
+                employee_id: 1,
+                start: date("2025-03-01"),
+                end: date("2025-04-30"),
+                total: 110,
+            }]
+        );
+        assert_eq!(
+            staffing.remaining_capacity(1, date("2025-01-01"), date("2025-02-28")),
+            40
+        );
+        assert_eq!(
+            staffing.remaining_capacity(1, date("2025-02-01"), date("2025-03-01")),
+            0
+        );
+        assert_eq!(
+            staffing.remaining_capacity(2, date("2025-09-01"), date("2025-12-31")),
+            100
+        );
+        assert_eq!(
+            staffing.remaining_capacity(3, date("2025-01-01"), date("2025-12-31")),
+            100
+        );
+
+        let staff = [
+            employee(1, Role::Developer, 100000),
+            employee(2, Role::Developer, 90000),
+            employee(3, Role::Designer, 80000),
+        ];
+        assert_eq!(
+            staffing.capacity_by_role(&staff, date("2025-05-01"), date("2025-05-31")),
+            vec![
+                (String::from("Designer"), 100),
+                (String::from("Developer"), 40)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labour_cost() {
+        let staffing = plan();
+        let company = Department {
+            name: String::from("Acme"),
+            employees: vec![
+                employee(1, Role::Developer, 100000),
+                employee(2, Role::Developer, 73000),
+            ],
+            sub_departments: Vec::new(),
+        };
+        // 60% of $100,000 for all 365 days.
+        assert_eq!(staffing.labour_cost("Apollo", &company), Ok(usd(60000)));
+        // 50% of $100,000 for 61 days plus $73,000 for 184 days.
+        let expected = Money::new(835616 + 3680000, Currency::Usd);
+        assert_eq!(staffing.labour_cost("Gemini", &company), Ok(expected));
+
+        let mut missing = company;
+        missing.employees.pop();
+        assert_eq!(
+            staffing.labour_cost("Gemini", &missing),
+            Err(StaffingError::UnknownEmployee(2))
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_assignments() {
+        let mut staffing = plan();
+        assert_eq!(
+            staffing.add_project(Project(String::from("Apollo"), 3), date("2026-01-01")),
+            Err(StaffingError::DuplicateProject(String::from("Apollo")))
+        );
+        assert_eq!(
+            staffing.assign(assignment(3, "Zeus", 50, "2025-03-01", "2025-03-31")),
+            Err(StaffingError::UnknownProject(String::from("Zeus")))
+        );
+        assert_eq!(
+            staffing.assign(assignment(3, "Apollo", 0, "2025-03-01", "2025-03-31")),
+            Err(StaffingError::InvalidAllocation(0))
+        );
+        assert!(matches!(
+            staffing.assign(assignment(3, "Apollo", 50, "2025-03-31", "2025-03-01")),
+            Err(StaffingError::InvalidDates { .. })
+        ));
+        // Gemini runs from March to the end of August.
+        assert_eq!(staffing.projects[1].end(), date("2025-08-31"));
+        assert_eq!(
+            staffing.assign(assignment(3, "Gemini", 50, "2025-08-01", "2025-09-01")),
+            Err(StaffingError::OutsideProject(String::from("Gemini")))
+        );
+        assert_eq!(staffing.assignments.len(), 3);
+    }
+}
@@ -0,0 +1,329 @@
+// Salary statistics over any slice of employees: spread, percentiles,
+// compression, histograms, and breakdowns by role and department.
+
+use crate::money::{Currency, Money, MoneyError};
+use crate::{Department, Employee};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// The most buckets `histogram` will produce; a narrower width is refused.
+pub const MAX_BUCKETS: usize = 1000;
+
+// ----------------------------
+// Errors
+// ----------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatsError {
+    // There were no salaries to summarise.
+    Empty,
+    // The histogram width would give more than `MAX_BUCKETS` buckets.
+    TooManyBuckets(u64),
+    Money(MoneyError),
+}
+
+impl fmt::Display for StatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsError::Empty => write!(f, "no salaries to summarise"),
+            StatsError::TooManyBuckets(count) => write!(
+                f,
+                "the histogram would have {} buckets; the most allowed is {}",
+                count, MAX_BUCKETS
+            ),
+            StatsError::Money(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StatsError {}
+
+impl From<MoneyError> for StatsError {
+    fn from(e: MoneyError) -> Self {
+        StatsError::Money(e)
+    }
+}
+
+// ----------------------------
+// Summary Statistics
+// ----------------------------
+
+// A summary of a non-empty set of salaries in one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SalaryStats {
+    pub currency: Currency,
+    pub count: usize,
+    pub min: Money,
+    pub max: Money,
+    pub mean: Money,
+    pub median: Money,
+    // Population standard deviation, rounded to the nearest minor unit.
+    pub std_dev: Money,
+    // Salaries in minor units, lowest first.
+    sorted: Vec<i64>,
+}
+
+// One bar of a salary histogram: salaries from `from` up to, but not including, `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bucket {
+    pub from: Money,
+    pub to: Money,
+    pub count: usize,
+}
+
+impl SalaryStats {
+    /// Summarises the salaries of `employees`, which must all be paid in the same currency.
+    pub fn from_employees(employees: &[Employee]) -> Result<SalaryStats, StatsError> {
+        let first = employees.first().ok_or(StatsError::Empty)?;
+        let currency = first.salary.currency();
+        let total = Money::sum(employees.iter().map(|e| &e.salary), currency)?;
+
+        let mut sorted: Vec<i64> = employees.iter().map(|e| e.salary.minor()).collect();
+        sorted.sort_unstable();
+        let count = sorted.len();
+        let mean = total.checked_div(count as i64)?;
+
+        let mean_minor = total.minor() as f64 / count as f64;
+        let variance = sorted
+            .iter()
+            .map(|&x| (x as f64 - mean_minor).powi(2))
+            .sum::<f64>()
+            / count as f64;
+
+        let mut stats = SalaryStats {
+            currency,
+            count,
+            min: Money::new(sorted[0], currency),
+            max: Money::new(sorted[count - 1], currency),
+            mean,
+            median: Money::zero(currency),
+            std_dev: Money::new(variance.sqrt().round() as i64, currency),
+            sorted,
+        };
+        stats.median = stats.percentile(50);
+        Ok(stats)
+    }
+
+    /// The salary below which `percent`% of salaries fall, interpolating
+    /// between neighbours and rounding half to even. 0 is the minimum and 100
+    /// the maximum; larger values are treated as 100.
+    pub fn percentile(&self, percent: u32) -> Money {
+        let percent = percent.min(100) as i64;
+        // Position in the sorted list, as the fraction rank / 100.
+        let rank = (self.count as i64 - 1) * percent;
+        let (index, fraction) = ((rank / 100) as usize, rank % 100);
+        let low = Money::new(self.sorted[index], self.currency);
+        if fraction == 0 {
+            return low;
+        }
+        let high = Money::new(self.sorted[index + 1], self.currency);
+        let step = high
+            .checked_sub(low)
+            .and_then(|gap| gap.mul_ratio(fraction, 100))
+            .unwrap_or(Money::zero(self.currency));
+        low.checked_add(step).unwrap_or(low)
+    }
+
+    /// The 10th percentile divided by the 90th. It does not depend on who is
+    /// paid what, only on how far apart salaries are: 1.0 means everyone in
+    /// the group is paid alike, and lower values mean a wider spread.
+    pub fn compression_ratio(&self) -> f64 {
+        let high = self.percentile(90).minor();
+        if high == 0 {
+            return 1.0;
+        }
+        self.percentile(10).minor() as f64 / high as f64
+    }
+
+    /// Counts salaries in buckets `width` wide, from the bucket holding the
+    /// minimum to the one holding the maximum. Empty buckets are kept so
+    /// bars line up, and a width giving more than `MAX_BUCKETS` is refused.
+    pub fn histogram(&self, width: Money) -> Result<Vec<Bucket>, StatsError> {
+        if width.currency() != self.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, width.currency()).into());
+        }
+        if width.minor() <= 0 {
+            return Err(MoneyError::DivisionByZero.into());
+        }
+        let width = width.minor();
+        let first = self.sorted[0].div_euclid(width);
+        let last = self.sorted[self.count - 1].div_euclid(width);
+        let buckets = last.abs_diff(first).saturating_add(1);
+        if buckets > MAX_BUCKETS as u64 {
+            return Err(StatsError::TooManyBuckets(buckets));
+        }
+        let mut counts = vec![0; buckets as usize];
+        for &salary in &self.sorted {
+            counts[(salary.div_euclid(width) - first) as usize] += 1;
+        }
+        let bound = |i: i64| Money::new(i, self.currency).checked_mul(width);
+        (first..=last)
+            .zip(counts)
+            .map(|(i, count)| {
+                Ok(Bucket {
+                    from: bound(i)?,
+                    to: bound(i.checked_add(1).ok_or(MoneyError::Overflow)?)?,
+                    count,
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for SalaryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n={} min {} median {} mean {} max {} sd {}",
+            self.count, self.min, self.median, self.mean, self.max, self.std_dev
+        )
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>12} - {:<12} {} {}",
+            self.from.to_string(),
+            self.to.to_string(),
+            "#".repeat(self.count),
+            self.count
+        )
+    }
+}
+
+// ----------------------------
+// Breakdowns
+// ----------------------------
+
+/// Statistics for each role, ordered by role name.
+pub fn stats_by_role(employees: &[Employee]) -> Result<Vec<(String, SalaryStats)>, StatsError> {
+    let mut groups: BTreeMap<String, Vec<Employee>> = BTreeMap::new();
+    for emp in employees {
+        groups
+            .entry(emp.role.to_string())
+            .or_default()
+            .push(emp.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(role, group)| Ok((role, SalaryStats::from_employees(&group)?)))
+        .collect()
+}
+
+/// Statistics for each department in the tree, counting only the people
+/// directly in it. Departments with nobody in them are skipped.
+pub fn stats_by_department(dept: &Department) -> Result<Vec<(String, SalaryStats)>, StatsError> {
+    let mut all = Vec::new();
+    if !dept.employees.is_empty() {
+        all.push((
+            dept.name.clone(),
+            SalaryStats::from_employees(&dept.employees)?,
+        ));
+    }
+    for sub in &dept.sub_departments {
+        all.extend(stats_by_department(sub)?);
+    }
+    Ok(all)
+}
+
+// ----------------------------
+// Unit Tests
+// ----------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{employee, usd};
+    use crate::Role;
+
+    #[test]
+    fn test_summary_and_percentiles() {
+        let staff = [
+            employee(1, Role::Developer, 70000),
+            employee(2, Role::Developer, 90000),
+            employee(3, Role::Designer, 60000),
+            employee(4, Role::Manager, 120000),
+        ];
+        let stats = SalaryStats::from_employees(&staff).unwrap();
+        assert_eq!(stats.min, usd(60000));
+        assert_eq!(stats.max, usd(120000));
+        assert_eq!(stats.mean, usd(85000));
+        // An even count takes the midpoint of the middle two.
+        assert_eq!(stats.median, usd(80000));
+        assert_eq!(stats.percentile(0), usd(60000));
+        assert_eq!(stats.percentile(100), usd(120000));
+        // p25 sits three quarters of the way from $60k to $70k.
+        assert_eq!(stats.percentile(25), usd(67500));
+        // sqrt(((-25)^2 + 5^2 + (-15)^2 + 35^2) / 4) thousand = $22,912.88
+        assert_eq!(stats.std_dev, Money::new(2291288, Currency::Usd));
+
+        let odd = SalaryStats::from_employees(&staff[..3]).unwrap();
+        assert_eq!(odd.median, usd(70000));
+    }
+
+    #[test]
+    fn test_histogram_and_compression() {
+        let staff: Vec<Employee> = [52000, 55000, 61000, 99000, 101000]
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| employee(i as i32, Role::Developer, s))
+            .collect();
+        let stats = SalaryStats::from_employees(&staff).unwrap();
+        let buckets = stats.histogram(usd(25000)).unwrap();
+        let counts: Vec<(Money, usize)> = buckets.iter().map(|b| (b.from, b.count)).collect();
+        assert_eq!(
+            counts,
+            vec![(usd(50000), 3), (usd(75000), 1), (usd(100000), 1)]
+        );
+        assert!(stats.histogram(Money::zero(Currency::Usd)).is_err());
+        // One-cent buckets from $52,000 to $101,000 would number millions.
+        assert_eq!(
+            stats.histogram(Money::new(1, Currency::Usd)),
+            Err(StatsError::TooManyBuckets(4_900_001))
+        );
+        assert!(stats
+            .histogram(Money::from_major(1, Currency::Eur).unwrap())
+            .is_err());
+
+        // p10 is $53,200 and p90 is $100,200.
+        assert!((stats.compression_ratio() - 532.0 / 1002.0).abs() < 1e-9);
+        let same = SalaryStats::from_employees(&staff[..1]).unwrap();
+        assert_eq!(same.compression_ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_breakdowns_and_errors() {
+        let staff = vec![
+            employee(1, Role::Developer, 80000),
+            employee(2, Role::Manager, 100000),
+            employee(3, Role::Developer, 90000),
+        ];
+        let by_role = stats_by_role(&staff).unwrap();
+        let names: Vec<&str> = by_role.iter().map(|(r, _)| r.as_str()).collect();
+        assert_eq!(names, vec!["Developer", "Manager"]);
+        assert_eq!(by_role[0].1.mean, usd(85000));
+
+        let dept = Department {
+            name: String::from("Top"),
+            employees: Vec::new(),
+            sub_departments: vec![Department {
+                name: String::from("Team"),
+                employees: staff,
+                sub_departments: Vec::new(),
+            }],
+        };
+        let by_dept = stats_by_department(&dept).unwrap();
+        assert_eq!(by_dept.len(), 1);
+        assert_eq!(by_dept[0].1.count, 3);
+
+        assert_eq!(SalaryStats::from_employees(&[]), Err(StatsError::Empty));
+        let mut mixed = dept.sub_departments[0].employees.clone();
+        mixed[0].salary = Money::from_major(1, Currency::Eur).unwrap();
+        assert!(matches!(
+            SalaryStats::from_employees(&mixed),
+            Err(StatsError::Money(MoneyError::CurrencyMismatch(..)))
+        ));
+    }
+}
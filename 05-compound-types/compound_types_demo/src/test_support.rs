@@ -0,0 +1,49 @@
+// Fixtures shared by the unit tests in every module, so a change to
+// `Employee` only has to be made here.
+
+use crate::money::{Currency, Money};
+use crate::{Employee, Role};
+use chrono::NaiveDate;
+
+/// A whole-dollar amount.
+pub fn usd(major: i64) -> Money {
+    Money::from_major(major, Currency::Usd).unwrap()
+}
+
+/// A date written as "YYYY-MM-DD".
+pub fn date(text: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+}
+
+/// An employee named "Employee <id>" at level 1 with no manager, paid in
+/// dollars. The methods below change one field at a time.
+pub fn employee(id: i32, role: Role, salary: i64) -> Employee {
+    Employee {
+        id,
+        name: format!("Employee {}", id),
+        role,
+        salary: usd(salary),
+        manager_id: None,
+        level: 1,
+    }
+}
+
+impl Employee {
+    pub fn named(self, name: &str) -> Employee {
+        Employee {
+            name: String::from(name),
+            ..self
+        }
+    }
+
+    pub fn reporting_to(self, manager_id: i32) -> Employee {
+        Employee {
+            manager_id: Some(manager_id),
+            ..self
+        }
+    }
+
+    pub fn at_level(self, level: u8) -> Employee {
+        Employee { level, ..self }
+    }
+}
@@ -3,11 +3,53 @@ pub mod models;
 pub mod services;
 
 // Re-export commonly used items for convenience
-pub use models::{book::Book, user::User};
-pub use services::{library::Library, auth::Auth};
+pub use models::{
+    acquisition::{Fund, Order, OrderStatus, PurchaseSuggestion, SuggestionStatus},
+    book::{Book, BookBuilder, Contributor, ContributorRole, Series},
+    calendar::{Closure, IcsError, LibraryCalendar, OpeningHours},
+    copy::{BookCopy, CopyStatus},
+    ill::{
+        BorrowState, BorrowingRequest, IllBody, IllMessage, IllParseError, LendState,
+        LendingRequest,
+    },
+    loan::{BorrowEvent, Hold, Loan},
+    policy::{LoanPolicy, RenewalBase, RetentionPolicy},
+    review::{RatingSummary, Review, ReviewFlag, ReviewStatus},
+    shelf::Shelf,
+    user::User,
+};
+pub use services::{
+    acquisitions::{AcquisitionError, FundBalance, OrderLine, Receipt},
+    auth::Auth,
+    barcode::{Barcode, BarcodeError, Identifier, Label, RenderOptions, SheetLayout, Symbology},
+    circulation::{CirculationError, RenewalRefusal},
+    clock::Clock,
+    collation::{Collation, SortKey},
+    duplicates::{
+        normalize_isbn, DuplicateCluster, DuplicateMatch, MatchReason, MergeError, MergeSummary,
+        DEFAULT_DUPLICATE_THRESHOLD,
+    },
+    graphql::{GraphqlApi, GraphqlServer, LibrarySchema, Viewer},
+    ill::{IllError, IllPartner, IllServer},
+    library::Library,
+    recommend::{Recommendation, RecommendationReason, Recommender},
+    retention::{LoanStatistics, PurgeReport},
+    reviews::{ModerationAction, ReviewError},
+    scanner::{DeskEvent, DeskSession, ScanError, ScannerConfig},
+    shelves::ShelfError,
+    simulation::{
+        compare_policies, simulate, DailySnapshot, SimulationConfig, SimulationError,
+        SimulationReport,
+    },
+    stocktake::{
+        CopyOnLoan, CorrectionOptions, CorrectionSummary, MisplacedCopy, ReconciliationReport,
+        StocktakeSession,
+    },
+    transactions::{Edit, EditBatch, EditError, HISTORY_LIMIT},
+};
 
 // Library crate configuration and initialization
 pub fn init() -> Library {
     println!("Initializing library system...");
     Library::new()
-}
\ No newline at end of file
+}
@@ -1,5 +1,14 @@
 // Import the library crate's functionality
-use library_system::{init, Book, User};
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use library_system::services::barcode::{label_sheets, patron_barcode};
+use library_system::{
+    compare_policies, init, Book, Collation, ContributorRole, CorrectionOptions, DeskSession, Edit,
+    GraphqlApi, GraphqlServer, IllServer, Label, Library, LibraryCalendar, LoanPolicy, OrderLine,
+    ScannerConfig, SheetLayout, SimulationConfig, Symbology, User, DEFAULT_DUPLICATE_THRESHOLD,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
 
 fn main() {
     // Initialize the library system
@@ -13,12 +22,395 @@ fn main() {
     );
     library.add_book(book);
 
-    // Register a user
+    // Books with richer metadata are put together with the builder
+    let translated = Book::builder(2, "The Three-Body Problem")
+        .author("Liu Cixin")
+        .translator("Ken Liu")
+        .publisher("Tor Books")
+        .published(NaiveDate::from_ymd_opt(2014, 11, 11).unwrap())
+        .language("en")
+        .subject("Science fiction")
+        .series("Remembrance of Earth's Past", 1)
+        .page_count(400)
+        .build();
+    println!(
+        "{} by {} (translated by {})",
+        translated.title,
+        translated.author_line(),
+        translated
+            .contributors_with_role(ContributorRole::Translator)
+            .join(", ")
+    );
+    library.add_book(translated);
+
+    // Register users
     let user = User::new(1, "dzikrisyairozi");
     library.register_user(user);
+    library.register_user(User::new(2, "lebronjames"));
 
     // Demonstrate accessing a book
     if let Some(book) = library.get_book(1) {
         println!("Found book: {:?}", book);
     }
-}
\ No newline at end of file
+
+    // Set up opening hours so due dates never fall on a closed day
+    let mut calendar = LibraryCalendar::always_open();
+    calendar.close_on(Weekday::Sun);
+    calendar.set_hours(
+        Weekday::Sat,
+        NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+        NaiveTime::from_hms_opt(14, 0, 0).unwrap(),
+    );
+    calendar.add_holiday(NaiveDate::from_ymd_opt(2025, 12, 25).unwrap());
+    match calendar.load_closures_from_ics("closures.ics") {
+        Ok(count) => println!("Loaded {} closures from closures.ics", count),
+        Err(e) => println!("Could not load closures: {}", e),
+    }
+    library.set_calendar(calendar);
+
+    // Borrow the book and renew the loan
+    let loan_id = library.checkout(1, 1).expect("book should be available");
+    if let Some(loan) = library.get_loan(loan_id) {
+        println!(
+            "Loan {} due on {}",
+            loan.id,
+            loan.due_date.format("%Y-%m-%d")
+        );
+    }
+    match library.renew(loan_id) {
+        Ok(due_date) => println!("Renewed until {}", due_date.format("%Y-%m-%d")),
+        Err(reason) => println!("Renewal refused: {}", reason),
+    }
+
+    // Once someone else is waiting for the book, renewals are refused
+    library.place_hold(1, 2).expect("hold should be accepted");
+    if let Err(reason) = library.renew(loan_id) {
+        println!("Renewal refused: {}", reason);
+    }
+
+    // Suggest what to read next based on everyone's borrowing history
+    for pick in library.recommend_for(2, 3) {
+        if let Some(book) = library.get_book(pick.book_id) {
+            println!("Recommended for user 2: {} ({:?})", book.title, pick.reason);
+        }
+    }
+
+    // Keep a personal reading list and share an opinion
+    library
+        .create_shelf(2, "Want to read")
+        .expect("shelf name should be free");
+    library
+        .add_to_shelf(2, "Want to read", 2)
+        .expect("book and shelf should exist");
+    library
+        .add_review(1, 1, 5, "Clear and thorough.")
+        .expect("first review of this title");
+    if let Some(rating) = library.rating_for(1) {
+        println!(
+            "Rated {:.1}/5 from {} review(s)",
+            rating.average, rating.count
+        );
+    }
+
+    // Returned loans lose their link to the borrower after the retention period
+    library.set_keep_history(2, true);
+    let report = library.purge_loan_history();
+    println!(
+        "Purge anonymised {} loan(s); {} loan(s) on record in total",
+        report.anonymised,
+        library.loan_statistics().total_loans
+    );
+
+    // Register physical copies and print their labels
+    let copy_id = library
+        .add_copy(2, "Main/Science Fiction")
+        .expect("book 2 exists");
+    let labels: Vec<Label> = [library.copy_label(copy_id, Symbology::Code128)]
+        .into_iter()
+        .chain([library.patron_card_label(2, Symbology::Ean13)])
+        .flatten()
+        .collect();
+    let pages = label_sheets(&labels, &SheetLayout::default()).expect("default layout has room");
+    println!(
+        "Printed {} label(s) on {} sheet(s)",
+        labels.len(),
+        pages.len()
+    );
+
+    // Scan a patron card and then a copy at the circulation desk
+    let mut desk = DeskSession::new(ScannerConfig::default());
+    for line in [
+        format!("{}\n", patron_barcode(2)),
+        format!("{}\n", library.get_copy(copy_id).unwrap().barcode),
+    ] {
+        match desk.scan(&mut library, &line) {
+            Ok(event) => println!("Desk: {:?}", event),
+            Err(e) => println!("Desk error: {}", e),
+        }
+    }
+
+    // Count the science fiction shelf and reconcile it with the catalogue
+    let mut stocktake = library.start_stocktake("Main/Science Fiction");
+    stocktake.record(&library.get_copy(copy_id).unwrap().barcode.clone());
+    let report = library.reconcile(&stocktake);
+    println!(
+        "Stocktake: {} missing, {} on loan but on the shelf, {} unknown",
+        report.missing.len(),
+        report.on_loan.len(),
+        report.unknown_barcodes.len()
+    );
+    let summary = library.apply_reconciliation(&report, CorrectionOptions::default());
+    println!(
+        "Checked in {} copy(ies) found on the shelf",
+        summary.checked_in
+    );
+
+    // A patron suggests a purchase; a librarian orders it and receives it
+    let mut acquisitions_librarian = User::new(3, "acquisitions");
+    acquisitions_librarian.is_librarian = true;
+    library.register_user(acquisitions_librarian);
+    library
+        .create_fund(3, "SF", "Science fiction", 20000)
+        .expect("new fund");
+    let suggestion = library
+        .suggest_purchase(2, "The Dark Forest", "Liu Cixin", "Sequel to our copy")
+        .expect("registered user");
+    library
+        .approve_suggestion(3, suggestion)
+        .expect("librarian");
+    let line = OrderLine {
+        vendor: "Book Wholesale Ltd".to_string(),
+        fund_code: "SF".to_string(),
+        title: "The Dark Forest".to_string(),
+        author: "Liu Cixin".to_string(),
+        quantity: 2,
+        unit_cost: 1800,
+        location: "Main/Science Fiction".to_string(),
+    };
+    match library.place_order(3, line, Some(suggestion)) {
+        Ok(order_id) => {
+            if let Ok(receipt) = library.receive(3, order_id, 1) {
+                println!(
+                    "Received {} copy(ies) of book {}; order is {:?}",
+                    receipt.copy_ids.len(),
+                    receipt.book_id,
+                    receipt.order_status
+                );
+            }
+        }
+        Err(e) => println!("Order failed: {}", e),
+    }
+    if let Ok(balance) = library.fund_balance("SF") {
+        println!(
+            "Fund SF: {} spent, {} committed, {} remaining (cents)",
+            balance.spent, balance.committed, balance.remaining
+        );
+    }
+
+    // Two partner libraries exchange an interlibrary loan over local TCP
+    let mut north = Library::new();
+    north.set_ill_code("NORTH");
+    north.add_book(Book::new(1, "Piranesi", "Susanna Clarke"));
+    north.register_user(User::new(90, "ill-south"));
+    let mut south = Library::new();
+    south.set_ill_code("SOUTH");
+    south.register_user(User::new(1, "reader"));
+    south.register_user(User::new(90, "ill-north"));
+    let north = Arc::new(Mutex::new(north));
+    let south = Arc::new(Mutex::new(south));
+    match (
+        IllServer::start(Arc::clone(&north), "127.0.0.1:0"),
+        IllServer::start(Arc::clone(&south), "127.0.0.1:0"),
+    ) {
+        (Ok(north_server), Ok(south_server)) => {
+            north
+                .lock()
+                .unwrap()
+                .add_ill_partner("SOUTH", south_server.addr(), 90)
+                .expect("partner account exists");
+            south
+                .lock()
+                .unwrap()
+                .add_ill_partner("NORTH", north_server.addr(), 90)
+                .expect("partner account exists");
+            let request =
+                south
+                    .lock()
+                    .unwrap()
+                    .request_ill("NORTH", 1, "Piranesi", "Susanna Clarke");
+            match request {
+                Ok(id) => {
+                    let shipped = north.lock().unwrap().ship_ill("SOUTH", id);
+                    println!("NORTH shipped request {}: {:?}", id, shipped.is_ok());
+                    let south = south.lock().unwrap();
+                    println!(
+                        "SOUTH sees request {} as {:?}",
+                        id,
+                        south.borrowing_request(id).map(|r| &r.state)
+                    );
+                }
+                Err(e) => println!("ILL request failed: {}", e),
+            }
+        }
+        _ => println!("Could not start the ILL servers"),
+    }
+
+    // Query a catalogue through the GraphQL endpoint on localhost
+    let mut catalogue = Library::new();
+    catalogue.add_book(Book::new(1, "Dune", "Frank Herbert"));
+    catalogue.add_book(Book::new(2, "Hyperion", "Dan Simmons"));
+    catalogue.register_user(User::new(1, "reader"));
+    let token = catalogue.issue_api_token(1).unwrap_or_default();
+    let api = GraphqlApi::new(Arc::new(Mutex::new(catalogue)));
+    let checkout = api.execute("mutation { checkout(bookId: 2) { dueDate } }", Some(1));
+    println!("GraphQL checkout errors: {}", checkout.errors.len());
+    match GraphqlServer::start(api, "127.0.0.1:0") {
+        Ok(server) => {
+            let body = r#"{"query":"{ viewer { username } books(first: 2) { edges { node { title isAvailable } } } }"}"#;
+            let mut response = String::new();
+            let sent = TcpStream::connect(server.addr()).and_then(|mut stream| {
+                write!(
+                    stream,
+                    "POST /graphql HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\n\
+                     Content-Length: {}\r\n\r\n{}",
+                    token,
+                    body.len(),
+                    body
+                )?;
+                stream.read_to_string(&mut response)
+            });
+            match sent {
+                Ok(_) => println!(
+                    "GraphQL at http://{}/graphql answered: {}",
+                    server.addr(),
+                    response.split("\r\n\r\n").nth(1).unwrap_or_default()
+                ),
+                Err(e) => println!("GraphQL request failed: {}", e),
+            }
+        }
+        Err(e) => println!("Could not start the GraphQL server: {}", e),
+    }
+
+    // List a catalogue in library filing order
+    let mut shelf_list = Library::new();
+    for (id, title, author) in [
+        (1, "Vol. 10: The Return", "Walter de la Mare"),
+        (2, "The Émigrés", "W. G. Sebald"),
+        (3, "Vol. 2: The Journey", "Walter de la Mare"),
+        (4, "Der Zauberberg", "Thomas Mann"),
+        (5, "A Room of One's Own", "Virginia Woolf"),
+    ] {
+        shelf_list.add_book(Book::new(id, title, author));
+    }
+    let collation = Collation::for_locale("en-GB").unwrap_or_default();
+    let titles: Vec<&str> = shelf_list
+        .books_by_title(&collation)
+        .iter()
+        .map(|b| b.title.as_str())
+        .collect();
+    println!("By title: {}", titles.join(" | "));
+    let authors: Vec<String> = shelf_list
+        .books_by_author(&collation)
+        .iter()
+        .map(|b| collation.inverted_name(&b.author_line()))
+        .collect();
+    println!("By author: {}", authors.join(" | "));
+
+    // Simulate three months of circulation under two loan periods
+    let config = SimulationConfig::default();
+    let policies = [
+        LoanPolicy::default(),
+        LoanPolicy {
+            loan_days: 14,
+            ..LoanPolicy::default()
+        },
+    ];
+    let reports = compare_policies(&policies, &config).expect("default config is valid");
+    for report in reports {
+        println!(
+            "{}-day loans: {:.0}% of requests met from the shelf, \
+             {:.1} holds waiting on average, {:.1} days average wait",
+            report.policy.loan_days,
+            report.availability * 100.0,
+            report.average_queue_length,
+            report.average_wait_days
+        );
+    }
+
+    // Re-catalogue in a batch that applies all or nothing, then undo it
+    let mut recatalogued = library.get_book(1).unwrap().clone();
+    recatalogued.subjects = vec!["Programming".to_string(), "Rust".to_string()];
+    let batch = vec![
+        Edit::UpdateBook(recatalogued),
+        Edit::AddCopy {
+            book_id: 1,
+            location: "Main/Computing".to_string(),
+        },
+        Edit::RemoveBook(999),
+    ];
+    if let Err(e) = library.apply_batch(3, "tag computing books", batch.clone()) {
+        println!("Batch refused: {}", e);
+    }
+    library
+        .apply_batch(3, "tag computing books", batch[..2].to_vec())
+        .unwrap();
+    println!(
+        "Subjects after batch: {:?}",
+        library.get_book(1).unwrap().subjects
+    );
+    let undone = library.undo(3).unwrap();
+    println!(
+        "Undid {:?}; subjects now {:?}",
+        undone,
+        library.get_book(1).unwrap().subjects
+    );
+
+    // Find records imported twice and merge them
+    let mut imported = Library::new();
+    let mut cataloguer = User::new(1, "cataloguer");
+    cataloguer.is_librarian = true;
+    imported.register_user(cataloguer);
+    imported.add_book(
+        Book::builder(1, "The Hobbit")
+            .author("J. R. R. Tolkien")
+            .build(),
+    );
+    imported.add_book(
+        Book::builder(2, "Hobbit, The")
+            .author("Tolkien, J.R.R.")
+            .build(),
+    );
+    imported.add_book(
+        Book::builder(3, "Hobbit")
+            .isbn("0-261-10221-4")
+            .publisher("HarperCollins")
+            .build(),
+    );
+    imported.add_book(
+        Book::builder(4, "The Hobbit")
+            .author("J.R.R. Tolkien")
+            .isbn("978-0-261-10221-7")
+            .build(),
+    );
+    imported.add_copy(2, "Main/Fiction").unwrap();
+    for cluster in imported.find_duplicates(&Collation::english(), DEFAULT_DUPLICATE_THRESHOLD) {
+        println!(
+            "Possible duplicates {:?} (score {:.2}), keeping {}",
+            cluster.book_ids, cluster.score, cluster.suggested_survivor
+        );
+        let others: Vec<u32> = cluster
+            .book_ids
+            .iter()
+            .copied()
+            .filter(|&id| id != cluster.suggested_survivor)
+            .collect();
+        let summary = imported
+            .merge_books(1, cluster.suggested_survivor, &others)
+            .unwrap();
+        let book = imported.get_book(summary.survivor).unwrap();
+        println!(
+            "Merged {:?} into {}: {} copies moved, ISBN {:?}",
+            summary.removed, summary.survivor, summary.copies, book.isbn
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// A patron's request that the library buy a title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurchaseSuggestion {
+    pub id: u32,
+    pub user_id: u32,
+    pub title: String,
+    pub author: String,
+    pub note: String,
+    pub status: SuggestionStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SuggestionStatus {
+    Pending,
+    Approved,
+    Rejected { reason: String },
+    Ordered { order_id: u32 },
+}
+
+// A budget line that orders are paid from. Amounts are in cents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fund {
+    pub code: String,
+    pub name: String,
+    pub allocated: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Open,
+    PartiallyReceived,
+    Received,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub id: u32,
+    pub vendor: String,
+    pub fund_code: String,
+    pub title: String,
+    pub author: String,
+    pub quantity: u32,
+    // Price of one copy in cents.
+    pub unit_cost: u64,
+    pub received: u32,
+    // Where received copies are shelved.
+    pub location: String,
+    pub status: OrderStatus,
+    pub suggestion_id: Option<u32>,
+    // The catalogue record created when the first copy arrives.
+    pub book_id: Option<u32>,
+}
+
+impl Order {
+    pub fn outstanding(&self) -> u32 {
+        self.quantity - self.received
+    }
+}
@@ -1,22 +1,196 @@
-use chrono::{DateTime, Utc};
+use chrono::NaiveDate;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContributorRole {
+    Author,
+    Editor,
+    Translator,
+}
+
+impl fmt::Display for ContributorRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let role_str = match self {
+            ContributorRole::Author => "author",
+            ContributorRole::Editor => "editor",
+            ContributorRole::Translator => "translator",
+        };
+        write!(f, "{}", role_str)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contributor {
+    pub name: String,
+    pub role: ContributorRole,
+}
+
+// A book's place in a series, e.g. volume 2 of "The Expanse".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Series {
+    pub name: String,
+    pub position: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Book {
     pub id: u32,
     pub title: String,
-    pub author: String,
-    pub published_date: DateTime<Utc>,
+    pub contributors: Vec<Contributor>,
+    pub publisher: Option<String>,
+    pub published_date: Option<NaiveDate>,
+    // ISO 639 language code, e.g. "en".
+    pub language: Option<String>,
+    pub subjects: Vec<String>,
+    pub series: Option<Series>,
+    pub page_count: Option<u32>,
+    // As catalogued; ISBN-10 or ISBN-13, with or without hyphens.
+    pub isbn: Option<String>,
     pub is_available: bool,
 }
 
 impl Book {
     pub fn new(id: u32, title: &str, author: &str) -> Self {
-        Book {
-            id,
-            title: title.to_string(),
-            author: author.to_string(),
-            published_date: Utc::now(),
-            is_available: true,
+        Book::builder(id, title).author(author).build()
+    }
+
+    pub fn builder(id: u32, title: &str) -> BookBuilder {
+        BookBuilder {
+            book: Book {
+                id,
+                title: title.to_string(),
+                contributors: Vec::new(),
+                publisher: None,
+                published_date: None,
+                language: None,
+                subjects: Vec::new(),
+                series: None,
+                page_count: None,
+                isbn: None,
+                is_available: true,
+            },
         }
     }
-}
\ No newline at end of file
+
+    pub fn contributors_with_role(&self, role: ContributorRole) -> Vec<&str> {
+        self.contributors
+            .iter()
+            .filter(|c| c.role == role)
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    pub fn authors(&self) -> Vec<&str> {
+        self.contributors_with_role(ContributorRole::Author)
+    }
+
+    // Authors joined for display, e.g. "Ann Leckie, Becky Chambers".
+    pub fn author_line(&self) -> String {
+        self.authors().join(", ")
+    }
+}
+
+// Sets optional metadata on a book without a long constructor.
+#[derive(Debug, Clone)]
+pub struct BookBuilder {
+    book: Book,
+}
+
+impl BookBuilder {
+    pub fn contributor(mut self, name: &str, role: ContributorRole) -> Self {
+        self.book.contributors.push(Contributor {
+            name: name.to_string(),
+            role,
+        });
+        self
+    }
+
+    pub fn author(self, name: &str) -> Self {
+        self.contributor(name, ContributorRole::Author)
+    }
+
+    pub fn editor(self, name: &str) -> Self {
+        self.contributor(name, ContributorRole::Editor)
+    }
+
+    pub fn translator(self, name: &str) -> Self {
+        self.contributor(name, ContributorRole::Translator)
+    }
+
+    pub fn publisher(mut self, publisher: &str) -> Self {
+        self.book.publisher = Some(publisher.to_string());
+        self
+    }
+
+    pub fn published(mut self, date: NaiveDate) -> Self {
+        self.book.published_date = Some(date);
+        self
+    }
+
+    pub fn language(mut self, language: &str) -> Self {
+        self.book.language = Some(language.to_string());
+        self
+    }
+
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.book.subjects.push(subject.to_string());
+        self
+    }
+
+    pub fn series(mut self, name: &str, position: u32) -> Self {
+        self.book.series = Some(Series {
+            name: name.to_string(),
+            position,
+        });
+        self
+    }
+
+    pub fn page_count(mut self, pages: u32) -> Self {
+        self.book.page_count = Some(pages);
+        self
+    }
+
+    pub fn isbn(mut self, isbn: &str) -> Self {
+        self.book.isbn = Some(isbn.to_string());
+        self
+    }
+
+    pub fn build(self) -> Book {
+        self.book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_sets_metadata() {
+        let book = Book::builder(7, "The Name of the Rose")
+            .author("Umberto Eco")
+            .translator("William Weaver")
+            .publisher("Harcourt")
+            .published(NaiveDate::from_ymd_opt(1983, 6, 1).unwrap())
+            .language("en")
+            .subject("Mystery")
+            .subject("Historical fiction")
+            .page_count(512)
+            .build();
+
+        assert_eq!(book.authors(), vec!["Umberto Eco"]);
+        assert_eq!(
+            book.contributors_with_role(ContributorRole::Translator),
+            vec!["William Weaver"]
+        );
+        assert_eq!(book.subjects.len(), 2);
+        assert_eq!(book.page_count, Some(512));
+        assert!(book.series.is_none());
+    }
+
+    #[test]
+    fn test_new_has_no_publication_date() {
+        let book = Book::new(1, "Dune", "Frank Herbert");
+        assert_eq!(book.author_line(), "Frank Herbert");
+        assert!(book.published_date.is_none());
+    }
+}
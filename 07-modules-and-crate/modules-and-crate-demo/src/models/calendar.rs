@@ -0,0 +1,363 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// How far ahead we look for an open day before giving up on a calendar
+// that never opens.
+const MAX_LOOKAHEAD_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningHours {
+    pub opens: NaiveTime,
+    pub closes: NaiveTime,
+}
+
+// A period the branch is closed outside its usual schedule, e.g. for
+// renovation. Both ends are inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Closure {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub reason: String,
+}
+
+impl Closure {
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcsError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for IcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for IcsError {}
+
+// Weekly opening hours plus public holidays and ad-hoc closures.
+#[derive(Debug, Clone)]
+pub struct LibraryCalendar {
+    weekly: [Option<OpeningHours>; 7],
+    holidays: HashSet<NaiveDate>,
+    closures: Vec<Closure>,
+}
+
+impl LibraryCalendar {
+    // A calendar that is open around the clock every day.
+    pub fn always_open() -> Self {
+        let hours = OpeningHours {
+            opens: NaiveTime::MIN,
+            closes: NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        };
+        LibraryCalendar {
+            weekly: [Some(hours); 7],
+            holidays: HashSet::new(),
+            closures: Vec::new(),
+        }
+    }
+
+    // A calendar with no opening hours set yet; every day is closed.
+    pub fn closed() -> Self {
+        LibraryCalendar {
+            weekly: [None; 7],
+            holidays: HashSet::new(),
+            closures: Vec::new(),
+        }
+    }
+
+    pub fn set_hours(&mut self, day: Weekday, opens: NaiveTime, closes: NaiveTime) {
+        self.weekly[day.num_days_from_monday() as usize] = Some(OpeningHours { opens, closes });
+    }
+
+    pub fn close_on(&mut self, day: Weekday) {
+        self.weekly[day.num_days_from_monday() as usize] = None;
+    }
+
+    pub fn hours_on(&self, day: Weekday) -> Option<OpeningHours> {
+        self.weekly[day.num_days_from_monday() as usize]
+    }
+
+    pub fn add_holiday(&mut self, date: NaiveDate) {
+        self.holidays.insert(date);
+    }
+
+    pub fn add_closure(&mut self, closure: Closure) {
+        self.closures.push(closure);
+    }
+
+    pub fn closures(&self) -> &[Closure] {
+        &self.closures
+    }
+
+    pub fn is_open(&self, date: NaiveDate) -> bool {
+        self.hours_on(date.weekday()).is_some()
+            && !self.holidays.contains(&date)
+            && !self.closures.iter().any(|c| c.covers(date))
+    }
+
+    pub fn is_open_at(&self, time: DateTime<Utc>) -> bool {
+        let date = time.date_naive();
+        match self.hours_on(date.weekday()) {
+            Some(hours) if self.is_open(date) => {
+                let t = time.time();
+                hours.opens <= t && t <= hours.closes
+            }
+            _ => false,
+        }
+    }
+
+    // The first open day on or after `date`, if one exists within a year.
+    pub fn next_open_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        (0..MAX_LOOKAHEAD_DAYS)
+            .map(|offset| date + Duration::days(offset))
+            .find(|d| self.is_open(*d))
+    }
+
+    // Moves a due date forward to the next open day, keeping the time of day.
+    pub fn adjust_due_date(&self, due: DateTime<Utc>) -> DateTime<Utc> {
+        match self.next_open_day(due.date_naive()) {
+            Some(date) => due + (date - due.date_naive()),
+            None => due,
+        }
+    }
+
+    // Open days in the half-open range `(from, to]`.
+    pub fn open_days_between(&self, from: NaiveDate, to: NaiveDate) -> i64 {
+        let mut count = 0;
+        let mut date = from + Duration::days(1);
+        while date <= to {
+            if self.is_open(date) {
+                count += 1;
+            }
+            date += Duration::days(1);
+        }
+        count
+    }
+
+    pub fn load_closures_from_ics<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, IcsError> {
+        let content = fs::read_to_string(path).map_err(|e| IcsError {
+            line: 0,
+            message: e.to_string(),
+        })?;
+        let closures = parse_ics(&content)?;
+        let count = closures.len();
+        self.closures.extend(closures);
+        Ok(count)
+    }
+}
+
+impl Default for LibraryCalendar {
+    fn default() -> Self {
+        LibraryCalendar::always_open()
+    }
+}
+
+// Reads every VEVENT of an iCalendar document as a closure. All-day events
+// use an exclusive DTEND, as in RFC 5545; a timed event closes the branch
+// on the day it ends as well.
+pub fn parse_ics(content: &str) -> Result<Vec<Closure>, IcsError> {
+    let mut closures = Vec::new();
+    let mut event: Option<(Option<IcsDate>, Option<IcsDate>, String)> = None;
+    let mut event_line = 0;
+
+    for (line_no, line) in unfold_lines(content) {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value.trim()),
+            None => continue,
+        };
+        // Property parameters such as ";VALUE=DATE" are not needed here.
+        let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+
+        match (name.as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some((None, None, String::new()));
+                event_line = line_no;
+            }
+            ("BEGIN", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                return Err(IcsError {
+                    line: line_no,
+                    message: "VEVENT nested inside another event".to_string(),
+                });
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                let (start, end, reason) = event.take().unwrap();
+                let start = start.ok_or(IcsError {
+                    line: event_line,
+                    message: "event has no DTSTART".to_string(),
+                })?;
+                let start = start.date;
+                let end = match end {
+                    Some(end) if end.all_day && end.date > start => end.date - Duration::days(1),
+                    Some(end) if !end.all_day && end.date > start => end.date,
+                    _ => start,
+                };
+                closures.push(Closure { start, end, reason });
+            }
+            ("DTSTART", Some(ev)) => ev.0 = Some(parse_ics_date(value, line_no)?),
+            ("DTEND", Some(ev)) => ev.1 = Some(parse_ics_date(value, line_no)?),
+            ("SUMMARY", Some(ev)) => ev.2 = value.to_string(),
+            _ => {}
+        }
+    }
+
+    if event.is_some() {
+        return Err(IcsError {
+            line: event_line,
+            message: "event is missing END:VEVENT".to_string(),
+        });
+    }
+    Ok(closures)
+}
+
+// Joins folded continuation lines, keeping the number of the first line.
+fn unfold_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, raw) in content.lines().enumerate() {
+        let raw = raw.trim_end_matches('\r');
+        if raw.starts_with(' ') || raw.starts_with('\t') {
+            if let Some((_, last)) = lines.last_mut() {
+                last.push_str(&raw[1..]);
+                continue;
+            }
+        }
+        lines.push((index + 1, raw.to_string()));
+    }
+    lines
+}
+
+// The day of a DTSTART or DTEND, and whether it was a DATE value rather
+// than a DATE-TIME.
+#[derive(Debug, Clone, Copy)]
+struct IcsDate {
+    date: NaiveDate,
+    all_day: bool,
+}
+
+// Accepts both DATE ("20241225") and DATE-TIME ("20241225T090000Z") values.
+fn parse_ics_date(value: &str, line: usize) -> Result<IcsDate, IcsError> {
+    let date = value.get(..8).unwrap_or(value);
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| IcsError {
+        line,
+        message: format!("invalid date '{}'", value),
+    })?;
+    Ok(IcsDate {
+        date,
+        all_day: !value.contains('T'),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn weekday_calendar() -> LibraryCalendar {
+        let mut calendar = LibraryCalendar::closed();
+        let opens = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let closes = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        for day in [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ] {
+            calendar.set_hours(day, opens, closes);
+        }
+        calendar
+    }
+
+    #[test]
+    fn test_next_open_day_skips_weekend_and_holiday() {
+        let mut calendar = weekday_calendar();
+        // 2024-03-30 is a Saturday; Monday 2024-04-01 is Easter Monday.
+        calendar.add_holiday(date(2024, 4, 1));
+        assert_eq!(
+            calendar.next_open_day(date(2024, 3, 30)),
+            Some(date(2024, 4, 2))
+        );
+    }
+
+    #[test]
+    fn test_parse_ics_closures() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   DTSTART;VALUE=DATE:20240610\r\n\
+                   DTEND;VALUE=DATE:20240613\r\n\
+                   SUMMARY:Roof\r\n  repairs\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   DTSTART:20240701T080000Z\r\n\
+                   SUMMARY:Staff training\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let closures = parse_ics(ics).unwrap();
+        assert_eq!(
+            closures,
+            vec![
+                Closure {
+                    start: date(2024, 6, 10),
+                    end: date(2024, 6, 12),
+                    reason: "Roof repairs".to_string(),
+                },
+                Closure {
+                    start: date(2024, 7, 1),
+                    end: date(2024, 7, 1),
+                    reason: "Staff training".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ics_keeps_last_day_of_timed_event() {
+        let ics = "BEGIN:VEVENT\n\
+                   DTSTART:20240701T080000Z\n\
+                   DTEND:20240702T120000Z\n\
+                   END:VEVENT\n";
+        let closures = parse_ics(ics).unwrap();
+        assert_eq!(closures[0].start, date(2024, 7, 1));
+        assert_eq!(closures[0].end, date(2024, 7, 2));
+    }
+
+    #[test]
+    fn test_parse_ics_rejects_nested_event() {
+        let ics = "BEGIN:VEVENT\nDTSTART:20240610\nBEGIN:VEVENT\nEND:VEVENT\nEND:VEVENT\n";
+        let err = parse_ics(ics).unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn test_parse_ics_reports_bad_date() {
+        let ics = "BEGIN:VEVENT\nDTSTART:2024-06-10\nEND:VEVENT\n";
+        let err = parse_ics(ics).unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+
+    #[test]
+    fn test_open_days_between_excludes_closures() {
+        let mut calendar = weekday_calendar();
+        calendar.add_closure(Closure {
+            start: date(2024, 6, 11),
+            end: date(2024, 6, 12),
+            reason: "Inventory".to_string(),
+        });
+        // Mon 10th to Mon 17th: Tue/Wed closed, weekend closed.
+        assert_eq!(
+            calendar.open_days_between(date(2024, 6, 10), date(2024, 6, 17)),
+            3
+        );
+    }
+}
@@ -0,0 +1,29 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyStatus {
+    InCirculation,
+    // Not found at the last stocktake; not offered for checkout.
+    Missing,
+}
+
+// A physical copy of a book, identified at the desk by its barcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookCopy {
+    pub id: u32,
+    pub book_id: u32,
+    pub barcode: String,
+    // Shelf or branch where the copy belongs, e.g. "Main/Fiction".
+    pub location: String,
+    pub status: CopyStatus,
+}
+
+impl BookCopy {
+    // Whether the copy belongs to `location` or one of its sub-locations,
+    // so "Main" covers "Main/Fiction".
+    pub fn is_in(&self, location: &str) -> bool {
+        self.location == location
+            || self
+                .location
+                .strip_prefix(location)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
@@ -0,0 +1,210 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+// What an interlibrary loan message says. Requests are always identified by
+// the id the borrowing library gave them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IllBody {
+    Request { title: String, author: String },
+    Accepted,
+    Unfilled { reason: String },
+    Shipped { due: DateTime<Utc> },
+    Received,
+    Returned,
+    Cancel { reason: String },
+    Ack,
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllMessage {
+    // Code of the library sending the message.
+    pub from: String,
+    pub request_id: u32,
+    pub body: IllBody,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllParseError(pub String);
+
+impl fmt::Display for IllParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed ILL message: {}", self.0)
+    }
+}
+
+impl std::error::Error for IllParseError {}
+
+impl IllMessage {
+    pub fn new(from: &str, request_id: u32, body: IllBody) -> Self {
+        IllMessage {
+            from: from.to_string(),
+            request_id,
+            body,
+        }
+    }
+
+    // One line of tab-separated fields: verb, sender, request id, then any
+    // arguments. Tabs, newlines and backslashes in text are escaped.
+    pub fn encode(&self) -> String {
+        let (verb, args): (&str, Vec<String>) = match &self.body {
+            IllBody::Request { title, author } => ("REQUEST", vec![title.clone(), author.clone()]),
+            IllBody::Accepted => ("ACCEPTED", vec![]),
+            IllBody::Unfilled { reason } => ("UNFILLED", vec![reason.clone()]),
+            IllBody::Shipped { due } => ("SHIPPED", vec![due.to_rfc3339()]),
+            IllBody::Received => ("RECEIVED", vec![]),
+            IllBody::Returned => ("RETURNED", vec![]),
+            IllBody::Cancel { reason } => ("CANCEL", vec![reason.clone()]),
+            IllBody::Ack => ("ACK", vec![]),
+            IllBody::Error { message } => ("ERROR", vec![message.clone()]),
+        };
+        let mut fields = vec![
+            verb.to_string(),
+            escape(&self.from),
+            self.request_id.to_string(),
+        ];
+        fields.extend(args.iter().map(|a| escape(a)));
+        fields.join("\t")
+    }
+
+    pub fn parse(line: &str) -> Result<IllMessage, IllParseError> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let fields: Vec<String> = line.split('\t').map(unescape).collect();
+        if fields.len() < 3 {
+            return Err(IllParseError(line.to_string()));
+        }
+        let request_id = fields[2]
+            .parse()
+            .map_err(|_| IllParseError(format!("bad request id {:?}", fields[2])))?;
+        let args = &fields[3..];
+        let arg = |i: usize| {
+            args.get(i)
+                .cloned()
+                .ok_or_else(|| IllParseError(format!("{} is missing an argument", fields[0])))
+        };
+
+        let body = match fields[0].as_str() {
+            "REQUEST" => IllBody::Request {
+                title: arg(0)?,
+                author: arg(1)?,
+            },
+            "ACCEPTED" => IllBody::Accepted,
+            "UNFILLED" => IllBody::Unfilled { reason: arg(0)? },
+            "SHIPPED" => {
+                let due = DateTime::parse_from_rfc3339(&arg(0)?)
+                    .map_err(|e| IllParseError(format!("bad due date: {}", e)))?;
+                IllBody::Shipped {
+                    due: due.with_timezone(&Utc),
+                }
+            }
+            "RECEIVED" => IllBody::Received,
+            "RETURNED" => IllBody::Returned,
+            "CANCEL" => IllBody::Cancel { reason: arg(0)? },
+            "ACK" => IllBody::Ack,
+            "ERROR" => IllBody::Error { message: arg(0)? },
+            verb => return Err(IllParseError(format!("unknown verb {:?}", verb))),
+        };
+        Ok(IllMessage {
+            from: fields[1].clone(),
+            request_id,
+            body,
+        })
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+// Where a request stands at the borrowing library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BorrowState {
+    Accepted,
+    Unfilled { reason: String },
+    Shipped { due: DateTime<Utc> },
+    Received { due: DateTime<Utc> },
+    Returned,
+    Cancelled { reason: String },
+}
+
+// An item this library asked a partner for, on behalf of one of its patrons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowingRequest {
+    pub id: u32,
+    pub lender: String,
+    pub patron_id: u32,
+    pub title: String,
+    pub author: String,
+    pub state: BorrowState,
+}
+
+// Where a request stands at the lending library. `Returned` means the
+// borrower has sent the item back; it is `Completed` once checked in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LendState {
+    Accepted,
+    Shipped { loan_id: u32 },
+    Received { loan_id: u32 },
+    Returned { loan_id: u32 },
+    Completed,
+    Cancelled { reason: String },
+}
+
+// An item a partner asked this library for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LendingRequest {
+    pub borrower: String,
+    pub request_id: u32,
+    pub book_id: u32,
+    pub state: LendState,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_messages_round_trip() {
+        let due = Utc.with_ymd_and_hms(2024, 3, 1, 17, 0, 0).unwrap();
+        let messages = [
+            IllBody::Request {
+                title: "Tabs\tand\\slashes".to_string(),
+                author: "Line\nbreak".to_string(),
+            },
+            IllBody::Shipped { due },
+            IllBody::Cancel {
+                reason: String::new(),
+            },
+            IllBody::Ack,
+        ];
+        for body in messages {
+            let message = IllMessage::new("NORTH", 7, body);
+            let line = message.encode();
+            assert!(!line.contains('\n'));
+            assert_eq!(IllMessage::parse(&line), Ok(message));
+        }
+        assert!(IllMessage::parse("REQUEST\tNORTH\t7\tonly a title").is_err());
+        assert!(IllMessage::parse("HELLO\tNORTH\t7").is_err());
+    }
+}
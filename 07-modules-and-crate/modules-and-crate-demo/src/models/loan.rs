@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+
+// A single checkout of a book by a user. Returned loans are kept as records;
+// `user_id` is cleared once the retention policy anonymises them.
+#[derive(Debug, Clone)]
+pub struct Loan {
+    pub id: u32,
+    pub book_id: u32,
+    // The physical copy lent out, when the book has registered copies.
+    pub copy_id: Option<u32>,
+    pub user_id: Option<u32>,
+    pub checked_out: DateTime<Utc>,
+    pub due_date: DateTime<Utc>,
+    pub renewals: u32,
+    pub returned: Option<DateTime<Utc>>,
+}
+
+impl Loan {
+    pub fn is_active(&self) -> bool {
+        self.returned.is_none()
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.is_active() && now > self.due_date
+    }
+}
+
+// A user's place in the queue for a book that is currently out.
+#[derive(Debug, Clone)]
+pub struct Hold {
+    pub book_id: u32,
+    pub user_id: u32,
+    pub placed: DateTime<Utc>,
+}
+
+// Who borrowed which book and when, as used for statistics and recommendations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowEvent {
+    pub user_id: u32,
+    pub book_id: u32,
+    pub borrowed: DateTime<Utc>,
+}
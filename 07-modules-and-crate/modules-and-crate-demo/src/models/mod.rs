@@ -1,3 +1,11 @@
 // Models module declaration - groups all data structures
+pub mod acquisition;
 pub mod book;
-pub mod user;
\ No newline at end of file
+pub mod calendar;
+pub mod copy;
+pub mod ill;
+pub mod loan;
+pub mod policy;
+pub mod review;
+pub mod shelf;
+pub mod user;
@@ -0,0 +1,45 @@
+// Where a renewed loan's new due date is counted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenewalBase {
+    // Extend from the current due date, so renewing early loses nothing.
+    DueDate,
+    // Restart the loan period from the moment of renewal.
+    RenewalDate,
+}
+
+// Circulation rules applied by the library when lending and renewing.
+#[derive(Debug, Clone)]
+pub struct LoanPolicy {
+    pub loan_days: i64,
+    pub renewal_days: i64,
+    pub max_renewals: u32,
+    pub renewal_base: RenewalBase,
+    // Overdue fine in cents for each day the branch is open past the due date.
+    pub fine_per_day: u32,
+}
+
+impl Default for LoanPolicy {
+    fn default() -> Self {
+        LoanPolicy {
+            loan_days: 21,
+            renewal_days: 14,
+            max_renewals: 2,
+            renewal_base: RenewalBase::DueDate,
+            fine_per_day: 25,
+        }
+    }
+}
+
+// How long returned loans stay linked to the user who borrowed them.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub anonymise_after_days: i64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            anonymise_after_days: 30,
+        }
+    }
+}
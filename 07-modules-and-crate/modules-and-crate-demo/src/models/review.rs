@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+
+pub const MIN_RATING: u8 = 1;
+pub const MAX_RATING: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewStatus {
+    Published,
+    // Hidden by a librarian; kept so the decision can be revisited.
+    Hidden,
+}
+
+// A report from a user that a review breaks the rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewFlag {
+    pub reporter_id: u32,
+    pub reason: String,
+    pub flagged: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub id: u32,
+    pub book_id: u32,
+    pub user_id: u32,
+    pub rating: u8,
+    pub text: String,
+    pub written: DateTime<Utc>,
+    pub status: ReviewStatus,
+    pub flags: Vec<ReviewFlag>,
+}
+
+impl Review {
+    pub fn is_published(&self) -> bool {
+        self.status == ReviewStatus::Published
+    }
+}
+
+// Average star rating over the published reviews of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RatingSummary {
+    pub average: f64,
+    pub count: usize,
+}
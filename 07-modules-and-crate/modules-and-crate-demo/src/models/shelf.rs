@@ -0,0 +1,21 @@
+// A named reading list or shelf owned by a user, e.g. "To read".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shelf {
+    pub owner_id: u32,
+    pub name: String,
+    pub book_ids: Vec<u32>,
+}
+
+impl Shelf {
+    pub fn new(owner_id: u32, name: &str) -> Self {
+        Shelf {
+            owner_id,
+            name: name.to_string(),
+            book_ids: Vec::new(),
+        }
+    }
+
+    pub fn contains(&self, book_id: u32) -> bool {
+        self.book_ids.contains(&book_id)
+    }
+}
@@ -3,6 +3,9 @@ pub struct User {
     pub id: u32,
     pub username: String,
     pub is_librarian: bool,
+    pub is_blocked: bool,
+    // Opt-in: keep this user's full loan history instead of anonymising it.
+    pub keep_history: bool,
 }
 
 impl User {
@@ -11,6 +14,8 @@ impl User {
             id,
             username: username.to_string(),
             is_librarian: false,
+            is_blocked: false,
+            keep_history: false,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,516 @@
+use crate::models::{
+    acquisition::{Fund, Order, OrderStatus, PurchaseSuggestion, SuggestionStatus},
+    book::Book,
+};
+use crate::services::{auth::Auth, library::Library};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcquisitionError {
+    UserNotFound(u32),
+    NotLibrarian(u32),
+    SuggestionNotFound(u32),
+    SuggestionNotPending(u32),
+    SuggestionNotApproved(u32),
+    FundNotFound(String),
+    FundExists(String),
+    InsufficientFunds {
+        fund_code: String,
+        needed: u64,
+        remaining: i64,
+    },
+    OrderNotFound(u32),
+    OrderClosed(u32),
+    OverReceipt {
+        order_id: u32,
+        outstanding: u32,
+    },
+    InvalidQuantity,
+    // A cost or fund total too large to count in cents.
+    AmountOverflow,
+}
+
+impl fmt::Display for AcquisitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AcquisitionError::UserNotFound(id) => write!(f, "user {} not found", id),
+            AcquisitionError::NotLibrarian(id) => write!(f, "user {} is not a librarian", id),
+            AcquisitionError::SuggestionNotFound(id) => write!(f, "suggestion {} not found", id),
+            AcquisitionError::SuggestionNotPending(id) => {
+                write!(f, "suggestion {} has already been decided", id)
+            }
+            AcquisitionError::SuggestionNotApproved(id) => {
+                write!(f, "suggestion {} has not been approved", id)
+            }
+            AcquisitionError::FundNotFound(code) => write!(f, "fund {} not found", code),
+            AcquisitionError::FundExists(code) => write!(f, "fund {} already exists", code),
+            AcquisitionError::InsufficientFunds {
+                fund_code,
+                needed,
+                remaining,
+            } => write!(
+                f,
+                "fund {} has {} cents left but the order needs {}",
+                fund_code, remaining, needed
+            ),
+            AcquisitionError::OrderNotFound(id) => write!(f, "order {} not found", id),
+            AcquisitionError::OrderClosed(id) => write!(f, "order {} is closed", id),
+            AcquisitionError::OverReceipt {
+                order_id,
+                outstanding,
+            } => write!(
+                f,
+                "order {} only has {} copies outstanding",
+                order_id, outstanding
+            ),
+            AcquisitionError::InvalidQuantity => write!(f, "quantity must be at least 1"),
+            AcquisitionError::AmountOverflow => write!(f, "amount is too large to account for"),
+        }
+    }
+}
+
+impl std::error::Error for AcquisitionError {}
+
+// What to buy, from whom and out of which fund.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderLine {
+    pub vendor: String,
+    pub fund_code: String,
+    pub title: String,
+    pub author: String,
+    pub quantity: u32,
+    pub unit_cost: u64,
+    pub location: String,
+}
+
+// Where a fund stands, in cents. `committed` is the cost of copies ordered
+// but not yet received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundBalance {
+    pub allocated: u64,
+    pub spent: u64,
+    pub committed: u64,
+    pub remaining: i64,
+}
+
+// The catalogue changes made by receiving part of an order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub book_id: u32,
+    pub copy_ids: Vec<u32>,
+    pub order_status: OrderStatus,
+}
+
+// The cost in cents of `quantity` copies at `unit_cost` each.
+fn line_cost(unit_cost: u64, quantity: u32) -> Result<u64, AcquisitionError> {
+    unit_cost
+        .checked_mul(quantity as u64)
+        .ok_or(AcquisitionError::AmountOverflow)
+}
+
+fn to_signed(cents: u64) -> Result<i64, AcquisitionError> {
+    i64::try_from(cents).map_err(|_| AcquisitionError::AmountOverflow)
+}
+
+impl Library {
+    pub fn suggest_purchase(
+        &mut self,
+        user_id: u32,
+        title: &str,
+        author: &str,
+        note: &str,
+    ) -> Result<u32, AcquisitionError> {
+        if !self.users.contains_key(&user_id) {
+            return Err(AcquisitionError::UserNotFound(user_id));
+        }
+        let id = self.next_suggestion_id;
+        self.next_suggestion_id += 1;
+        self.suggestions.insert(
+            id,
+            PurchaseSuggestion {
+                id,
+                user_id,
+                title: title.trim().to_string(),
+                author: author.trim().to_string(),
+                note: note.trim().to_string(),
+                status: SuggestionStatus::Pending,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn get_suggestion(&self, id: u32) -> Option<&PurchaseSuggestion> {
+        self.suggestions.get(&id)
+    }
+
+    pub fn pending_suggestions(&self) -> Vec<&PurchaseSuggestion> {
+        let mut pending: Vec<&PurchaseSuggestion> = self
+            .suggestions
+            .values()
+            .filter(|s| s.status == SuggestionStatus::Pending)
+            .collect();
+        pending.sort_by_key(|s| s.id);
+        pending
+    }
+
+    pub fn approve_suggestion(
+        &mut self,
+        librarian_id: u32,
+        suggestion_id: u32,
+    ) -> Result<(), AcquisitionError> {
+        self.decide_suggestion(librarian_id, suggestion_id, SuggestionStatus::Approved)
+    }
+
+    pub fn reject_suggestion(
+        &mut self,
+        librarian_id: u32,
+        suggestion_id: u32,
+        reason: &str,
+    ) -> Result<(), AcquisitionError> {
+        let status = SuggestionStatus::Rejected {
+            reason: reason.trim().to_string(),
+        };
+        self.decide_suggestion(librarian_id, suggestion_id, status)
+    }
+
+    fn decide_suggestion(
+        &mut self,
+        librarian_id: u32,
+        suggestion_id: u32,
+        status: SuggestionStatus,
+    ) -> Result<(), AcquisitionError> {
+        self.require_acquisitions_librarian(librarian_id)?;
+        let suggestion = self
+            .suggestions
+            .get_mut(&suggestion_id)
+            .ok_or(AcquisitionError::SuggestionNotFound(suggestion_id))?;
+        if suggestion.status != SuggestionStatus::Pending {
+            return Err(AcquisitionError::SuggestionNotPending(suggestion_id));
+        }
+        suggestion.status = status;
+        Ok(())
+    }
+
+    pub fn create_fund(
+        &mut self,
+        librarian_id: u32,
+        code: &str,
+        name: &str,
+        allocated: u64,
+    ) -> Result<(), AcquisitionError> {
+        self.require_acquisitions_librarian(librarian_id)?;
+        if self.funds.contains_key(code) {
+            return Err(AcquisitionError::FundExists(code.to_string()));
+        }
+        // Balances are signed, so the allocation must fit in an i64.
+        to_signed(allocated)?;
+        self.funds.insert(
+            code.to_string(),
+            Fund {
+                code: code.to_string(),
+                name: name.to_string(),
+                allocated,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn fund_balance(&self, code: &str) -> Result<FundBalance, AcquisitionError> {
+        let fund = self
+            .funds
+            .get(code)
+            .ok_or_else(|| AcquisitionError::FundNotFound(code.to_string()))?;
+        let mut spent: u64 = 0;
+        let mut committed: u64 = 0;
+        for order in self.orders.values().filter(|o| o.fund_code == code) {
+            spent = spent
+                .checked_add(line_cost(order.unit_cost, order.received)?)
+                .ok_or(AcquisitionError::AmountOverflow)?;
+            if matches!(
+                order.status,
+                OrderStatus::Open | OrderStatus::PartiallyReceived
+            ) {
+                committed = committed
+                    .checked_add(line_cost(order.unit_cost, order.outstanding())?)
+                    .ok_or(AcquisitionError::AmountOverflow)?;
+            }
+        }
+        let remaining = to_signed(fund.allocated)?
+            .checked_sub(to_signed(spent)?)
+            .ok_or(AcquisitionError::AmountOverflow)?
+            .checked_sub(to_signed(committed)?)
+            .ok_or(AcquisitionError::AmountOverflow)?;
+        Ok(FundBalance {
+            allocated: fund.allocated,
+            spent,
+            committed,
+            remaining,
+        })
+    }
+
+    // Places an order, optionally fulfilling an approved suggestion. The whole
+    // order is committed against the fund straight away.
+    pub fn place_order(
+        &mut self,
+        librarian_id: u32,
+        line: OrderLine,
+        suggestion_id: Option<u32>,
+    ) -> Result<u32, AcquisitionError> {
+        self.require_acquisitions_librarian(librarian_id)?;
+        if line.quantity == 0 {
+            return Err(AcquisitionError::InvalidQuantity);
+        }
+        if let Some(id) = suggestion_id {
+            let suggestion = self
+                .suggestions
+                .get(&id)
+                .ok_or(AcquisitionError::SuggestionNotFound(id))?;
+            if suggestion.status != SuggestionStatus::Approved {
+                return Err(AcquisitionError::SuggestionNotApproved(id));
+            }
+        }
+        let balance = self.fund_balance(&line.fund_code)?;
+        let needed = line_cost(line.unit_cost, line.quantity)?;
+        if u64::try_from(balance.remaining).map_or(true, |remaining| needed > remaining) {
+            return Err(AcquisitionError::InsufficientFunds {
+                fund_code: line.fund_code,
+                needed,
+                remaining: balance.remaining,
+            });
+        }
+
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.orders.insert(
+            id,
+            Order {
+                id,
+                vendor: line.vendor,
+                fund_code: line.fund_code,
+                title: line.title,
+                author: line.author,
+                quantity: line.quantity,
+                unit_cost: line.unit_cost,
+                received: 0,
+                location: line.location,
+                status: OrderStatus::Open,
+                suggestion_id,
+                book_id: None,
+            },
+        );
+        if let Some(suggestion) = suggestion_id.and_then(|s| self.suggestions.get_mut(&s)) {
+            suggestion.status = SuggestionStatus::Ordered { order_id: id };
+        }
+        Ok(id)
+    }
+
+    pub fn get_order(&self, id: u32) -> Option<&Order> {
+        self.orders.get(&id)
+    }
+
+    // Records copies arriving from the vendor. The first delivery creates the
+    // catalogue record; every delivery adds its copies to the shelf.
+    pub fn receive(
+        &mut self,
+        librarian_id: u32,
+        order_id: u32,
+        quantity: u32,
+    ) -> Result<Receipt, AcquisitionError> {
+        self.require_acquisitions_librarian(librarian_id)?;
+        if quantity == 0 {
+            return Err(AcquisitionError::InvalidQuantity);
+        }
+        let order = self
+            .orders
+            .get(&order_id)
+            .ok_or(AcquisitionError::OrderNotFound(order_id))?;
+        if matches!(order.status, OrderStatus::Received | OrderStatus::Cancelled) {
+            return Err(AcquisitionError::OrderClosed(order_id));
+        }
+        if quantity > order.outstanding() {
+            return Err(AcquisitionError::OverReceipt {
+                order_id,
+                outstanding: order.outstanding(),
+            });
+        }
+
+        let existing = order.book_id.filter(|id| self.books.contains_key(id));
+        let new_book = (order.title.clone(), order.author.clone());
+        let location = order.location.clone();
+        let book_id = match existing {
+            Some(book_id) => book_id,
+            None => {
+                let book_id = self.next_book_id();
+                self.add_book(Book::new(book_id, &new_book.0, &new_book.1));
+                book_id
+            }
+        };
+        let copy_ids: Vec<u32> = (0..quantity)
+            .filter_map(|_| self.add_copy(book_id, &location))
+            .collect();
+
+        let order = self.orders.get_mut(&order_id).expect("order checked above");
+        order.book_id = Some(book_id);
+        order.received += quantity;
+        order.status = if order.outstanding() == 0 {
+            OrderStatus::Received
+        } else {
+            OrderStatus::PartiallyReceived
+        };
+        Ok(Receipt {
+            book_id,
+            copy_ids,
+            order_status: order.status,
+        })
+    }
+
+    // Cancels what is still outstanding; copies already received are kept
+    // and stay charged to the fund.
+    pub fn cancel_order(
+        &mut self,
+        librarian_id: u32,
+        order_id: u32,
+    ) -> Result<(), AcquisitionError> {
+        self.require_acquisitions_librarian(librarian_id)?;
+        let order = self
+            .orders
+            .get_mut(&order_id)
+            .ok_or(AcquisitionError::OrderNotFound(order_id))?;
+        if matches!(order.status, OrderStatus::Received | OrderStatus::Cancelled) {
+            return Err(AcquisitionError::OrderClosed(order_id));
+        }
+        order.status = OrderStatus::Cancelled;
+        Ok(())
+    }
+
+    fn require_acquisitions_librarian(&self, user_id: u32) -> Result<(), AcquisitionError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or(AcquisitionError::UserNotFound(user_id))?;
+        if Auth::check_librarian(user) {
+            Ok(())
+        } else {
+            Err(AcquisitionError::NotLibrarian(user_id))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+
+    const LIBRARIAN: u32 = 1;
+    const PATRON: u32 = 2;
+
+    fn setup() -> Library {
+        let mut library = Library::new();
+        let mut librarian = User::new(LIBRARIAN, "carol");
+        librarian.is_librarian = true;
+        library.register_user(librarian);
+        library.register_user(User::new(PATRON, "alice"));
+        library.add_book(Book::new(1, "Existing", "Someone"));
+        library
+            .create_fund(LIBRARIAN, "FIC", "Adult fiction", 10000)
+            .unwrap();
+        library
+    }
+
+    fn line(quantity: u32, unit_cost: u64) -> OrderLine {
+        OrderLine {
+            vendor: "Book Wholesale Ltd".to_string(),
+            fund_code: "FIC".to_string(),
+            title: "Babel".to_string(),
+            author: "R. F. Kuang".to_string(),
+            quantity,
+            unit_cost,
+            location: "Main/Fiction".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_suggestion_to_catalogue() {
+        let mut library = setup();
+        let suggestion = library
+            .suggest_purchase(PATRON, "Babel", "R. F. Kuang", "Book club pick")
+            .unwrap();
+        assert_eq!(
+            library.approve_suggestion(PATRON, suggestion),
+            Err(AcquisitionError::NotLibrarian(PATRON))
+        );
+        assert_eq!(
+            library.place_order(LIBRARIAN, line(3, 2000), Some(suggestion)),
+            Err(AcquisitionError::SuggestionNotApproved(suggestion))
+        );
+        library.approve_suggestion(LIBRARIAN, suggestion).unwrap();
+        let order_id = library
+            .place_order(LIBRARIAN, line(3, 2000), Some(suggestion))
+            .unwrap();
+        assert_eq!(
+            library.get_suggestion(suggestion).unwrap().status,
+            SuggestionStatus::Ordered { order_id }
+        );
+
+        let first = library.receive(LIBRARIAN, order_id, 2).unwrap();
+        assert_eq!(first.book_id, 2);
+        assert_eq!(first.copy_ids.len(), 2);
+        assert_eq!(first.order_status, OrderStatus::PartiallyReceived);
+        assert_eq!(library.get_book(2).unwrap().title, "Babel");
+
+        let second = library.receive(LIBRARIAN, order_id, 1).unwrap();
+        assert_eq!(second.book_id, 2);
+        assert_eq!(second.order_status, OrderStatus::Received);
+        assert_eq!(library.copies_of(2).len(), 3);
+        assert_eq!(
+            library.receive(LIBRARIAN, order_id, 1),
+            Err(AcquisitionError::OrderClosed(order_id))
+        );
+    }
+
+    #[test]
+    fn test_fund_balance_tracks_orders() {
+        let mut library = setup();
+        let order_id = library.place_order(LIBRARIAN, line(4, 1500), None).unwrap();
+        library.receive(LIBRARIAN, order_id, 1).unwrap();
+
+        let balance = library.fund_balance("FIC").unwrap();
+        assert_eq!(balance.spent, 1500);
+        assert_eq!(balance.committed, 4500);
+        assert_eq!(balance.remaining, 4000);
+
+        assert_eq!(
+            library.place_order(LIBRARIAN, line(1, 5000), None),
+            Err(AcquisitionError::InsufficientFunds {
+                fund_code: "FIC".to_string(),
+                needed: 5000,
+                remaining: 4000,
+            })
+        );
+
+        // Cancelling releases the outstanding copies but not what was received.
+        library.cancel_order(LIBRARIAN, order_id).unwrap();
+        let balance = library.fund_balance("FIC").unwrap();
+        assert_eq!(balance.committed, 0);
+        assert_eq!(balance.remaining, 8500);
+    }
+
+    #[test]
+    fn test_order_cost_overflow_is_an_error() {
+        let mut library = setup();
+        assert_eq!(
+            library.place_order(LIBRARIAN, line(u32::MAX, u64::MAX / 2), None),
+            Err(AcquisitionError::AmountOverflow)
+        );
+        // A fund too large to hold as a signed balance is never created.
+        assert_eq!(
+            library.create_fund(LIBRARIAN, "BIG", "Endowment", u64::MAX),
+            Err(AcquisitionError::AmountOverflow)
+        );
+        assert_eq!(
+            library.fund_balance("BIG"),
+            Err(AcquisitionError::FundNotFound("BIG".to_string()))
+        );
+        assert!(library.get_order(1).is_none());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::models::user::User;
+use crate::services::library::Library;
+use rand::RngCore;
 
 pub struct Auth;
 
@@ -6,4 +8,52 @@ impl Auth {
     pub fn check_librarian(user: &User) -> bool {
         user.is_librarian
     }
-}
\ No newline at end of file
+}
+
+// Bearer tokens for API clients. Each token stands for one user until it is
+// revoked; the token is the only credential, so it must be kept secret.
+impl Library {
+    // Issues a new random token for a registered user.
+    pub fn issue_api_token(&mut self, user_id: u32) -> Option<String> {
+        if !self.users.contains_key(&user_id) {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        self.api_tokens.insert(token.clone(), user_id);
+        Some(token)
+    }
+
+    pub fn revoke_api_token(&mut self, token: &str) -> bool {
+        self.api_tokens.remove(token).is_some()
+    }
+
+    // The user a token was issued to, if it is still valid and the user still
+    // exists.
+    pub fn user_for_token(&self, token: &str) -> Option<u32> {
+        let user_id = *self.api_tokens.get(token)?;
+        self.users.contains_key(&user_id).then_some(user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_tokens_identify_users_until_revoked() {
+        let mut library = Library::new();
+        library.register_user(User::new(1, "alice"));
+        assert_eq!(library.issue_api_token(2), None);
+
+        let token = library.issue_api_token(1).unwrap();
+        assert_eq!(token.len(), 64);
+        assert_ne!(library.issue_api_token(1).unwrap(), token);
+        assert_eq!(library.user_for_token(&token), Some(1));
+        assert_eq!(library.user_for_token("1"), None);
+
+        assert!(library.revoke_api_token(&token));
+        assert_eq!(library.user_for_token(&token), None);
+    }
+}
@@ -0,0 +1,624 @@
+use crate::services::library::Library;
+use std::fmt;
+use std::fmt::Write as _;
+
+// Barcode numbers are EAN-13 shaped: a two-digit prefix from the GS1
+// "restricted circulation" range (20-29, free for in-house use), a ten-digit
+// id and a check digit. The same number can be printed as Code 128.
+pub const COPY_PREFIX: &str = "20";
+pub const PATRON_PREFIX: &str = "21";
+
+// Bar/space widths for Code 128 symbol values 0-105, plus the stop pattern.
+const CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+const CODE128_CODE_B: usize = 100;
+const CODE128_START_B: usize = 104;
+const CODE128_START_C: usize = 105;
+const CODE128_STOP: usize = 106;
+
+// EAN-13 left-hand "odd parity" digit patterns; the even (G) and right-hand
+// (R) sets are derived from these.
+const EAN_L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+// Which of the six left-hand digits use the G set, keyed by the first digit.
+const EAN_PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbology {
+    Code128,
+    Ean13,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BarcodeError {
+    InvalidCharacter(char),
+    InvalidLength(usize),
+    BadCheckDigit { expected: u32, found: u32 },
+    UnknownPrefix(String),
+    Empty,
+    EmptyLayout { columns: usize, rows: usize },
+}
+
+impl fmt::Display for BarcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarcodeError::InvalidCharacter(c) => write!(f, "character {:?} cannot be encoded", c),
+            BarcodeError::InvalidLength(len) => write!(f, "expected 12 or 13 digits, got {}", len),
+            BarcodeError::BadCheckDigit { expected, found } => {
+                write!(f, "check digit should be {} but is {}", expected, found)
+            }
+            BarcodeError::UnknownPrefix(code) => write!(f, "'{}' is not a library barcode", code),
+            BarcodeError::Empty => write!(f, "nothing to encode"),
+            BarcodeError::EmptyLayout { columns, rows } => {
+                write!(f, "a sheet of {} x {} labels has no room", columns, rows)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BarcodeError {}
+
+// What a library barcode number refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identifier {
+    Copy(u32),
+    Patron(u32),
+}
+
+// An encoded barcode as a row of modules; `true` is a dark bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barcode {
+    pub symbology: Symbology,
+    pub text: String,
+    pub modules: Vec<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    // Width of one module in pixels (PNG) or user units (SVG).
+    pub module_width: u32,
+    pub height: u32,
+    // Blank modules on each side, needed by scanners to find the code.
+    pub quiet_zone: u32,
+    pub show_text: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            module_width: 2,
+            height: 60,
+            quiet_zone: 10,
+            show_text: true,
+        }
+    }
+}
+
+impl Barcode {
+    pub fn encode(symbology: Symbology, text: &str) -> Result<Barcode, BarcodeError> {
+        match symbology {
+            Symbology::Code128 => Barcode::code128(text),
+            Symbology::Ean13 => Barcode::ean13(text),
+        }
+    }
+
+    // Digit strings are packed two per symbol with code set C; an odd final
+    // digit switches to code set B. Anything else uses code set B throughout.
+    pub fn code128(text: &str) -> Result<Barcode, BarcodeError> {
+        if text.is_empty() {
+            return Err(BarcodeError::Empty);
+        }
+        let mut values = Vec::new();
+        if text.len() >= 2 && text.bytes().all(|b| b.is_ascii_digit()) {
+            values.push(CODE128_START_C);
+            let mut pairs = text.as_bytes().chunks_exact(2);
+            for pair in pairs.by_ref() {
+                values.push(((pair[0] - b'0') * 10 + (pair[1] - b'0')) as usize);
+            }
+            if let [last] = pairs.remainder() {
+                values.push(CODE128_CODE_B);
+                values.push((last - b' ') as usize);
+            }
+        } else {
+            values.push(CODE128_START_B);
+            for c in text.chars() {
+                if !(' '..='~').contains(&c) {
+                    return Err(BarcodeError::InvalidCharacter(c));
+                }
+                values.push(c as usize - 32);
+            }
+        }
+        let checksum = values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| if i == 0 { v } else { i * v })
+            .sum::<usize>()
+            % 103;
+        values.push(checksum);
+        values.push(CODE128_STOP);
+
+        let mut modules = Vec::new();
+        for value in values {
+            for (i, width) in CODE128_PATTERNS[value].bytes().enumerate() {
+                let dark = i % 2 == 0;
+                modules.extend(std::iter::repeat_n(dark, (width - b'0') as usize));
+            }
+        }
+        Ok(Barcode {
+            symbology: Symbology::Code128,
+            text: text.to_string(),
+            modules,
+        })
+    }
+
+    // Accepts 12 digits (the check digit is added) or 13 digits (checked).
+    pub fn ean13(digits: &str) -> Result<Barcode, BarcodeError> {
+        let text = with_check_digit(digits)?;
+        let d: Vec<usize> = text.bytes().map(|b| (b - b'0') as usize).collect();
+
+        let mut pattern = String::from("101");
+        for (i, parity) in EAN_PARITY[d[0]].chars().enumerate() {
+            let l = EAN_L[d[i + 1]];
+            if parity == 'L' {
+                pattern.push_str(l);
+            } else {
+                // G is the mirror image of R, which is L with bars and spaces swapped.
+                pattern.extend(l.chars().rev().map(invert));
+            }
+        }
+        pattern.push_str("01010");
+        for &digit in &d[7..] {
+            pattern.extend(EAN_L[digit].chars().map(invert));
+        }
+        pattern.push_str("101");
+
+        Ok(Barcode {
+            symbology: Symbology::Ean13,
+            text,
+            modules: pattern.chars().map(|c| c == '1').collect(),
+        })
+    }
+
+    pub fn to_svg(&self, options: &RenderOptions) -> String {
+        let mut svg = String::new();
+        let (width, height) = self.size(options);
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = width,
+            h = height
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+        self.write_bars(
+            &mut svg,
+            0.0,
+            0.0,
+            options.module_width as f64,
+            options.height as f64,
+            options.quiet_zone as f64,
+        );
+        if options.show_text {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" text-anchor="middle">{}</text>"#,
+                width / 2,
+                height - 2,
+                TEXT_HEIGHT - 4,
+                escape_xml(&self.text)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // Grayscale PNG; the text line is left to the SVG output.
+    pub fn to_png(&self, options: &RenderOptions) -> Result<Vec<u8>, png::EncodingError> {
+        let width = (self.modules.len() as u32 + 2 * options.quiet_zone) * options.module_width;
+        let height = options.height;
+
+        let mut row = vec![255u8; width as usize];
+        for (i, &dark) in self.modules.iter().enumerate() {
+            if dark {
+                let start = (options.quiet_zone as usize + i) * options.module_width as usize;
+                row[start..start + options.module_width as usize].fill(0);
+            }
+        }
+        let pixels = row.repeat(height as usize);
+
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+        Ok(bytes)
+    }
+
+    fn size(&self, options: &RenderOptions) -> (u32, u32) {
+        let width = (self.modules.len() as u32 + 2 * options.quiet_zone) * options.module_width;
+        let text = if options.show_text { TEXT_HEIGHT } else { 0 };
+        (width, options.height + text)
+    }
+
+    // Writes one `<rect>` per run of dark modules.
+    fn write_bars(
+        &self,
+        svg: &mut String,
+        x: f64,
+        y: f64,
+        module: f64,
+        height: f64,
+        quiet_zone: f64,
+    ) {
+        let mut i = 0;
+        while i < self.modules.len() {
+            if !self.modules[i] {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < self.modules.len() && self.modules[i] {
+                i += 1;
+            }
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.3}" y="{:.3}" width="{:.3}" height="{:.3}" fill="black"/>"#,
+                x + (quiet_zone + start as f64) * module,
+                y,
+                (i - start) as f64 * module,
+                height
+            );
+        }
+    }
+}
+
+const TEXT_HEIGHT: u32 = 16;
+
+fn invert(c: char) -> char {
+    if c == '1' {
+        '0'
+    } else {
+        '1'
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// The EAN-13 check digit for the first 12 digits of `digits`.
+pub fn ean13_check_digit(digits: &str) -> Result<u32, BarcodeError> {
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_digit()) {
+        return Err(BarcodeError::InvalidCharacter(c));
+    }
+    if digits.len() < 12 {
+        return Err(BarcodeError::InvalidLength(digits.len()));
+    }
+    let sum: u32 = digits
+        .bytes()
+        .take(12)
+        .enumerate()
+        .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    Ok((10 - sum % 10) % 10)
+}
+
+fn with_check_digit(digits: &str) -> Result<String, BarcodeError> {
+    let expected = ean13_check_digit(digits)?;
+    match digits.len() {
+        12 => Ok(format!("{}{}", digits, expected)),
+        13 => {
+            let found = (digits.as_bytes()[12] - b'0') as u32;
+            if found == expected {
+                Ok(digits.to_string())
+            } else {
+                Err(BarcodeError::BadCheckDigit { expected, found })
+            }
+        }
+        len => Err(BarcodeError::InvalidLength(len)),
+    }
+}
+
+pub fn copy_barcode(copy_id: u32) -> String {
+    library_number(COPY_PREFIX, copy_id)
+}
+
+pub fn patron_barcode(user_id: u32) -> String {
+    library_number(PATRON_PREFIX, user_id)
+}
+
+fn library_number(prefix: &str, id: u32) -> String {
+    let digits = format!("{}{:010}", prefix, id);
+    let check = ean13_check_digit(&digits).expect("twelve generated digits");
+    format!("{}{}", digits, check)
+}
+
+// Reads a copy or patron number back from its 13 digits.
+pub fn parse_identifier(code: &str) -> Result<Identifier, BarcodeError> {
+    let code = with_check_digit(code)?;
+    if code.len() != 13 || !matches!(&code[..2], COPY_PREFIX | PATRON_PREFIX) {
+        return Err(BarcodeError::UnknownPrefix(code));
+    }
+    let id: u32 = code[2..12]
+        .parse()
+        .map_err(|_| BarcodeError::UnknownPrefix(code.clone()))?;
+    if &code[..2] == COPY_PREFIX {
+        Ok(Identifier::Copy(id))
+    } else {
+        Ok(Identifier::Patron(id))
+    }
+}
+
+// One sticker on a label sheet.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub barcode: Barcode,
+    pub caption: String,
+}
+
+// Page geometry for label sheets, in millimetres. The default matches a
+// common A4 sheet of 3 x 7 labels.
+#[derive(Debug, Clone)]
+pub struct SheetLayout {
+    pub page_width: f64,
+    pub page_height: f64,
+    pub columns: usize,
+    pub rows: usize,
+    pub margin: f64,
+    pub gap: f64,
+}
+
+impl Default for SheetLayout {
+    fn default() -> Self {
+        SheetLayout {
+            page_width: 210.0,
+            page_height: 297.0,
+            columns: 3,
+            rows: 7,
+            margin: 10.0,
+            gap: 2.5,
+        }
+    }
+}
+
+impl SheetLayout {
+    pub fn labels_per_page(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn label_size(&self) -> (f64, f64) {
+        let width = (self.page_width - 2.0 * self.margin - (self.columns as f64 - 1.0) * self.gap)
+            / self.columns as f64;
+        let height = (self.page_height - 2.0 * self.margin - (self.rows as f64 - 1.0) * self.gap)
+            / self.rows as f64;
+        (width, height)
+    }
+}
+
+// Lays labels out row by row and returns one SVG document per page.
+pub fn label_sheets(labels: &[Label], layout: &SheetLayout) -> Result<Vec<String>, BarcodeError> {
+    if layout.columns == 0 || layout.rows == 0 {
+        return Err(BarcodeError::EmptyLayout {
+            columns: layout.columns,
+            rows: layout.rows,
+        });
+    }
+    let (label_width, label_height) = layout.label_size();
+    let per_page = layout.labels_per_page();
+
+    let pages = labels
+        .chunks(per_page)
+        .map(|page| {
+            let mut svg = String::new();
+            let _ = writeln!(
+                svg,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}">"#,
+                w = layout.page_width,
+                h = layout.page_height
+            );
+            for (i, label) in page.iter().enumerate() {
+                let x = layout.margin + (i % layout.columns) as f64 * (label_width + layout.gap);
+                let y = layout.margin + (i / layout.columns) as f64 * (label_height + layout.gap);
+                let padding = 3.0;
+                let quiet_zone = 10.0;
+                let modules = label.barcode.modules.len() as f64 + 2.0 * quiet_zone;
+                let module = (label_width - 2.0 * padding) / modules;
+                let bar_height = label_height * 0.5;
+
+                let _ = writeln!(svg, r#"<g class="label">"#);
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="3">{}</text>"#,
+                    x + padding,
+                    y + padding + 3.0,
+                    escape_xml(&label.caption)
+                );
+                label.barcode.write_bars(
+                    &mut svg,
+                    x + padding,
+                    y + padding + 5.0,
+                    module,
+                    bar_height,
+                    quiet_zone,
+                );
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.2}" y="{:.2}" font-family="monospace" font-size="3" text-anchor="middle">{}</text>"#,
+                    x + label_width / 2.0,
+                    y + padding + 5.0 + bar_height + 4.0,
+                    escape_xml(&label.barcode.text)
+                );
+                let _ = writeln!(svg, "</g>");
+            }
+            svg.push_str("</svg>\n");
+            svg
+        })
+        .collect();
+    Ok(pages)
+}
+
+impl Library {
+    // A spine label for a copy: the title as caption over its barcode.
+    pub fn copy_label(&self, copy_id: u32, symbology: Symbology) -> Option<Label> {
+        let copy = self.copies.get(&copy_id)?;
+        let title = self
+            .books
+            .get(&copy.book_id)
+            .map(|b| b.title.as_str())
+            .unwrap_or_default();
+        Some(Label {
+            barcode: Barcode::encode(symbology, &copy.barcode).ok()?,
+            caption: truncate(title, 40),
+        })
+    }
+
+    pub fn patron_card_label(&self, user_id: u32, symbology: Symbology) -> Option<Label> {
+        let user = self.users.get(&user_id)?;
+        Some(Label {
+            barcode: Barcode::encode(symbology, &patron_barcode(user_id)).ok()?,
+            caption: truncate(&user.username, 40),
+        })
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let mut short: String = text.chars().take(max_chars - 1).collect();
+        short.push('…');
+        short
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code128_patterns_are_well_formed() {
+        for (value, pattern) in CODE128_PATTERNS.iter().enumerate() {
+            let widths: Vec<u32> = pattern.bytes().map(|b| (b - b'0') as u32).collect();
+            let expected = if value == CODE128_STOP { 13 } else { 11 };
+            assert_eq!(widths.iter().sum::<u32>(), expected, "value {}", value);
+            let bars: u32 = widths.iter().step_by(2).sum();
+            assert_eq!(bars % 2, 0, "value {} has odd bar width", value);
+        }
+    }
+
+    #[test]
+    fn test_code128_checksum_and_length() {
+        // Start B (104) + P(48) + J(42): (104 + 48 + 2 * 42) % 103 = 30.
+        let barcode = Barcode::code128("PJ").unwrap();
+        let expected = [104, 48, 42, 30, 106]
+            .iter()
+            .flat_map(|&v| CODE128_PATTERNS[v].bytes())
+            .map(|b| (b - b'0') as usize)
+            .sum::<usize>();
+        assert_eq!(barcode.modules.len(), expected);
+
+        // Start C, seven digit pairs, checksum and stop.
+        assert_eq!(
+            Barcode::code128("20000000000018").unwrap().modules.len(),
+            11 * 9 + 13
+        );
+        // Six pairs, then a switch to code set B for the thirteenth digit.
+        assert_eq!(
+            Barcode::code128("2000000000018").unwrap().modules.len(),
+            11 * 10 + 13
+        );
+        assert_eq!(
+            Barcode::code128("é"),
+            Err(BarcodeError::InvalidCharacter('é'))
+        );
+    }
+
+    #[test]
+    fn test_ean13_known_code() {
+        // 4006381333931 is a commonly used EAN-13 example.
+        let barcode = Barcode::ean13("400638133393").unwrap();
+        assert_eq!(barcode.text, "4006381333931");
+        assert_eq!(barcode.modules.len(), 95);
+        let bits: String = barcode
+            .modules
+            .iter()
+            .map(|&m| if m { '1' } else { '0' })
+            .collect();
+        // Start guard, then "0" in L and "0" in G parity (first digit 4 is LGLLGG).
+        assert!(bits.starts_with("101000110101001110"));
+        assert!(bits.ends_with("1100110101"));
+        assert_eq!(
+            Barcode::ean13("4006381333932"),
+            Err(BarcodeError::BadCheckDigit {
+                expected: 1,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_library_numbers_round_trip() {
+        let copy = copy_barcode(42);
+        assert_eq!(copy.len(), 13);
+        assert_eq!(parse_identifier(&copy), Ok(Identifier::Copy(42)));
+        assert_eq!(
+            parse_identifier(&patron_barcode(7)),
+            Ok(Identifier::Patron(7))
+        );
+        assert!(matches!(
+            parse_identifier("4006381333931"),
+            Err(BarcodeError::UnknownPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn test_png_and_label_sheet_output() {
+        let barcode = Barcode::ean13(&copy_barcode(1)).unwrap();
+        let png = barcode.to_png(&RenderOptions::default()).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let labels: Vec<Label> = (0..25)
+            .map(|i| Label {
+                barcode: Barcode::code128(&copy_barcode(i)).unwrap(),
+                caption: format!("Copy {}", i),
+            })
+            .collect();
+        let pages = label_sheets(&labels, &SheetLayout::default()).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].matches(r#"<g class="label">"#).count(), 21);
+        assert_eq!(pages[1].matches(r#"<g class="label">"#).count(), 4);
+
+        let no_columns = SheetLayout {
+            columns: 0,
+            ..SheetLayout::default()
+        };
+        assert_eq!(
+            label_sheets(&labels, &no_columns),
+            Err(BarcodeError::EmptyLayout {
+                columns: 0,
+                rows: 7
+            })
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use crate::models::{
+    copy::CopyStatus,
+    loan::{BorrowEvent, Hold, Loan},
+    policy::RenewalBase,
+};
+use crate::services::library::Library;
+use chrono::{DateTime, Duration, Utc};
+use std::fmt;
+
+// Why a checkout, return or hold could not be carried out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CirculationError {
+    BookNotFound(u32),
+    UserNotFound(u32),
+    LoanNotFound(u32),
+    AccountBlocked(u32),
+    BookUnavailable(u32),
+    CopyNotFound(u32),
+    CopyOnLoan(u32),
+    CopyMissing(u32),
+    ReservedForAnotherUser { book_id: u32, user_id: u32 },
+    AlreadyOnHold { book_id: u32, user_id: u32 },
+    AlreadyReturned(u32),
+}
+
+impl fmt::Display for CirculationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CirculationError::BookNotFound(id) => write!(f, "book {} not found", id),
+            CirculationError::UserNotFound(id) => write!(f, "user {} not found", id),
+            CirculationError::LoanNotFound(id) => write!(f, "loan {} not found", id),
+            CirculationError::AccountBlocked(id) => write!(f, "account of user {} is blocked", id),
+            CirculationError::BookUnavailable(id) => write!(f, "book {} is not available", id),
+            CirculationError::CopyNotFound(id) => write!(f, "copy {} not found", id),
+            CirculationError::CopyOnLoan(id) => write!(f, "copy {} is already on loan", id),
+            CirculationError::CopyMissing(id) => write!(f, "copy {} is marked missing", id),
+            CirculationError::ReservedForAnotherUser { book_id, user_id } => {
+                write!(f, "book {} is reserved for user {}", book_id, user_id)
+            }
+            CirculationError::AlreadyOnHold { book_id, user_id } => {
+                write!(f, "user {} already has a hold on book {}", user_id, book_id)
+            }
+            CirculationError::AlreadyReturned(id) => write!(f, "loan {} was already returned", id),
+        }
+    }
+}
+
+impl std::error::Error for CirculationError {}
+
+// Structured reason a renewal was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenewalRefusal {
+    LoanNotFound(u32),
+    AlreadyReturned(u32),
+    MaxRenewalsReached { loan_id: u32, max: u32 },
+    HeldByAnotherUser { book_id: u32, user_id: u32 },
+    AccountBlocked(u32),
+}
+
+impl fmt::Display for RenewalRefusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenewalRefusal::LoanNotFound(id) => write!(f, "loan {} not found", id),
+            RenewalRefusal::AlreadyReturned(id) => write!(f, "loan {} was already returned", id),
+            RenewalRefusal::MaxRenewalsReached { loan_id, max } => {
+                write!(
+                    f,
+                    "loan {} has reached the maximum of {} renewals",
+                    loan_id, max
+                )
+            }
+            RenewalRefusal::HeldByAnotherUser { book_id, user_id } => {
+                write!(f, "book {} is on hold for user {}", book_id, user_id)
+            }
+            RenewalRefusal::AccountBlocked(id) => write!(f, "account of user {} is blocked", id),
+        }
+    }
+}
+
+impl std::error::Error for RenewalRefusal {}
+
+impl Library {
+    // Lends a book to a user and returns the new loan id. If the book has
+    // physical copies, the first copy on the shelf is used. Each hold placed
+    // ahead of the user claims one copy on the shelf; the user's own hold is
+    // fulfilled by the checkout.
+    pub fn checkout(&mut self, book_id: u32, user_id: u32) -> Result<u32, CirculationError> {
+        self.lend(book_id, None, user_id)
+    }
+
+    // Lends a specific copy, e.g. one scanned at the desk. Copies marked
+    // missing must be found again by a stocktake first.
+    pub fn checkout_copy(&mut self, copy_id: u32, user_id: u32) -> Result<u32, CirculationError> {
+        let book_id = self
+            .copies
+            .get(&copy_id)
+            .ok_or(CirculationError::CopyNotFound(copy_id))?
+            .book_id;
+        self.lend(book_id, Some(copy_id), user_id)
+    }
+
+    fn lend(
+        &mut self,
+        book_id: u32,
+        requested_copy: Option<u32>,
+        user_id: u32,
+    ) -> Result<u32, CirculationError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or(CirculationError::UserNotFound(user_id))?;
+        if user.is_blocked {
+            return Err(CirculationError::AccountBlocked(user_id));
+        }
+        let book = self
+            .books
+            .get(&book_id)
+            .ok_or(CirculationError::BookNotFound(book_id))?;
+        let copy_id = match requested_copy {
+            Some(copy_id) if self.is_copy_on_loan(copy_id) => {
+                return Err(CirculationError::CopyOnLoan(copy_id));
+            }
+            Some(copy_id)
+                if self
+                    .copies
+                    .get(&copy_id)
+                    .is_some_and(|c| c.status == CopyStatus::Missing) =>
+            {
+                return Err(CirculationError::CopyMissing(copy_id));
+            }
+            Some(copy_id) => Some(copy_id),
+            None if self.copies.values().any(|c| c.book_id == book_id) => Some(
+                self.copy_on_shelf(book_id)
+                    .ok_or(CirculationError::BookUnavailable(book_id))?,
+            ),
+            None if !book.is_available => return Err(CirculationError::BookUnavailable(book_id)),
+            None => None,
+        };
+        let ahead: Vec<&Hold> = self
+            .holds_for_book(book_id)
+            .into_iter()
+            .take_while(|h| h.user_id != user_id)
+            .collect();
+        if let Some(first) = ahead.first() {
+            if ahead.len() >= self.available_copies(book_id) {
+                return Err(CirculationError::ReservedForAnotherUser {
+                    book_id,
+                    user_id: first.user_id,
+                });
+            }
+        }
+        self.holds
+            .retain(|h| !(h.book_id == book_id && h.user_id == user_id));
+
+        let now = self.now();
+        let id = self.next_loan_id;
+        self.next_loan_id += 1;
+        self.loans.insert(
+            id,
+            Loan {
+                id,
+                book_id,
+                copy_id,
+                user_id: Some(user_id),
+                checked_out: now,
+                due_date: self.due_date_after(now, self.policy.loan_days),
+                renewals: 0,
+                returned: None,
+            },
+        );
+        let still_available = copy_id.is_some() && self.copy_on_shelf(book_id).is_some();
+        if let Some(book) = self.books.get_mut(&book_id) {
+            book.is_available = still_available;
+        }
+        Ok(id)
+    }
+
+    pub fn is_copy_on_loan(&self, copy_id: u32) -> bool {
+        self.loans
+            .values()
+            .any(|l| l.is_active() && l.copy_id == Some(copy_id))
+    }
+
+    // The lowest-numbered copy of a book that is neither on loan nor missing.
+    pub fn copy_on_shelf(&self, book_id: u32) -> Option<u32> {
+        self.copies_of(book_id)
+            .into_iter()
+            .filter(|c| c.status == CopyStatus::InCirculation)
+            .map(|c| c.id)
+            .find(|&id| !self.is_copy_on_loan(id))
+    }
+
+    // How many loans of a book could start right now: its copies on the
+    // shelf, or one or none for a book without physical copies.
+    fn available_copies(&self, book_id: u32) -> usize {
+        let copies = self.copies_of(book_id);
+        if copies.is_empty() {
+            return usize::from(self.books.get(&book_id).is_some_and(|b| b.is_available));
+        }
+        copies
+            .into_iter()
+            .filter(|c| c.status == CopyStatus::InCirculation && !self.is_copy_on_loan(c.id))
+            .count()
+    }
+
+    // The active loan of a copy, used when a copy is scanned back in.
+    pub fn active_loan_for_copy(&self, copy_id: u32) -> Option<&Loan> {
+        self.loans
+            .values()
+            .find(|l| l.is_active() && l.copy_id == Some(copy_id))
+    }
+
+    pub fn return_book(&mut self, loan_id: u32) -> Result<(), CirculationError> {
+        let now = self.now();
+        let loan = self
+            .loans
+            .get_mut(&loan_id)
+            .ok_or(CirculationError::LoanNotFound(loan_id))?;
+        if !loan.is_active() {
+            return Err(CirculationError::AlreadyReturned(loan_id));
+        }
+        loan.returned = Some(now);
+        if let Some(book) = self.books.get_mut(&loan.book_id) {
+            book.is_available = true;
+        }
+        Ok(())
+    }
+
+    // Queues a user for a book. Holds are served in the order they were placed.
+    pub fn place_hold(&mut self, book_id: u32, user_id: u32) -> Result<(), CirculationError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or(CirculationError::UserNotFound(user_id))?;
+        if user.is_blocked {
+            return Err(CirculationError::AccountBlocked(user_id));
+        }
+        if !self.books.contains_key(&book_id) {
+            return Err(CirculationError::BookNotFound(book_id));
+        }
+        if self
+            .holds
+            .iter()
+            .any(|h| h.book_id == book_id && h.user_id == user_id)
+        {
+            return Err(CirculationError::AlreadyOnHold { book_id, user_id });
+        }
+        let placed = self.now();
+        self.holds.push(Hold {
+            book_id,
+            user_id,
+            placed,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_hold(&mut self, book_id: u32, user_id: u32) -> bool {
+        let before = self.holds.len();
+        self.holds
+            .retain(|h| !(h.book_id == book_id && h.user_id == user_id));
+        self.holds.len() != before
+    }
+
+    // Extends an active loan, returning the new due date. Holds by other
+    // users only block the renewal when there are more of them than copies
+    // on the shelf to serve them; the renewed copy stays with the borrower.
+    pub fn renew(&mut self, loan_id: u32) -> Result<DateTime<Utc>, RenewalRefusal> {
+        let now = self.now();
+        let loan = self
+            .loans
+            .get(&loan_id)
+            .ok_or(RenewalRefusal::LoanNotFound(loan_id))?;
+        if !loan.is_active() {
+            return Err(RenewalRefusal::AlreadyReturned(loan_id));
+        }
+        if let Some(user_id) = loan.user_id {
+            if self.users.get(&user_id).is_some_and(|user| user.is_blocked) {
+                return Err(RenewalRefusal::AccountBlocked(user_id));
+            }
+        }
+        if loan.renewals >= self.policy.max_renewals {
+            return Err(RenewalRefusal::MaxRenewalsReached {
+                loan_id,
+                max: self.policy.max_renewals,
+            });
+        }
+        let others: Vec<&Hold> = self
+            .holds_for_book(loan.book_id)
+            .into_iter()
+            .filter(|h| Some(h.user_id) != loan.user_id)
+            .collect();
+        if let Some(first) = others.first() {
+            if others.len() > self.available_copies(loan.book_id) {
+                return Err(RenewalRefusal::HeldByAnotherUser {
+                    book_id: loan.book_id,
+                    user_id: first.user_id,
+                });
+            }
+        }
+
+        let base = match self.policy.renewal_base {
+            RenewalBase::DueDate => loan.due_date,
+            RenewalBase::RenewalDate => now,
+        };
+        let due_date = self.due_date_after(base, self.policy.renewal_days);
+        if let Some(loan) = self.loans.get_mut(&loan_id) {
+            loan.due_date = due_date;
+            loan.renewals += 1;
+        }
+        Ok(due_date)
+    }
+
+    // Counts `days` from `base` and moves the result off any closed day.
+    fn due_date_after(&self, base: DateTime<Utc>, days: i64) -> DateTime<Utc> {
+        self.calendar.adjust_due_date(base + Duration::days(days))
+    }
+
+    // Fine in cents accrued by a loan, counting only days the branch was open
+    // after the due date. Runs until the return date, or today if still out.
+    // Saturates at `u32::MAX` rather than wrapping on very long overdues.
+    pub fn fine_for(&self, loan_id: u32) -> Option<u32> {
+        let loan = self.loans.get(&loan_id)?;
+        let end = loan.returned.unwrap_or_else(|| self.now());
+        if end <= loan.due_date {
+            return Some(0);
+        }
+        let days = self
+            .calendar
+            .open_days_between(loan.due_date.date_naive(), end.date_naive());
+        let days = u32::try_from(days).unwrap_or(u32::MAX);
+        Some(days.saturating_mul(self.policy.fine_per_day))
+    }
+
+    pub fn get_loan(&self, loan_id: u32) -> Option<&Loan> {
+        self.loans.get(&loan_id)
+    }
+
+    pub fn active_loans_for(&self, user_id: u32) -> Vec<&Loan> {
+        let mut loans: Vec<&Loan> = self
+            .loans
+            .values()
+            .filter(|l| l.user_id == Some(user_id) && l.is_active())
+            .collect();
+        loans.sort_by_key(|l| l.id);
+        loans
+    }
+
+    // Every loan still linked to a user, active or returned, oldest first.
+    // Loans anonymised by the retention policy are left out.
+    pub fn borrowing_history(&self) -> Vec<BorrowEvent> {
+        let mut loans: Vec<&Loan> = self.loans.values().collect();
+        loans.sort_by_key(|l| (l.checked_out, l.id));
+        loans
+            .into_iter()
+            .filter_map(|l| {
+                Some(BorrowEvent {
+                    user_id: l.user_id?,
+                    book_id: l.book_id,
+                    borrowed: l.checked_out,
+                })
+            })
+            .collect()
+    }
+
+    pub fn holds_for_book(&self, book_id: u32) -> Vec<&Hold> {
+        self.holds.iter().filter(|h| h.book_id == book_id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        book::Book,
+        calendar::{Closure, LibraryCalendar},
+        policy::LoanPolicy,
+        user::User,
+    };
+    use crate::services::clock::Clock;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Weekday};
+
+    fn setup(policy: LoanPolicy) -> Library {
+        let mut library = Library::with_policy(policy);
+        library.set_clock(Clock::Fixed(
+            Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap(),
+        ));
+        library.add_book(Book::new(1, "Dune", "Frank Herbert"));
+        library.register_user(User::new(1, "alice"));
+        library.register_user(User::new(2, "bob"));
+        library
+    }
+
+    #[test]
+    fn test_renew_extends_from_due_date() {
+        let mut library = setup(LoanPolicy::default());
+        let loan_id = library.checkout(1, 1).unwrap();
+        let due = library.get_loan(loan_id).unwrap().due_date;
+
+        let renewed = library.renew(loan_id).unwrap();
+        assert_eq!(renewed, due + Duration::days(14));
+        assert_eq!(library.get_loan(loan_id).unwrap().renewals, 1);
+    }
+
+    #[test]
+    fn test_renew_from_renewal_date() {
+        let policy = LoanPolicy {
+            renewal_base: RenewalBase::RenewalDate,
+            ..LoanPolicy::default()
+        };
+        let mut library = setup(policy);
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.clock_mut().advance(Duration::days(10));
+
+        let renewed = library.renew(loan_id).unwrap();
+        assert_eq!(renewed, library.now() + Duration::days(14));
+    }
+
+    #[test]
+    fn test_renew_refused_after_max_renewals() {
+        let mut library = setup(LoanPolicy::default());
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.renew(loan_id).unwrap();
+        library.renew(loan_id).unwrap();
+
+        assert_eq!(
+            library.renew(loan_id),
+            Err(RenewalRefusal::MaxRenewalsReached { loan_id, max: 2 })
+        );
+    }
+
+    #[test]
+    fn test_renew_refused_when_another_user_holds() {
+        let mut library = setup(LoanPolicy::default());
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.place_hold(1, 2).unwrap();
+
+        assert_eq!(
+            library.renew(loan_id),
+            Err(RenewalRefusal::HeldByAnotherUser {
+                book_id: 1,
+                user_id: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_renew_refused_for_blocked_account() {
+        let mut library = setup(LoanPolicy::default());
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.get_user_mut(1).unwrap().is_blocked = true;
+
+        assert_eq!(
+            library.renew(loan_id),
+            Err(RenewalRefusal::AccountBlocked(1))
+        );
+    }
+
+    #[test]
+    fn test_hold_reserves_returned_book() {
+        let mut library = setup(LoanPolicy::default());
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.place_hold(1, 2).unwrap();
+        library.return_book(loan_id).unwrap();
+
+        assert_eq!(
+            library.checkout(1, 1),
+            Err(CirculationError::ReservedForAnotherUser {
+                book_id: 1,
+                user_id: 2
+            })
+        );
+        assert!(library.checkout(1, 2).is_ok());
+        assert!(library.holds_for_book(1).is_empty());
+    }
+
+    fn with_copies(library: &mut Library, count: usize) {
+        for _ in 0..count {
+            library.add_copy(1, "Main");
+        }
+    }
+
+    #[test]
+    fn test_hold_only_blocks_when_no_spare_copy() {
+        let mut library = setup(LoanPolicy::default());
+        with_copies(&mut library, 2);
+        library.register_user(User::new(3, "carol"));
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.place_hold(1, 2).unwrap();
+
+        // Bob's hold can be met by the copy still on the shelf.
+        assert!(library.renew(loan_id).is_ok());
+        assert_eq!(
+            library.checkout(1, 3),
+            Err(CirculationError::ReservedForAnotherUser {
+                book_id: 1,
+                user_id: 2
+            })
+        );
+        assert!(library.checkout(1, 2).is_ok());
+        assert!(library.checkout(1, 3).is_err());
+    }
+
+    #[test]
+    fn test_missing_copy_cannot_be_lent() {
+        let mut library = setup(LoanPolicy::default());
+        with_copies(&mut library, 1);
+        let copy_id = library.copies_of(1)[0].id;
+        library.copies.get_mut(&copy_id).unwrap().status = CopyStatus::Missing;
+
+        assert_eq!(
+            library.checkout_copy(copy_id, 1),
+            Err(CirculationError::CopyMissing(copy_id))
+        );
+        assert_eq!(
+            library.get_copy(copy_id).unwrap().status,
+            CopyStatus::Missing
+        );
+    }
+
+    fn weekday_calendar() -> LibraryCalendar {
+        let mut calendar = LibraryCalendar::closed();
+        let opens = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let closes = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+        for day in [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ] {
+            calendar.set_hours(day, opens, closes);
+        }
+        calendar
+    }
+
+    #[test]
+    fn test_due_date_moves_to_next_open_day() {
+        // 2024-03-01 + 21 days is Friday 2024-03-22, closed for a stocktake.
+        let mut calendar = weekday_calendar();
+        calendar.add_closure(Closure {
+            start: NaiveDate::from_ymd_opt(2024, 3, 22).unwrap(),
+            end: NaiveDate::from_ymd_opt(2024, 3, 22).unwrap(),
+            reason: "Stocktake".to_string(),
+        });
+        let mut library = setup(LoanPolicy::default());
+        library.set_calendar(calendar);
+
+        let loan_id = library.checkout(1, 1).unwrap();
+        let due = library.get_loan(loan_id).unwrap().due_date;
+        assert_eq!(due, Utc.with_ymd_and_hms(2024, 3, 25, 10, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_fine_skips_closed_days() {
+        let mut library = setup(LoanPolicy::default());
+        library.set_calendar(weekday_calendar());
+        let loan_id = library.checkout(1, 1).unwrap();
+        assert_eq!(library.fine_for(loan_id), Some(0));
+
+        // Due Friday 2024-03-22; returned the following Friday. Only the
+        // five weekdays from Monday to Friday accrue a fine.
+        library.clock_mut().advance(Duration::days(28));
+        library.return_book(loan_id).unwrap();
+        assert_eq!(library.fine_for(loan_id), Some(5 * 25));
+    }
+
+    #[test]
+    fn test_fine_saturates_instead_of_overflowing() {
+        let policy = LoanPolicy {
+            fine_per_day: u32::MAX / 2,
+            ..LoanPolicy::default()
+        };
+        let mut library = setup(policy);
+        let loan_id = library.checkout(1, 1).unwrap();
+        library.clock_mut().advance(Duration::days(30));
+        assert_eq!(library.fine_for(loan_id), Some(u32::MAX));
+    }
+}
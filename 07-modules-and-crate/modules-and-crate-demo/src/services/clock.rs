@@ -0,0 +1,25 @@
+use chrono::{DateTime, Duration, Utc};
+
+// Source of "now" for the library. Tests and simulations pin the time
+// instead of depending on the wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    System,
+    Fixed(DateTime<Utc>),
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::System => Utc::now(),
+            Clock::Fixed(time) => *time,
+        }
+    }
+
+    // Moves a fixed clock forward; the system clock cannot be advanced.
+    pub fn advance(&mut self, by: Duration) {
+        if let Clock::Fixed(time) = self {
+            *time += by;
+        }
+    }
+}
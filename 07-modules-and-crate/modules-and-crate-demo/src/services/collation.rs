@@ -0,0 +1,498 @@
+use crate::models::book::Book;
+use crate::services::library::Library;
+use std::cmp::Ordering;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// Weight given to a run of spaces or punctuation, below every letter, so
+// "New York" files before "Newark".
+const SEPARATOR: u32 = 1;
+
+// Locale rules for building sort keys. Text is lowercased, decomposed and
+// stripped of accents unless a rule below says otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collation {
+    pub locale: String,
+    // Leading words skipped when filing titles. An article ending in an
+    // apostrophe ("l'") attaches directly to the next word.
+    pub articles: Vec<String>,
+    // Characters replaced by a sequence before comparison, e.g. ß -> ss.
+    pub expansions: Vec<(char, String)>,
+    // Letters that keep their accent and file straight after another letter,
+    // e.g. Spanish ñ after n. Several letters after the same one keep the
+    // order they are listed in.
+    pub tailored: Vec<(char, char)>,
+    // Name particles such as "de" or "von".
+    pub particles: Vec<String>,
+    // Whether particles file with the surname ("de la Mare, Walter") or
+    // after the forenames ("Goethe, Johann Wolfgang von").
+    pub particles_lead_surname: bool,
+}
+
+fn strings(words: &[&str]) -> Vec<String> {
+    words.iter().map(|w| w.to_string()).collect()
+}
+
+impl Collation {
+    pub fn english() -> Self {
+        Collation {
+            locale: "en".to_string(),
+            articles: strings(&["the", "a", "an"]),
+            expansions: vec![('æ', "ae".to_string()), ('œ', "oe".to_string())],
+            tailored: Vec::new(),
+            particles: strings(&["de", "la", "le", "du", "van", "von", "der"]),
+            particles_lead_surname: true,
+        }
+    }
+
+    pub fn german() -> Self {
+        Collation {
+            locale: "de".to_string(),
+            articles: strings(&["der", "die", "das", "ein", "eine", "einer"]),
+            expansions: vec![('ß', "ss".to_string())],
+            tailored: Vec::new(),
+            particles: strings(&["von", "zu", "vom", "zum", "van", "der", "den"]),
+            particles_lead_surname: false,
+        }
+    }
+
+    pub fn french() -> Self {
+        Collation {
+            locale: "fr".to_string(),
+            articles: strings(&["le", "la", "les", "l'", "un", "une"]),
+            expansions: vec![('æ', "ae".to_string()), ('œ', "oe".to_string())],
+            tailored: Vec::new(),
+            particles: strings(&["de", "du", "des"]),
+            particles_lead_surname: false,
+        }
+    }
+
+    pub fn spanish() -> Self {
+        Collation {
+            locale: "es".to_string(),
+            articles: strings(&["el", "la", "los", "las", "un", "una"]),
+            expansions: Vec::new(),
+            tailored: vec![('ñ', 'n')],
+            particles: strings(&["de", "del", "la", "y"]),
+            particles_lead_surname: false,
+        }
+    }
+
+    pub fn swedish() -> Self {
+        Collation {
+            locale: "sv".to_string(),
+            // Swedish articles are suffixes, so there is nothing to skip.
+            articles: Vec::new(),
+            expansions: Vec::new(),
+            tailored: vec![('å', 'z'), ('ä', 'z'), ('ö', 'z')],
+            particles: strings(&["af", "von", "de"]),
+            particles_lead_surname: false,
+        }
+    }
+
+    // Looks up a table by language tag; "de-AT" finds the German rules.
+    pub fn for_locale(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Collation::english()),
+            "de" => Some(Collation::german()),
+            "fr" => Some(Collation::french()),
+            "es" => Some(Collation::spanish()),
+            "sv" => Some(Collation::swedish()),
+            _ => None,
+        }
+    }
+
+    // The title as filed: leading article removed, original case kept.
+    pub fn filing_title<'a>(&self, title: &'a str) -> &'a str {
+        let title = title.trim();
+        for article in &self.articles {
+            let Some(rest) = strip_prefix_ignore_case(title, article) else {
+                continue;
+            };
+            let elided = article.ends_with('\'');
+            let rest = if elided {
+                rest
+            } else if rest.starts_with(char::is_whitespace) {
+                rest.trim_start()
+            } else {
+                continue;
+            };
+            if !rest.is_empty() {
+                return rest;
+            }
+        }
+        title
+    }
+
+    pub fn title_key(&self, title: &str) -> SortKey {
+        self.key(self.filing_title(title), title)
+    }
+
+    // A book's title key. Its leading article is found with the rules for
+    // the book's own language, so "Der Process" files under P in an English
+    // listing too; books in other or unknown languages use this table.
+    pub fn book_title_key(&self, book: &Book) -> SortKey {
+        let filed = match book.language.as_deref().and_then(Collation::for_locale) {
+            Some(rules) => rules.filing_title(&book.title),
+            None => self.filing_title(&book.title),
+        };
+        self.key(filed, &book.title)
+    }
+
+    // Personal names file as "Surname, Forenames". Names already written
+    // that way, and single names, are used as they are.
+    pub fn author_key(&self, name: &str) -> SortKey {
+        self.key(&self.inverted_name(name), name)
+    }
+
+    pub fn inverted_name(&self, name: &str) -> String {
+        let name = name.trim();
+        if name.contains(',') {
+            return name.to_string();
+        }
+        let words: Vec<&str> = name.split_whitespace().collect();
+        if words.len() < 2 {
+            return name.to_string();
+        }
+        let is_particle = |w: &str| self.particles.iter().any(|p| w.eq_ignore_ascii_case(p));
+        // Particles are the words directly before the surname.
+        let mut split = words.len() - 1;
+        while split > 1 && is_particle(words[split - 1]) {
+            split -= 1;
+        }
+        let surname = words[words.len() - 1];
+        let particles = &words[split..words.len() - 1];
+        let forenames = &words[..split];
+        if particles.is_empty() {
+            format!("{}, {}", surname, forenames.join(" "))
+        } else if self.particles_lead_surname {
+            format!(
+                "{} {}, {}",
+                particles.join(" "),
+                surname,
+                forenames.join(" ")
+            )
+        } else {
+            format!(
+                "{}, {} {}",
+                surname,
+                forenames.join(" "),
+                particles.join(" ")
+            )
+        }
+    }
+
+    pub fn compare_titles(&self, a: &str, b: &str) -> Ordering {
+        self.title_key(a).cmp(&self.title_key(b))
+    }
+
+    // Lowercase base letters and digits, with any run of other characters
+    // turned into one space. Used to compare text rather than to sort it.
+    pub fn fold(&self, text: &str) -> String {
+        let mut folded = String::new();
+        for c in text.nfc().flat_map(char::to_lowercase) {
+            let chars: Vec<char> = match self.expansions.iter().find(|(from, _)| *from == c) {
+                Some((_, expansion)) => expansion.chars().collect(),
+                None if self.tailored.iter().any(|(letter, _)| *letter == c) => vec![c],
+                None => c
+                    .to_string()
+                    .nfd()
+                    .filter(|ch| !is_combining_mark(*ch))
+                    .collect(),
+            };
+            for ch in chars {
+                if ch.is_alphanumeric() {
+                    folded.push(ch);
+                } else if !folded.is_empty() && !folded.ends_with(' ') {
+                    folded.push(' ');
+                }
+            }
+        }
+        folded.trim_end().to_string()
+    }
+
+    fn key(&self, filed: &str, original: &str) -> SortKey {
+        let mut segments = Vec::new();
+        let mut text = Vec::new();
+        let mut digits = String::new();
+
+        for unit in self.units(filed) {
+            if let Unit::Digit(d) = unit {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                digits.push(d);
+                continue;
+            }
+            if !digits.is_empty() {
+                segments.push(Segment::number(&std::mem::take(&mut digits)));
+            }
+            match unit {
+                Unit::Letter(weight) => text.push(weight),
+                Unit::Separator if text.last().is_some_and(|&w| w != SEPARATOR) => {
+                    text.push(SEPARATOR)
+                }
+                // Separators between numbers, or leading ones, are not needed.
+                _ => {}
+            }
+        }
+        if !digits.is_empty() {
+            segments.push(Segment::number(&digits));
+        }
+        if text.last() == Some(&SEPARATOR) {
+            text.pop();
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        SortKey {
+            segments,
+            original: original.to_string(),
+        }
+    }
+
+    // Folds text into letters, digits and separators.
+    fn units(&self, text: &str) -> Vec<Unit> {
+        let mut units = Vec::new();
+        for c in text.nfc().flat_map(char::to_lowercase) {
+            if let Some((_, expansion)) = self.expansions.iter().find(|(from, _)| *from == c) {
+                units.extend(expansion.chars().map(|e| self.unit(e)));
+                continue;
+            }
+            if let Some(weight) = self.tailored_weight(c) {
+                units.push(Unit::Letter(weight));
+                continue;
+            }
+            let base = c
+                .to_string()
+                .nfd()
+                .filter(|ch| !is_combining_mark(*ch))
+                .collect::<Vec<_>>();
+            units.extend(base.into_iter().map(|b| self.unit(b)));
+        }
+        units
+    }
+
+    fn unit(&self, c: char) -> Unit {
+        if c.is_ascii_digit() {
+            Unit::Digit(c)
+        } else if c.is_alphanumeric() {
+            Unit::Letter((c as u32) << 8)
+        } else {
+            Unit::Separator
+        }
+    }
+
+    fn tailored_weight(&self, c: char) -> Option<u32> {
+        let (_, after) = self.tailored.iter().find(|(letter, _)| *letter == c)?;
+        let rank = self
+            .tailored
+            .iter()
+            .filter(|(_, a)| a == after)
+            .position(|(letter, _)| *letter == c)?;
+        Some(((*after as u32) << 8) + rank as u32 + 1)
+    }
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Collation::english()
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut rest = text.char_indices();
+    for p in prefix.chars() {
+        let (_, c) = rest.next()?;
+        let same = c.to_lowercase().eq(p.to_lowercase()) || (p == '\'' && c == '\u{2019}');
+        if !same {
+            return None;
+        }
+    }
+    Some(rest.next().map_or("", |(i, _)| &text[i..]))
+}
+
+enum Unit {
+    Letter(u32),
+    Digit(char),
+    Separator,
+}
+
+// Numbers compare by value and file before words.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Number { len: usize, digits: String },
+    Text(Vec<u32>),
+}
+
+impl Segment {
+    fn number(digits: &str) -> Self {
+        let digits = digits.trim_start_matches('0');
+        Segment::Number {
+            len: digits.len(),
+            digits: digits.to_string(),
+        }
+    }
+}
+
+// A comparable key. Strings that fold to the same key are ordered by their
+// original text so sorting is stable across runs.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey {
+    segments: Vec<Segment>,
+    original: String,
+}
+
+impl SortKey {
+    // Whether two keys are equal once case, accents and articles are ignored.
+    pub fn matches(&self, other: &SortKey) -> bool {
+        self.segments == other.segments
+    }
+}
+
+impl Library {
+    pub fn books_by_title(&self, collation: &Collation) -> Vec<&Book> {
+        let mut books: Vec<(SortKey, &Book)> = self
+            .books
+            .values()
+            .map(|b| (collation.book_title_key(b), b))
+            .collect();
+        books.sort_by(|(a, x), (b, y)| a.cmp(b).then(x.id.cmp(&y.id)));
+        books.into_iter().map(|(_, b)| b).collect()
+    }
+
+    // Orders by first author, then title. Books without an author file
+    // under their title.
+    pub fn books_by_author(&self, collation: &Collation) -> Vec<&Book> {
+        let mut books: Vec<(SortKey, SortKey, &Book)> = self
+            .books
+            .values()
+            .map(|b| {
+                let author = match b.authors().first() {
+                    Some(name) => collation.author_key(name),
+                    None => collation.book_title_key(b),
+                };
+                (author, collation.book_title_key(b), b)
+            })
+            .collect();
+        books.sort_by(|(a1, t1, x), (a2, t2, y)| a1.cmp(a2).then(t1.cmp(t2)).then(x.id.cmp(&y.id)));
+        books.into_iter().map(|(_, _, b)| b).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: &Collation, titles: &[&str]) -> Vec<String> {
+        let mut titles: Vec<&str> = titles.to_vec();
+        titles.sort_by_key(|t| collation.title_key(t));
+        titles.into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_articles_and_natural_numbers() {
+        let en = Collation::english();
+        assert_eq!(en.filing_title("The Hobbit"), "Hobbit");
+        assert_eq!(
+            en.filing_title("Theory of Everything"),
+            "Theory of Everything"
+        );
+        assert_eq!(en.filing_title("A"), "A");
+        assert_eq!(
+            sorted(
+                &en,
+                &["Vol. 10", "The Zoo", "Vol. 2", "An Apple", "vol. 1", "1984", "Vol 3"]
+            ),
+            ["1984", "An Apple", "vol. 1", "Vol. 2", "Vol 3", "Vol. 10", "The Zoo"]
+        );
+        assert_eq!(
+            sorted(&en, &["Newark", "New York", "Newt"]),
+            ["New York", "Newark", "Newt"]
+        );
+    }
+
+    #[test]
+    fn test_case_and_diacritic_folding() {
+        let fr = Collation::french();
+        assert_eq!(fr.filing_title("L'Étranger"), "Étranger");
+        assert_eq!(fr.filing_title("L’Étranger"), "Étranger");
+        assert!(fr
+            .title_key("Les Misérables")
+            .matches(&fr.title_key("MISERABLES")));
+        assert_eq!(
+            sorted(&fr, &["Emu", "Ekel", "Élan", "eclair"]),
+            ["eclair", "Ekel", "Élan", "Emu"]
+        );
+        // Decomposed input sorts the same as precomposed.
+        assert!(fr.title_key("E\u{301}lan").matches(&fr.title_key("Élan")));
+        let es = Collation::spanish();
+        assert!(es.title_key("n\u{303}u").matches(&es.title_key("ñu")));
+    }
+
+    #[test]
+    fn test_locale_tables() {
+        let de = Collation::for_locale("de-AT").unwrap();
+        assert_eq!(de.filing_title("Der Process"), "Process");
+        assert!(de.title_key("Straße").matches(&de.title_key("Strasse")));
+        assert_eq!(
+            sorted(&de, &["Zucker", "Äpfel", "Ast"]),
+            ["Äpfel", "Ast", "Zucker"]
+        );
+
+        let sv = Collation::swedish();
+        assert_eq!(
+            sorted(&sv, &["Öland", "Zebra", "Åsa", "Ask"]),
+            ["Ask", "Zebra", "Åsa", "Öland"]
+        );
+        let es = Collation::spanish();
+        assert_eq!(sorted(&es, &["ñu", "nube", "oso"]), ["nube", "ñu", "oso"]);
+        assert!(Collation::for_locale("xx").is_none());
+    }
+
+    #[test]
+    fn test_author_keys() {
+        let en = Collation::english();
+        assert_eq!(en.inverted_name("Ursula K. Le Guin"), "Le Guin, Ursula K.");
+        assert_eq!(en.inverted_name("Walter de la Mare"), "de la Mare, Walter");
+        assert_eq!(en.inverted_name("Austen, Jane"), "Austen, Jane");
+        assert_eq!(en.inverted_name("Homer"), "Homer");
+        let de = Collation::german();
+        assert_eq!(
+            de.inverted_name("Johann Wolfgang von Goethe"),
+            "Goethe, Johann Wolfgang von"
+        );
+
+        let mut library = Library::new();
+        library.add_book(Book::new(1, "Faust", "Johann Wolfgang von Goethe"));
+        library.add_book(Book::new(2, "Emma", "Jane Austen"));
+        library.add_book(Book::new(
+            3,
+            "Die Leiden des jungen Werthers",
+            "Johann Wolfgang von Goethe",
+        ));
+        let ids: Vec<u32> = library.books_by_author(&de).iter().map(|b| b.id).collect();
+        assert_eq!(ids, [2, 1, 3]);
+        let ids: Vec<u32> = library.books_by_title(&de).iter().map(|b| b.id).collect();
+        assert_eq!(ids, [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_articles_follow_book_language() {
+        let mut library = Library::new();
+        library.add_book(
+            Book::builder(1, "Der Process")
+                .author("Franz Kafka")
+                .language("de")
+                .build(),
+        );
+        library.add_book(Book::builder(2, "Moby-Dick").language("en").build());
+        library.add_book(Book::builder(3, "The Quiet American").build());
+        library.add_book(Book::builder(4, "Die Hard").language("en").build());
+
+        let en = Collation::english();
+        let ids: Vec<u32> = library.books_by_title(&en).iter().map(|b| b.id).collect();
+        // "Die" is not an English article, but "Der" is a German one.
+        assert_eq!(ids, [4, 2, 1, 3]);
+    }
+}
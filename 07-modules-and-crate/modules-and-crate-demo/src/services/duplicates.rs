@@ -0,0 +1,596 @@
+use crate::models::book::Book;
+use crate::services::{auth::Auth, barcode, collation::Collation, library::Library};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Pairs scoring below this are not reported by default.
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.85;
+
+// How much of a metadata score comes from the title; the rest is the author.
+const TITLE_WEIGHT: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchReason {
+    // Both records carry the same ISBN, in either form.
+    Isbn,
+    // Titles and authors are alike once normalised.
+    Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateMatch {
+    pub first: u32,
+    pub second: u32,
+    // From 0.0 (unrelated) to 1.0 (certain).
+    pub score: f64,
+    pub reason: MatchReason,
+}
+
+// Records that look like the same work, linked by one or more matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateCluster {
+    pub book_ids: Vec<u32>,
+    // The weakest match holding the cluster together.
+    pub score: f64,
+    pub matches: Vec<DuplicateMatch>,
+    // The record with the most copies and loans, a sensible one to keep.
+    pub suggested_survivor: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    UserNotFound(u32),
+    NotLibrarian(u32),
+    BookNotFound(u32),
+    // The survivor was also listed as a duplicate.
+    SameRecord(u32),
+    // A duplicate was listed more than once.
+    RepeatedRecord(u32),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::UserNotFound(id) => write!(f, "user {} not found", id),
+            MergeError::NotLibrarian(id) => write!(f, "user {} is not a librarian", id),
+            MergeError::BookNotFound(id) => write!(f, "book {} not found", id),
+            MergeError::SameRecord(id) => write!(f, "book {} cannot be merged into itself", id),
+            MergeError::RepeatedRecord(id) => write!(f, "book {} is listed more than once", id),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+// What a merge moved onto the surviving record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub survivor: u32,
+    pub removed: Vec<u32>,
+    pub copies: usize,
+    pub loans: usize,
+    pub holds: usize,
+    // Holds dropped because the user already held or had borrowed the
+    // survivor.
+    pub duplicate_holds: usize,
+    pub reviews: usize,
+    // Older reviews dropped because the user had reviewed several records.
+    pub duplicate_reviews: usize,
+    pub shelves: usize,
+}
+
+// The ISBN-13 form of an ISBN-10 or ISBN-13, ignoring hyphens and spaces.
+// Returns None when the text is not a valid ISBN.
+pub fn normalize_isbn(text: &str) -> Option<String> {
+    let compact: String = text
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    // The checks below index by byte, which is only safe on ASCII.
+    if !compact.is_ascii() {
+        return None;
+    }
+    match compact.len() {
+        10 => {
+            let (body, check) = compact.split_at(9);
+            if !body.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let check = match check {
+                "X" => 10,
+                digit => digit.parse::<u32>().ok()?,
+            };
+            let sum: u32 = body
+                .bytes()
+                .enumerate()
+                .map(|(i, b)| (10 - i as u32) * (b - b'0') as u32)
+                .sum();
+            if !(sum + check).is_multiple_of(11) {
+                return None;
+            }
+            let digits = format!("978{}", body);
+            let check = barcode::ean13_check_digit(&digits).ok()?;
+            Some(format!("{}{}", digits, check))
+        }
+        13 if compact.starts_with("978") || compact.starts_with("979") => {
+            let check = barcode::ean13_check_digit(&compact).ok()?;
+            let found = compact[12..].parse::<u32>().ok()?;
+            (check == found).then_some(compact)
+        }
+        _ => None,
+    }
+}
+
+// Dice coefficient over character pairs, which tolerates small typos and
+// reordered words.
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let pairs = |text: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = text.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let (first, second) = (pairs(a), pairs(b));
+    if first.is_empty() || second.is_empty() {
+        return 0.0;
+    }
+    let mut unmatched = second.clone();
+    let mut common = 0;
+    for pair in &first {
+        if let Some(i) = unmatched.iter().position(|p| p == pair) {
+            unmatched.swap_remove(i);
+            common += 1;
+        }
+    }
+    2.0 * common as f64 / (first.len() + second.len()) as f64
+}
+
+// The folded title without its article, wherever the cataloguer put it:
+// "The Hobbit" and "Hobbit, The" both give "hobbit".
+fn filing_form(title: &str, collation: &Collation) -> String {
+    let folded = collation.fold(collation.filing_title(title));
+    for article in &collation.articles {
+        let article = collation.fold(article);
+        if let Some(rest) = folded.strip_suffix(&article) {
+            if !article.is_empty() && rest.ends_with(' ') {
+                return rest.trim_end().to_string();
+            }
+        }
+    }
+    folded
+}
+
+// The normalised forms a record is compared by.
+struct Fingerprint {
+    id: u32,
+    title: String,
+    author: Option<String>,
+    isbn: Option<String>,
+}
+
+impl Fingerprint {
+    fn new(book: &Book, collation: &Collation) -> Self {
+        Fingerprint {
+            id: book.id,
+            title: filing_form(&book.title, collation),
+            author: book
+                .authors()
+                .first()
+                .map(|name| collation.fold(&collation.inverted_name(name))),
+            isbn: book.isbn.as_deref().and_then(normalize_isbn),
+        }
+    }
+
+    fn compare(&self, other: &Fingerprint) -> Option<(f64, MatchReason)> {
+        match (&self.isbn, &other.isbn) {
+            (Some(a), Some(b)) if a == b => return Some((1.0, MatchReason::Isbn)),
+            // Different ISBNs mean different editions, which are kept apart.
+            (Some(_), Some(_)) => return None,
+            _ => {}
+        }
+        let author = match (&self.author, &other.author) {
+            (Some(a), Some(b)) => similarity(a, b),
+            (None, None) => 1.0,
+            // One record is missing its author: neither for nor against.
+            _ => 0.5,
+        };
+        let title = similarity(&self.title, &other.title);
+        Some((
+            TITLE_WEIGHT * title + (1.0 - TITLE_WEIGHT) * author,
+            MatchReason::Metadata,
+        ))
+    }
+}
+
+fn find_root(parents: &mut HashMap<u32, u32>, id: u32) -> u32 {
+    let parent = parents[&id];
+    if parent == id {
+        return id;
+    }
+    let root = find_root(parents, parent);
+    parents.insert(id, root);
+    root
+}
+
+impl Library {
+    // Groups records that look like the same work. Clusters come strongest
+    // first; each lists the matches that put it together.
+    pub fn find_duplicates(&self, collation: &Collation, threshold: f64) -> Vec<DuplicateCluster> {
+        let mut books: Vec<&Book> = self.books.values().collect();
+        books.sort_by_key(|b| b.id);
+        let prints: Vec<Fingerprint> = books
+            .iter()
+            .map(|b| Fingerprint::new(b, collation))
+            .collect();
+
+        let mut matches = Vec::new();
+        for (i, a) in prints.iter().enumerate() {
+            for b in &prints[i + 1..] {
+                match a.compare(b) {
+                    Some((score, reason)) if score >= threshold => matches.push(DuplicateMatch {
+                        first: a.id,
+                        second: b.id,
+                        score,
+                        reason,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut parents: HashMap<u32, u32> = HashMap::new();
+        for m in &matches {
+            parents.entry(m.first).or_insert(m.first);
+            parents.entry(m.second).or_insert(m.second);
+            let (a, b) = (
+                find_root(&mut parents, m.first),
+                find_root(&mut parents, m.second),
+            );
+            parents.insert(a.max(b), a.min(b));
+        }
+        let mut clusters: HashMap<u32, Vec<DuplicateMatch>> = HashMap::new();
+        for m in matches {
+            let root = find_root(&mut parents, m.first);
+            clusters.entry(root).or_default().push(m);
+        }
+
+        let mut clusters: Vec<DuplicateCluster> = clusters
+            .into_values()
+            .map(|matches| {
+                let mut book_ids: Vec<u32> = matches
+                    .iter()
+                    .flat_map(|m| [m.first, m.second])
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                book_ids.sort();
+                let score = matches.iter().map(|m| m.score).fold(1.0, f64::min);
+                let suggested_survivor = *book_ids
+                    .iter()
+                    .max_by_key(|&&id| {
+                        (self.copies_of(id).len(), self.loan_count(id), u32::MAX - id)
+                    })
+                    .expect("a match links two records");
+                DuplicateCluster {
+                    book_ids,
+                    score,
+                    matches,
+                    suggested_survivor,
+                }
+            })
+            .collect();
+        clusters.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(a.book_ids[0].cmp(&b.book_ids[0]))
+        });
+        clusters
+    }
+
+    // Folds the duplicates into the survivor: copies, loans, holds, reviews,
+    // shelves and orders are re-pointed, metadata the survivor lacks is
+    // copied over, and the duplicate records are removed. A user is left with
+    // at most one hold and one review of the survivor. Merges are not kept
+    // in the undo history.
+    pub fn merge_books(
+        &mut self,
+        librarian_id: u32,
+        survivor: u32,
+        duplicates: &[u32],
+    ) -> Result<MergeSummary, MergeError> {
+        let user = self
+            .users
+            .get(&librarian_id)
+            .ok_or(MergeError::UserNotFound(librarian_id))?;
+        if !Auth::check_librarian(user) {
+            return Err(MergeError::NotLibrarian(librarian_id));
+        }
+        if !self.books.contains_key(&survivor) {
+            return Err(MergeError::BookNotFound(survivor));
+        }
+        let mut merged = HashSet::new();
+        for &id in duplicates {
+            if id == survivor {
+                return Err(MergeError::SameRecord(id));
+            }
+            if !merged.insert(id) {
+                return Err(MergeError::RepeatedRecord(id));
+            }
+            if !self.books.contains_key(&id) {
+                return Err(MergeError::BookNotFound(id));
+            }
+        }
+        let mut summary = MergeSummary {
+            survivor,
+            removed: duplicates.to_vec(),
+            ..MergeSummary::default()
+        };
+
+        for copy in self.copies.values_mut() {
+            if merged.contains(&copy.book_id) {
+                copy.book_id = survivor;
+                summary.copies += 1;
+            }
+        }
+        for loan in self.loans.values_mut() {
+            if merged.contains(&loan.book_id) {
+                loan.book_id = survivor;
+                summary.loans += 1;
+            }
+        }
+        // Holds stay in the order they were placed. A user keeps only their
+        // earliest place, and none if they now have the survivor on loan.
+        let borrowers: HashSet<u32> = self
+            .loans
+            .values()
+            .filter(|l| l.is_active() && l.book_id == survivor)
+            .filter_map(|l| l.user_id)
+            .collect();
+        let mut holders = HashSet::new();
+        self.holds.retain_mut(|hold| {
+            let moved = merged.contains(&hold.book_id);
+            if moved {
+                hold.book_id = survivor;
+            }
+            if hold.book_id != survivor {
+                return true;
+            }
+            let keep = !borrowers.contains(&hold.user_id) && holders.insert(hold.user_id);
+            if !keep {
+                summary.duplicate_holds += 1;
+            } else if moved {
+                summary.holds += 1;
+            }
+            keep
+        });
+        // Each user may review a title once, so only their newest review of
+        // the merged records is kept.
+        let mut moved = HashSet::new();
+        for review in self.reviews.values_mut() {
+            if merged.contains(&review.book_id) {
+                review.book_id = survivor;
+                moved.insert(review.id);
+            }
+        }
+        let mut newest: HashMap<u32, (DateTime<Utc>, u32)> = HashMap::new();
+        for review in self.reviews.values().filter(|r| r.book_id == survivor) {
+            let entry = newest
+                .entry(review.user_id)
+                .or_insert((review.written, review.id));
+            *entry = (*entry).max((review.written, review.id));
+        }
+        let before = self.reviews.len();
+        self.reviews
+            .retain(|id, review| review.book_id != survivor || newest[&review.user_id].1 == *id);
+        summary.duplicate_reviews = before - self.reviews.len();
+        summary.reviews = moved
+            .iter()
+            .filter(|id| self.reviews.contains_key(id))
+            .count();
+        for shelf in &mut self.shelves {
+            if !shelf.book_ids.iter().any(|id| merged.contains(id)) {
+                continue;
+            }
+            let mut seen = HashSet::new();
+            shelf.book_ids = shelf
+                .book_ids
+                .iter()
+                .map(|id| if merged.contains(id) { survivor } else { *id })
+                .filter(|id| seen.insert(*id))
+                .collect();
+            summary.shelves += 1;
+        }
+        for order in self.orders.values_mut() {
+            if order.book_id.is_some_and(|id| merged.contains(&id)) {
+                order.book_id = Some(survivor);
+            }
+        }
+        for request in self.lending_requests.values_mut() {
+            if merged.contains(&request.book_id) {
+                request.book_id = survivor;
+            }
+        }
+
+        for id in duplicates {
+            let duplicate = self.books.remove(id).expect("checked above");
+            let book = self.books.get_mut(&survivor).expect("checked above");
+            fill_missing(book, duplicate);
+        }
+        self.refresh_availability(survivor);
+        Ok(summary)
+    }
+
+    fn loan_count(&self, book_id: u32) -> usize {
+        self.loans.values().filter(|l| l.book_id == book_id).count()
+    }
+}
+
+fn fill_missing(book: &mut Book, duplicate: Book) {
+    if book.contributors.is_empty() {
+        book.contributors = duplicate.contributors;
+    }
+    book.publisher = book.publisher.take().or(duplicate.publisher);
+    book.published_date = book.published_date.or(duplicate.published_date);
+    book.language = book.language.take().or(duplicate.language);
+    book.series = book.series.take().or(duplicate.series);
+    book.page_count = book.page_count.or(duplicate.page_count);
+    book.isbn = book.isbn.take().or(duplicate.isbn);
+    for subject in duplicate.subjects {
+        if !book.subjects.contains(&subject) {
+            book.subjects.push(subject);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+    use crate::services::clock::Clock;
+    use chrono::{Duration, TimeZone};
+
+    fn catalogue() -> Library {
+        let mut library = Library::new();
+        let mut librarian = User::new(1, "carol");
+        librarian.is_librarian = true;
+        library.register_user(librarian);
+        library.register_user(User::new(2, "alice"));
+        library.register_user(User::new(3, "bob"));
+        for book in [
+            Book::builder(1, "The Hobbit")
+                .author("J. R. R. Tolkien")
+                .build(),
+            Book::builder(2, "Hobbit, The")
+                .author("Tolkien, J.R.R.")
+                .build(),
+            Book::builder(3, "The Hobbit: or There and Back Again")
+                .isbn("0-261-10221-4")
+                .build(),
+            Book::builder(4, "The Hobit")
+                .author("J.R.R. Tolkien")
+                .isbn("9780261102217")
+                .build(),
+            Book::builder(5, "The Silmarillion")
+                .author("J. R. R. Tolkien")
+                .build(),
+            Book::builder(6, "Emma")
+                .author("Jane Austen")
+                .isbn("978-0-14-143958-7")
+                .build(),
+            Book::builder(7, "Emma")
+                .author("Jane Austen")
+                .isbn("978-0-19-953552-1")
+                .build(),
+        ] {
+            library.add_book(book);
+        }
+        library
+    }
+
+    #[test]
+    fn test_isbn_forms_are_equivalent() {
+        assert_eq!(
+            normalize_isbn("0-261-10221-4"),
+            Some("9780261102217".to_string())
+        );
+        assert_eq!(
+            normalize_isbn("978 0 261 10221 7"),
+            normalize_isbn("0261102214")
+        );
+        assert_eq!(
+            normalize_isbn("080442957X"),
+            Some("9780804429573".to_string())
+        );
+        assert_eq!(normalize_isbn("0-261-10221-5"), None);
+        assert_eq!(normalize_isbn("12345"), None);
+        assert_eq!(normalize_isbn("12345678é"), None);
+        assert_eq!(normalize_isbn("978026110221é"), None);
+    }
+
+    #[test]
+    fn test_finds_clusters_across_spellings_and_isbns() {
+        let library = catalogue();
+        let clusters = library.find_duplicates(&Collation::english(), DEFAULT_DUPLICATE_THRESHOLD);
+        assert_eq!(clusters.len(), 1);
+        let cluster = &clusters[0];
+        assert_eq!(cluster.book_ids, vec![1, 2, 3, 4]);
+        assert!(cluster.score >= DEFAULT_DUPLICATE_THRESHOLD && cluster.score < 1.0);
+        assert!(cluster
+            .matches
+            .iter()
+            .any(|m| (m.first, m.second) == (3, 4) && m.reason == MatchReason::Isbn));
+        // Editions of Emma have different ISBNs and are not duplicates.
+        assert!(!cluster.book_ids.contains(&6));
+    }
+
+    #[test]
+    fn test_merge_repoints_circulation() {
+        let mut library = catalogue();
+        library.register_user(User::new(4, "dan"));
+        library.register_user(User::new(5, "erin"));
+        library.add_copy(1, "Main").unwrap();
+        library.add_copy(2, "Branch").unwrap();
+        library.checkout(1, 4).unwrap();
+        library.checkout(2, 3).unwrap();
+        library.place_hold(4, 5).unwrap();
+        library.place_hold(1, 3).unwrap();
+        library.place_hold(2, 2).unwrap();
+        library.place_hold(4, 2).unwrap();
+
+        assert_eq!(
+            library.merge_books(2, 1, &[2, 4]),
+            Err(MergeError::NotLibrarian(2))
+        );
+        assert_eq!(
+            library.merge_books(1, 1, &[1]),
+            Err(MergeError::SameRecord(1))
+        );
+        assert_eq!(
+            library.merge_books(1, 1, &[2, 2]),
+            Err(MergeError::RepeatedRecord(2))
+        );
+        assert_eq!(
+            library.merge_books(1, 1, &[2, 1]),
+            Err(MergeError::SameRecord(1))
+        );
+        let summary = library.merge_books(1, 1, &[2, 4]).unwrap();
+        assert_eq!(summary.copies, 1);
+        assert_eq!(summary.loans, 1);
+        assert_eq!(summary.holds, 2);
+        // Bob has the survivor on loan; Alice held two of the records.
+        assert_eq!(summary.duplicate_holds, 2);
+
+        assert!(library.get_book(2).is_none() && library.get_book(4).is_none());
+        assert_eq!(library.copies_of(1).len(), 2);
+        assert!(library.loans.values().all(|l| l.book_id == 1));
+        let queue: Vec<u32> = library.holds.iter().map(|h| h.user_id).collect();
+        assert_eq!(queue, vec![5, 2]);
+        assert_eq!(
+            library.get_book(1).unwrap().isbn.as_deref(),
+            Some("9780261102217")
+        );
+    }
+
+    #[test]
+    fn test_merge_keeps_newest_review_per_user() {
+        let mut library = catalogue();
+        library.set_clock(Clock::Fixed(
+            Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        ));
+        let old = library.add_review(2, 2, 2, "Slow start").unwrap();
+        library.clock_mut().advance(Duration::days(30));
+        let new = library.add_review(2, 4, 5, "Grew on me").unwrap();
+        let other = library.add_review(3, 1, 4, "Lovely").unwrap();
+
+        let summary = library.merge_books(1, 1, &[2, 4]).unwrap();
+        assert_eq!(summary.reviews, 1);
+        assert_eq!(summary.duplicate_reviews, 1);
+        assert!(library.get_review(old).is_none());
+        let ids: Vec<u32> = library.reviews_for(1).iter().map(|r| r.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&new) && ids.contains(&other));
+    }
+}
@@ -0,0 +1,771 @@
+use crate::models::{
+    book::Book,
+    loan::{Hold, Loan},
+    user::User,
+};
+use crate::services::{auth::Auth, library::Library};
+use async_graphql::connection::{query, Connection, Edge, OpaqueCursor};
+use async_graphql::{
+    Context, EmptySubscription, Error, Object, OutputType, Request, Response, Result, Schema,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_executor::block_on;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Page size used when a query asks for neither `first` nor `last`.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+// Limits on an HTTP request: how long the server waits for each read, and
+// how many bytes it accepts for the request line and headers and for the body.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEAD: u64 = 16 * 1024;
+const MAX_BODY: usize = 1024 * 1024;
+
+pub type LibrarySchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+// The user a request is made on behalf of, if any. Anonymous requests can
+// browse the catalogue but see nothing about other people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewer(pub Option<u32>);
+
+// GraphQL access to a shared library.
+#[derive(Clone)]
+pub struct GraphqlApi {
+    schema: LibrarySchema,
+    library: Arc<Mutex<Library>>,
+}
+
+impl GraphqlApi {
+    pub fn new(library: Arc<Mutex<Library>>) -> Self {
+        let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+            .data(Arc::clone(&library))
+            .finish();
+        GraphqlApi { schema, library }
+    }
+
+    // The user a bearer token was issued to; see `Library::issue_api_token`.
+    pub fn viewer_for_token(&self, token: &str) -> Option<u32> {
+        self.library
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .user_for_token(token)
+    }
+
+    // The schema in GraphQL SDL, for client tooling.
+    pub fn sdl(&self) -> String {
+        self.schema.sdl()
+    }
+
+    pub fn execute(&self, request: impl Into<Request>, viewer: Option<u32>) -> Response {
+        block_on(self.schema.execute(request.into().data(Viewer(viewer))))
+    }
+}
+
+fn library<'a>(ctx: &Context<'a>) -> MutexGuard<'a, Library> {
+    ctx.data_unchecked::<Arc<Mutex<Library>>>()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn viewer(ctx: &Context<'_>) -> Option<u32> {
+    ctx.data_opt::<Viewer>().and_then(|v| v.0)
+}
+
+fn is_librarian(library: &Library, viewer: Option<u32>) -> bool {
+    viewer
+        .and_then(|id| library.get_user(id))
+        .is_some_and(Auth::check_librarian)
+}
+
+// Data about a user is visible to that user and to librarians.
+fn authorise(library: &Library, viewer: Option<u32>, owner: Option<u32>) -> Result<()> {
+    if (viewer.is_some() && viewer == owner) || is_librarian(library, viewer) {
+        Ok(())
+    } else {
+        Err(Error::new("not authorised"))
+    }
+}
+
+fn require_librarian(library: &Library, viewer: Option<u32>) -> Result<()> {
+    if is_librarian(library, viewer) {
+        Ok(())
+    } else {
+        Err(Error::new("librarians only"))
+    }
+}
+
+pub type Cursor = OpaqueCursor<u32>;
+pub type Page<T> = Connection<Cursor, T>;
+
+// Relay-style pagination over items keyed by id. Cursors encode the id, so
+// pages stay stable while items are added.
+async fn paginate<T: OutputType>(
+    mut items: Vec<(u32, T)>,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> Result<Page<T>> {
+    items.sort_by_key(|(id, _)| *id);
+    query(
+        after,
+        before,
+        first,
+        last,
+        |after: Option<Cursor>, before: Option<Cursor>, first, last| async move {
+            let mut start = after.map_or(0, |a| items.partition_point(|(id, _)| *id <= a.0));
+            let mut end =
+                before.map_or(items.len(), |b| items.partition_point(|(id, _)| *id < b.0));
+            end = end.max(start);
+            match (first, last) {
+                (Some(first), _) => end = end.min(start + first),
+                (None, None) => end = end.min(start + DEFAULT_PAGE_SIZE),
+                _ => {}
+            }
+            if let Some(last) = last {
+                start = start.max(end.saturating_sub(last));
+            }
+
+            let mut connection = Connection::new(start > 0, end < items.len());
+            connection.edges.extend(
+                items
+                    .into_iter()
+                    .skip(start)
+                    .take(end - start)
+                    .map(|(id, node)| Edge::new(OpaqueCursor(id), node)),
+            );
+            Ok::<_, Error>(connection)
+        },
+    )
+    .await
+}
+
+pub struct BookNode(Book);
+
+#[Object(name = "Book")]
+impl BookNode {
+    async fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn authors(&self) -> Vec<&str> {
+        self.0.authors()
+    }
+
+    async fn publisher(&self) -> Option<&str> {
+        self.0.publisher.as_deref()
+    }
+
+    async fn published_date(&self) -> Option<NaiveDate> {
+        self.0.published_date
+    }
+
+    async fn subjects(&self) -> &[String] {
+        &self.0.subjects
+    }
+
+    async fn page_count(&self) -> Option<u32> {
+        self.0.page_count
+    }
+
+    async fn is_available(&self) -> bool {
+        self.0.is_available
+    }
+
+    async fn copy_count(&self, ctx: &Context<'_>) -> usize {
+        library(ctx).copies_of(self.0.id).len()
+    }
+
+    async fn hold_count(&self, ctx: &Context<'_>) -> usize {
+        library(ctx).holds_for_book(self.0.id).len()
+    }
+
+    // The queue itself shows who is waiting, so only librarians see it.
+    async fn holds(&self, ctx: &Context<'_>) -> Result<Option<Vec<HoldNode>>> {
+        let library = library(ctx);
+        require_librarian(&library, viewer(ctx))?;
+        let holds = library.holds_for_book(self.0.id);
+        Ok(Some(holds.into_iter().cloned().map(HoldNode).collect()))
+    }
+}
+
+pub struct UserNode(User);
+
+#[Object(name = "User")]
+impl UserNode {
+    async fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    async fn username(&self, ctx: &Context<'_>) -> Result<Option<&str>> {
+        authorise(&library(ctx), viewer(ctx), Some(self.0.id))?;
+        Ok(Some(&self.0.username))
+    }
+
+    async fn is_librarian(&self) -> bool {
+        self.0.is_librarian
+    }
+
+    async fn loans(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Option<Page<LoanNode>>> {
+        let items = {
+            let library = library(ctx);
+            authorise(&library, viewer(ctx), Some(self.0.id))?;
+            library
+                .loan_history_for(self.0.id)
+                .into_iter()
+                .map(|l| (l.id, LoanNode(l.clone())))
+                .collect()
+        };
+        paginate(items, after, before, first, last).await.map(Some)
+    }
+
+    async fn holds(&self, ctx: &Context<'_>) -> Result<Option<Vec<HoldNode>>> {
+        let library = library(ctx);
+        authorise(&library, viewer(ctx), Some(self.0.id))?;
+        let holds = library.holds.iter().filter(|h| h.user_id == self.0.id);
+        Ok(Some(holds.cloned().map(HoldNode).collect()))
+    }
+}
+
+pub struct LoanNode(Loan);
+
+#[Object(name = "Loan")]
+impl LoanNode {
+    async fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    async fn book(&self, ctx: &Context<'_>) -> Option<BookNode> {
+        library(ctx).get_book(self.0.book_id).cloned().map(BookNode)
+    }
+
+    async fn copy_id(&self) -> Option<u32> {
+        self.0.copy_id
+    }
+
+    // Null for anonymised loans as well as unauthorised viewers.
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<UserNode>> {
+        let library = library(ctx);
+        let Some(user_id) = self.0.user_id else {
+            return Ok(None);
+        };
+        authorise(&library, viewer(ctx), Some(user_id))?;
+        Ok(library.get_user(user_id).cloned().map(UserNode))
+    }
+
+    async fn checked_out(&self) -> DateTime<Utc> {
+        self.0.checked_out
+    }
+
+    async fn due_date(&self) -> DateTime<Utc> {
+        self.0.due_date
+    }
+
+    async fn returned(&self) -> Option<DateTime<Utc>> {
+        self.0.returned
+    }
+
+    async fn renewals(&self) -> u32 {
+        self.0.renewals
+    }
+
+    async fn is_overdue(&self, ctx: &Context<'_>) -> bool {
+        self.0.is_overdue(library(ctx).now())
+    }
+
+    // Accrued fine in cents.
+    async fn fine(&self, ctx: &Context<'_>) -> Option<u32> {
+        library(ctx).fine_for(self.0.id)
+    }
+}
+
+pub struct HoldNode(Hold);
+
+#[Object(name = "Hold")]
+impl HoldNode {
+    async fn book(&self, ctx: &Context<'_>) -> Option<BookNode> {
+        library(ctx).get_book(self.0.book_id).cloned().map(BookNode)
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<UserNode>> {
+        let library = library(ctx);
+        authorise(&library, viewer(ctx), Some(self.0.user_id))?;
+        Ok(library.get_user(self.0.user_id).cloned().map(UserNode))
+    }
+
+    async fn placed(&self) -> DateTime<Utc> {
+        self.0.placed
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn books(
+        &self,
+        ctx: &Context<'_>,
+        available: Option<bool>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<BookNode>> {
+        let items = library(ctx)
+            .books
+            .values()
+            .filter(|b| available.is_none_or(|a| b.is_available == a))
+            .map(|b| (b.id, BookNode(b.clone())))
+            .collect();
+        paginate(items, after, before, first, last).await
+    }
+
+    async fn book(&self, ctx: &Context<'_>, id: u32) -> Option<BookNode> {
+        library(ctx).get_book(id).cloned().map(BookNode)
+    }
+
+    // The user making the request.
+    async fn viewer(&self, ctx: &Context<'_>) -> Option<UserNode> {
+        let id = viewer(ctx)?;
+        library(ctx).get_user(id).cloned().map(UserNode)
+    }
+
+    async fn user(&self, ctx: &Context<'_>, id: u32) -> Result<Option<UserNode>> {
+        let library = library(ctx);
+        authorise(&library, viewer(ctx), Some(id))?;
+        Ok(library.get_user(id).cloned().map(UserNode))
+    }
+
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<UserNode>> {
+        let items = {
+            let library = library(ctx);
+            require_librarian(&library, viewer(ctx))?;
+            library
+                .users
+                .values()
+                .map(|u| (u.id, UserNode(u.clone())))
+                .collect()
+        };
+        paginate(items, after, before, first, last).await
+    }
+
+    async fn loan(&self, ctx: &Context<'_>, id: u32) -> Result<Option<LoanNode>> {
+        let library = library(ctx);
+        let Some(loan) = library.get_loan(id) else {
+            return Ok(None);
+        };
+        authorise(&library, viewer(ctx), loan.user_id)?;
+        Ok(Some(LoanNode(loan.clone())))
+    }
+
+    async fn loans(
+        &self,
+        ctx: &Context<'_>,
+        active_only: Option<bool>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<Page<LoanNode>> {
+        let items = {
+            let library = library(ctx);
+            require_librarian(&library, viewer(ctx))?;
+            library
+                .loans
+                .values()
+                .filter(|l| !active_only.unwrap_or(false) || l.is_active())
+                .map(|l| (l.id, LoanNode(l.clone())))
+                .collect()
+        };
+        paginate(items, after, before, first, last).await
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    // Borrows a book for the viewer, or for `userId` when a librarian is
+    // working the desk.
+    async fn checkout(
+        &self,
+        ctx: &Context<'_>,
+        book_id: u32,
+        user_id: Option<u32>,
+    ) -> Result<LoanNode> {
+        let mut library = library(ctx);
+        let viewer = viewer(ctx);
+        let borrower = user_id
+            .or(viewer)
+            .ok_or_else(|| Error::new("not signed in"))?;
+        authorise(&library, viewer, Some(borrower))?;
+        let loan_id = library.checkout(book_id, borrower)?;
+        Ok(LoanNode(library.loans[&loan_id].clone()))
+    }
+
+    async fn return_loan(&self, ctx: &Context<'_>, loan_id: u32) -> Result<LoanNode> {
+        let mut library = library(ctx);
+        let owner = library
+            .get_loan(loan_id)
+            .ok_or_else(|| Error::new(format!("loan {} not found", loan_id)))?
+            .user_id;
+        authorise(&library, viewer(ctx), owner)?;
+        library.return_book(loan_id)?;
+        Ok(LoanNode(library.loans[&loan_id].clone()))
+    }
+}
+
+// Serves `POST /graphql` on a background thread. The viewer is the user an
+// `Authorization: Bearer <token>` header was issued to; requests without one
+// are anonymous, and an unknown token is refused with 401.
+pub struct GraphqlServer {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl GraphqlServer {
+    pub fn start(api: GraphqlApi, addr: impl ToSocketAddrs) -> io::Result<GraphqlServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if !flag.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let api = api.clone();
+                thread::spawn(move || {
+                    let _ = serve_http(stream, &api);
+                });
+            }
+        });
+        Ok(GraphqlServer {
+            addr,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.running.store(false, Ordering::SeqCst);
+            let _ = TcpStream::connect(self.addr);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for GraphqlServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Handles a single HTTP/1.1 request and closes the connection.
+fn serve_http(stream: TcpStream, api: &GraphqlApi) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let (status, body) = respond(&mut reader, api)?;
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn http_error(status: &'static str, message: &str) -> (&'static str, String) {
+    (status, serde_json::json!({ "error": message }).to_string())
+}
+
+// Reads the request and works out the status and body to send back. Requests
+// that are malformed, too large or badly authenticated are refused before the
+// body is read.
+fn respond(
+    reader: &mut BufReader<TcpStream>,
+    api: &GraphqlApi,
+) -> io::Result<(&'static str, String)> {
+    let mut head = reader.by_ref().take(MAX_HEAD);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        head.read_line(&mut header)?;
+        if !header.ends_with('\n') && head.limit() == 0 {
+            return Ok(http_error(
+                "431 Request Header Fields Too Large",
+                "request headers are too large",
+            ));
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => match value.trim().parse() {
+                    Ok(length) => content_length = length,
+                    Err(_) => return Ok(http_error("400 Bad Request", "invalid Content-Length")),
+                },
+                "authorization" => authorization = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    if (parts.next(), parts.next()) != (Some("POST"), Some("/graphql")) {
+        return Ok(http_error("404 Not Found", "not found"));
+    }
+    if content_length > MAX_BODY {
+        return Ok(http_error(
+            "413 Payload Too Large",
+            &format!("request body is larger than {} bytes", MAX_BODY),
+        ));
+    }
+    let viewer = match authorization {
+        None => None,
+        Some(value) => match value
+            .strip_prefix("Bearer ")
+            .and_then(|token| api.viewer_for_token(token.trim()))
+        {
+            Some(id) => Some(id),
+            None => return Ok(http_error("401 Unauthorized", "invalid bearer token")),
+        },
+    };
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(match serde_json::from_slice::<Request>(&body) {
+        Ok(request) => {
+            let response = api.execute(request, viewer);
+            (
+                "200 OK",
+                serde_json::to_string(&response).unwrap_or_default(),
+            )
+        }
+        Err(e) => http_error("400 Bad Request", &e.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    const LIBRARIAN: u32 = 1;
+    const ALICE: u32 = 2;
+    const BOB: u32 = 3;
+
+    fn api() -> GraphqlApi {
+        let mut library = Library::new();
+        for id in 1..=5 {
+            library.add_book(Book::new(id, &format!("Volume {}", id), "Anon"));
+        }
+        let mut librarian = User::new(LIBRARIAN, "carol");
+        librarian.is_librarian = true;
+        library.register_user(librarian);
+        library.register_user(User::new(ALICE, "alice"));
+        library.register_user(User::new(BOB, "bob"));
+        library.checkout(1, ALICE).unwrap();
+        GraphqlApi::new(Arc::new(Mutex::new(library)))
+    }
+
+    fn data(response: Response) -> Value {
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        response.data.into_json().unwrap()
+    }
+
+    #[test]
+    fn test_cursor_pagination() {
+        let api = api();
+        let page = data(api.execute(
+            "{ books(first: 2) { edges { cursor node { id } } \
+             pageInfo { hasNextPage endCursor } } }",
+            None,
+        ));
+        let ids: Vec<&Value> = page["books"]["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| &e["node"]["id"])
+            .collect();
+        assert_eq!(ids, [&json!(1), &json!(2)]);
+        assert_eq!(page["books"]["pageInfo"]["hasNextPage"], json!(true));
+
+        let cursor = page["books"]["pageInfo"]["endCursor"].as_str().unwrap();
+        let query = format!(
+            "{{ books(first: 10, after: \"{}\") {{ edges {{ node {{ id }} }} \
+             pageInfo {{ hasNextPage hasPreviousPage }} }} }}",
+            cursor
+        );
+        let rest = data(api.execute(query.as_str(), None));
+        assert_eq!(rest["books"]["edges"].as_array().unwrap().len(), 3);
+        assert_eq!(rest["books"]["edges"][0]["node"]["id"], json!(3));
+        assert_eq!(rest["books"]["pageInfo"]["hasNextPage"], json!(false));
+        assert_eq!(rest["books"]["pageInfo"]["hasPreviousPage"], json!(true));
+    }
+
+    #[test]
+    fn test_field_authorisation() {
+        let api = api();
+        let query = "{ user(id: 2) { id username loans { edges { node { id } } } } }";
+
+        let own = data(api.execute(query, Some(ALICE)));
+        assert_eq!(own["user"]["username"], json!("alice"));
+        assert_eq!(own["user"]["loans"]["edges"].as_array().unwrap().len(), 1);
+        assert_eq!(data(api.execute(query, Some(LIBRARIAN))), own);
+
+        let response = api.execute(query, Some(BOB));
+        assert!(!response.errors.is_empty());
+
+        // Anyone may browse books, but the hold queue is for librarians.
+        let response = api.execute("{ book(id: 1) { title holdCount holds { placed } } }", None);
+        assert_eq!(response.errors.len(), 1);
+        let book = response.data.into_json().unwrap();
+        assert_eq!(book["book"]["title"], json!("Volume 1"));
+        assert_eq!(book["book"]["holds"], Value::Null);
+
+        assert!(!api
+            .execute("{ loans { edges { cursor } } }", Some(ALICE))
+            .errors
+            .is_empty());
+    }
+
+    #[test]
+    fn test_checkout_and_return_mutations() {
+        let api = api();
+        let loan = data(api.execute(
+            "mutation { checkout(bookId: 2) { id book { isAvailable } } }",
+            Some(BOB),
+        ));
+        let loan_id = loan["checkout"]["id"].as_u64().unwrap();
+        assert_eq!(loan["checkout"]["book"]["isAvailable"], json!(false));
+
+        // Patrons cannot borrow or return on someone else's behalf.
+        assert!(!api
+            .execute(
+                "mutation { checkout(bookId: 3, userId: 3) { id } }",
+                Some(ALICE)
+            )
+            .errors
+            .is_empty());
+        let return_query = format!(
+            "mutation {{ returnLoan(loanId: {}) {{ returned }} }}",
+            loan_id
+        );
+        assert!(!api
+            .execute(return_query.as_str(), Some(ALICE))
+            .errors
+            .is_empty());
+
+        let returned = data(api.execute(return_query.as_str(), Some(BOB)));
+        assert!(returned["returnLoan"]["returned"].is_string());
+        assert!(!api
+            .execute("mutation { checkout(bookId: 1) { id } }", Some(BOB))
+            .errors
+            .is_empty());
+    }
+
+    // Sends a raw request with the given extra headers and returns the status
+    // line and body.
+    fn post(server: &GraphqlServer, headers: &str, body: &str) -> (String, Value) {
+        let mut stream = TcpStream::connect(server.addr()).unwrap();
+        write!(
+            stream,
+            "POST /graphql HTTP/1.1\r\nHost: localhost\r\n{}\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            headers,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.lines().next().unwrap().to_string();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_http_endpoint() {
+        let api = api();
+        let token = api.library.lock().unwrap().issue_api_token(ALICE).unwrap();
+        let server = GraphqlServer::start(api, "127.0.0.1:0").unwrap();
+        let query = json!({ "query": "{ viewer { username } }" }).to_string();
+
+        let (status, json) = post(
+            &server,
+            &format!("Authorization: Bearer {}\r\n", token),
+            &query,
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(json["data"]["viewer"]["username"], json!("alice"));
+
+        // A bare user id is not a credential.
+        let (status, json) = post(&server, "X-User-Id: 1\r\n", &query);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(json["data"]["viewer"], Value::Null);
+
+        let (status, _) = post(&server, "Authorization: Bearer 1\r\n", &query);
+        assert_eq!(status, "HTTP/1.1 401 Unauthorized");
+    }
+
+    #[test]
+    fn test_http_rejects_bad_lengths_before_reading() {
+        let server = GraphqlServer::start(api(), "127.0.0.1:0").unwrap();
+        for (length, expected) in [
+            ("lots", "HTTP/1.1 400 Bad Request"),
+            ("-1", "HTTP/1.1 400 Bad Request"),
+            ("99999999999", "HTTP/1.1 413 Payload Too Large"),
+        ] {
+            let mut stream = TcpStream::connect(server.addr()).unwrap();
+            write!(
+                stream,
+                "POST /graphql HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                length
+            )
+            .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with(expected), "{}", response);
+        }
+    }
+}
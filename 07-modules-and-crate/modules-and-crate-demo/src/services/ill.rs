@@ -0,0 +1,831 @@
+use crate::models::ill::{
+    BorrowState, BorrowingRequest, IllBody, IllMessage, IllParseError, LendState, LendingRequest,
+};
+use crate::services::{circulation::CirculationError, library::Library};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// The longest message line the server will read, newline included.
+const MAX_LINE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IllError {
+    UnknownPartner(String),
+    // A message signed with a partner's code arrived from another host.
+    WrongPeer {
+        partner: String,
+        peer: IpAddr,
+    },
+    UserNotFound(u32),
+    RequestNotFound(u32),
+    // The request is not in a state where `action` makes sense.
+    InvalidState {
+        request_id: u32,
+        action: &'static str,
+    },
+    Circulation(CirculationError),
+    Protocol(IllParseError),
+    // The partner understood the message but refused it.
+    Remote(String),
+    Io(String),
+}
+
+impl fmt::Display for IllError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllError::UnknownPartner(code) => write!(f, "{} is not a partner library", code),
+            IllError::WrongPeer { partner, peer } => {
+                write!(f, "{} does not send messages from {}", partner, peer)
+            }
+            IllError::UserNotFound(id) => write!(f, "user {} not found", id),
+            IllError::RequestNotFound(id) => write!(f, "ILL request {} not found", id),
+            IllError::InvalidState { request_id, action } => write!(
+                f,
+                "ILL request {} cannot be {} in its current state",
+                request_id, action
+            ),
+            IllError::Circulation(e) => write!(f, "{}", e),
+            IllError::Protocol(e) => write!(f, "{}", e),
+            IllError::Remote(message) => write!(f, "partner refused: {}", message),
+            IllError::Io(message) => write!(f, "ILL transport error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for IllError {}
+
+impl From<CirculationError> for IllError {
+    fn from(e: CirculationError) -> Self {
+        IllError::Circulation(e)
+    }
+}
+
+impl From<IllParseError> for IllError {
+    fn from(e: IllParseError) -> Self {
+        IllError::Protocol(e)
+    }
+}
+
+impl From<io::Error> for IllError {
+    fn from(e: io::Error) -> Self {
+        IllError::Io(e.to_string())
+    }
+}
+
+// A library we exchange loans with. When we lend to it, the loans are
+// recorded against `user_id`, a local account standing in for the partner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllPartner {
+    pub code: String,
+    pub addr: SocketAddr,
+    pub user_id: u32,
+}
+
+// Sends one message and waits for the reply, over a fresh connection.
+pub fn send_ill(addr: SocketAddr, message: &IllMessage) -> Result<IllMessage, IllError> {
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    writeln!(stream, "{}", message.encode())?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(IllMessage::parse(&line)?)
+}
+
+impl Library {
+    // The code this library signs its ILL messages with.
+    pub fn set_ill_code(&mut self, code: &str) {
+        self.ill_code = code.to_string();
+    }
+
+    pub fn ill_code(&self) -> &str {
+        &self.ill_code
+    }
+
+    pub fn add_ill_partner(
+        &mut self,
+        code: &str,
+        addr: SocketAddr,
+        user_id: u32,
+    ) -> Result<(), IllError> {
+        if !self.users.contains_key(&user_id) {
+            return Err(IllError::UserNotFound(user_id));
+        }
+        self.ill_partners.insert(
+            code.to_string(),
+            IllPartner {
+                code: code.to_string(),
+                addr,
+                user_id,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn borrowing_request(&self, id: u32) -> Option<&BorrowingRequest> {
+        self.borrowing_requests.get(&id)
+    }
+
+    pub fn lending_request(&self, borrower: &str, request_id: u32) -> Option<&LendingRequest> {
+        self.lending_requests
+            .get(&(borrower.to_string(), request_id))
+    }
+
+    // Asks a partner for a title on behalf of a patron. The request is
+    // recorded whether the partner accepts it or not. Its id is used up even
+    // if no reply arrives, since the partner may have recorded it anyway.
+    pub fn request_ill(
+        &mut self,
+        lender: &str,
+        patron_id: u32,
+        title: &str,
+        author: &str,
+    ) -> Result<u32, IllError> {
+        if !self.users.contains_key(&patron_id) {
+            return Err(IllError::UserNotFound(patron_id));
+        }
+        let id = self.next_ill_id;
+        self.next_ill_id += 1;
+        let body = IllBody::Request {
+            title: title.to_string(),
+            author: author.to_string(),
+        };
+        let state = match self.send_to_partner(lender, id, body)? {
+            IllBody::Accepted => BorrowState::Accepted,
+            IllBody::Unfilled { reason } => BorrowState::Unfilled { reason },
+            other => return Err(unexpected_reply(&other)),
+        };
+        self.borrowing_requests.insert(
+            id,
+            BorrowingRequest {
+                id,
+                lender: lender.to_string(),
+                patron_id,
+                title: title.to_string(),
+                author: author.to_string(),
+                state,
+            },
+        );
+        Ok(id)
+    }
+
+    // Confirms that a shipped item has arrived.
+    pub fn receive_ill(&mut self, id: u32) -> Result<(), IllError> {
+        let request = self.find_borrowing(id)?;
+        let BorrowState::Shipped { due } = request.state else {
+            return Err(IllError::InvalidState {
+                request_id: id,
+                action: "received",
+            });
+        };
+        let lender = request.lender.clone();
+        self.send_expecting_ack(&lender, id, IllBody::Received)?;
+        self.set_borrow_state(id, BorrowState::Received { due });
+        Ok(())
+    }
+
+    // Sends a received item back to the lender.
+    pub fn return_ill(&mut self, id: u32) -> Result<(), IllError> {
+        let request = self.find_borrowing(id)?;
+        if !matches!(request.state, BorrowState::Received { .. }) {
+            return Err(IllError::InvalidState {
+                request_id: id,
+                action: "returned",
+            });
+        }
+        let lender = request.lender.clone();
+        self.send_expecting_ack(&lender, id, IllBody::Returned)?;
+        self.set_borrow_state(id, BorrowState::Returned);
+        Ok(())
+    }
+
+    // Withdraws a request the lender has not shipped yet.
+    pub fn cancel_ill(&mut self, id: u32, reason: &str) -> Result<(), IllError> {
+        let request = self.find_borrowing(id)?;
+        if !matches!(request.state, BorrowState::Accepted) {
+            return Err(IllError::InvalidState {
+                request_id: id,
+                action: "cancelled",
+            });
+        }
+        let lender = request.lender.clone();
+        let body = IllBody::Cancel {
+            reason: reason.to_string(),
+        };
+        self.send_expecting_ack(&lender, id, body)?;
+        self.set_borrow_state(
+            id,
+            BorrowState::Cancelled {
+                reason: reason.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    // Lends the requested book to the partner and tells it the due date.
+    pub fn ship_ill(&mut self, borrower: &str, request_id: u32) -> Result<u32, IllError> {
+        let key = (borrower.to_string(), request_id);
+        let request = self.find_lending(&key)?;
+        if request.state != LendState::Accepted {
+            return Err(IllError::InvalidState {
+                request_id,
+                action: "shipped",
+            });
+        }
+        let book_id = request.book_id;
+        let partner_user = self.partner(borrower)?.user_id;
+
+        let loan_id = self.checkout(book_id, partner_user)?;
+        let due = self.loans[&loan_id].due_date;
+        if let Err(e) = self.send_expecting_ack(borrower, request_id, IllBody::Shipped { due }) {
+            // The book never left, so the loan is dropped rather than
+            // returned and stays out of the history and statistics.
+            self.loans.remove(&loan_id);
+            if let Some(book) = self.books.get_mut(&book_id) {
+                book.is_available = true;
+            }
+            return Err(e);
+        }
+        self.set_lend_state(&key, LendState::Shipped { loan_id });
+        Ok(loan_id)
+    }
+
+    // Tells the borrower we cannot fill a request we accepted earlier.
+    pub fn decline_ill(
+        &mut self,
+        borrower: &str,
+        request_id: u32,
+        reason: &str,
+    ) -> Result<(), IllError> {
+        let key = (borrower.to_string(), request_id);
+        let request = self.find_lending(&key)?;
+        if request.state != LendState::Accepted {
+            return Err(IllError::InvalidState {
+                request_id,
+                action: "declined",
+            });
+        }
+        let body = IllBody::Unfilled {
+            reason: reason.to_string(),
+        };
+        self.send_expecting_ack(borrower, request_id, body)?;
+        self.set_lend_state(
+            &key,
+            LendState::Cancelled {
+                reason: reason.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    // Closes the loan once the returned item is back on our shelves.
+    pub fn check_in_ill(&mut self, borrower: &str, request_id: u32) -> Result<(), IllError> {
+        let key = (borrower.to_string(), request_id);
+        let request = self.find_lending(&key)?;
+        let LendState::Returned { loan_id } = request.state else {
+            return Err(IllError::InvalidState {
+                request_id,
+                action: "checked in",
+            });
+        };
+        self.return_book(loan_id)?;
+        self.set_lend_state(&key, LendState::Completed);
+        Ok(())
+    }
+
+    // Applies a message from a partner and produces the reply to send back.
+    // `peer` is the address the message came from; it must be the host the
+    // sending partner was registered with.
+    pub fn handle_ill(&mut self, peer: IpAddr, message: IllMessage) -> IllMessage {
+        let body = match self.apply_ill(peer, &message) {
+            Ok(body) => body,
+            Err(e) => IllBody::Error {
+                message: e.to_string(),
+            },
+        };
+        IllMessage::new(&self.ill_code, message.request_id, body)
+    }
+
+    fn apply_ill(&mut self, peer: IpAddr, message: &IllMessage) -> Result<IllBody, IllError> {
+        if self.partner(&message.from)?.addr.ip() != peer {
+            return Err(IllError::WrongPeer {
+                partner: message.from.clone(),
+                peer,
+            });
+        }
+        let id = message.request_id;
+        let key = (message.from.clone(), id);
+
+        match &message.body {
+            // Sent by a borrower to us as lender.
+            IllBody::Request { title, author } => {
+                if self.lending_requests.contains_key(&key) {
+                    return Err(IllError::InvalidState {
+                        request_id: id,
+                        action: "requested again",
+                    });
+                }
+                let Some(book_id) = self.find_lendable(title, author) else {
+                    return Ok(IllBody::Unfilled {
+                        reason: format!("{} is not available", title),
+                    });
+                };
+                self.lending_requests.insert(
+                    key,
+                    LendingRequest {
+                        borrower: message.from.clone(),
+                        request_id: id,
+                        book_id,
+                        state: LendState::Accepted,
+                    },
+                );
+                Ok(IllBody::Accepted)
+            }
+            IllBody::Received => {
+                let state = &self.find_lending(&key)?.state;
+                let LendState::Shipped { loan_id } = *state else {
+                    return Err(IllError::InvalidState {
+                        request_id: id,
+                        action: "received",
+                    });
+                };
+                self.set_lend_state(&key, LendState::Received { loan_id });
+                Ok(IllBody::Ack)
+            }
+            IllBody::Returned => {
+                let state = &self.find_lending(&key)?.state;
+                let (LendState::Shipped { loan_id } | LendState::Received { loan_id }) = *state
+                else {
+                    return Err(IllError::InvalidState {
+                        request_id: id,
+                        action: "returned",
+                    });
+                };
+                self.set_lend_state(&key, LendState::Returned { loan_id });
+                Ok(IllBody::Ack)
+            }
+            IllBody::Cancel { reason } => {
+                if self.find_lending(&key)?.state != LendState::Accepted {
+                    return Err(IllError::InvalidState {
+                        request_id: id,
+                        action: "cancelled",
+                    });
+                }
+                let state = LendState::Cancelled {
+                    reason: reason.clone(),
+                };
+                self.set_lend_state(&key, state);
+                Ok(IllBody::Ack)
+            }
+
+            // Sent by a lender to us as borrower.
+            IllBody::Shipped { due } => {
+                self.borrowing_request_from(&message.from, id, "shipped")?;
+                self.set_borrow_state(id, BorrowState::Shipped { due: *due });
+                Ok(IllBody::Ack)
+            }
+            IllBody::Unfilled { reason } => {
+                self.borrowing_request_from(&message.from, id, "declined")?;
+                let state = BorrowState::Unfilled {
+                    reason: reason.clone(),
+                };
+                self.set_borrow_state(id, state);
+                Ok(IllBody::Ack)
+            }
+
+            other => Err(unexpected_reply(other)),
+        }
+    }
+
+    // A book matching the title (and author, if given) that can go out now.
+    fn find_lendable(&self, title: &str, author: &str) -> Option<u32> {
+        let mut matches: Vec<u32> = self
+            .books
+            .values()
+            .filter(|b| b.is_available && b.title.eq_ignore_ascii_case(title.trim()))
+            .filter(|b| {
+                author.trim().is_empty()
+                    || b.authors()
+                        .iter()
+                        .any(|a| a.eq_ignore_ascii_case(author.trim()))
+            })
+            .map(|b| b.id)
+            .collect();
+        matches.sort_unstable();
+        matches.first().copied()
+    }
+
+    // An outstanding request from `lender` waiting to be shipped or declined.
+    fn borrowing_request_from(
+        &self,
+        lender: &str,
+        id: u32,
+        action: &'static str,
+    ) -> Result<(), IllError> {
+        let request = self
+            .borrowing_requests
+            .get(&id)
+            .filter(|r| r.lender == lender)
+            .ok_or(IllError::RequestNotFound(id))?;
+        if matches!(request.state, BorrowState::Accepted) {
+            Ok(())
+        } else {
+            Err(IllError::InvalidState {
+                request_id: id,
+                action,
+            })
+        }
+    }
+
+    fn find_borrowing(&self, id: u32) -> Result<&BorrowingRequest, IllError> {
+        self.borrowing_requests
+            .get(&id)
+            .ok_or(IllError::RequestNotFound(id))
+    }
+
+    fn find_lending(&self, key: &(String, u32)) -> Result<&LendingRequest, IllError> {
+        self.lending_requests
+            .get(key)
+            .ok_or(IllError::RequestNotFound(key.1))
+    }
+
+    fn set_borrow_state(&mut self, id: u32, state: BorrowState) {
+        if let Some(request) = self.borrowing_requests.get_mut(&id) {
+            request.state = state;
+        }
+    }
+
+    fn set_lend_state(&mut self, key: &(String, u32), state: LendState) {
+        if let Some(request) = self.lending_requests.get_mut(key) {
+            request.state = state;
+        }
+    }
+
+    fn partner(&self, code: &str) -> Result<&IllPartner, IllError> {
+        self.ill_partners
+            .get(code)
+            .ok_or_else(|| IllError::UnknownPartner(code.to_string()))
+    }
+
+    fn send_to_partner(
+        &self,
+        code: &str,
+        request_id: u32,
+        body: IllBody,
+    ) -> Result<IllBody, IllError> {
+        let addr = self.partner(code)?.addr;
+        let reply = send_ill(addr, &IllMessage::new(&self.ill_code, request_id, body))?;
+        match reply.body {
+            IllBody::Error { message } => Err(IllError::Remote(message)),
+            body => Ok(body),
+        }
+    }
+
+    fn send_expecting_ack(
+        &self,
+        code: &str,
+        request_id: u32,
+        body: IllBody,
+    ) -> Result<(), IllError> {
+        match self.send_to_partner(code, request_id, body)? {
+            IllBody::Ack => Ok(()),
+            other => Err(unexpected_reply(&other)),
+        }
+    }
+}
+
+fn unexpected_reply(body: &IllBody) -> IllError {
+    IllError::Protocol(IllParseError(format!("unexpected message {:?}", body)))
+}
+
+// Answers ILL messages for a shared library on a background thread, one
+// thread per connection.
+pub struct IllServer {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IllServer {
+    // Binding to port 0 picks a free port; see `addr`.
+    pub fn start(library: Arc<Mutex<Library>>, addr: impl ToSocketAddrs) -> io::Result<IllServer> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if !flag.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let library = Arc::clone(&library);
+                thread::spawn(move || serve_connection(stream, &library));
+            }
+        });
+        Ok(IllServer {
+            addr,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.running.store(false, Ordering::SeqCst);
+            // Wake the accept loop so it notices the flag.
+            let _ = TcpStream::connect_timeout(&self.addr, TIMEOUT);
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for IllServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Idle or stalled clients are dropped after `TIMEOUT`, and a line longer than
+// `MAX_LINE` gets an error reply and closes the connection.
+fn serve_connection(stream: TcpStream, library: &Mutex<Library>) {
+    if stream.set_read_timeout(Some(TIMEOUT)).is_err()
+        || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let Ok(peer) = stream.peer_addr() else {
+        return;
+    };
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        match reader.by_ref().take(MAX_LINE).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let too_long = !line.ends_with('\n');
+        let mut library = library.lock().unwrap_or_else(|e| e.into_inner());
+        let parsed = if too_long {
+            Err(IllParseError(format!(
+                "message longer than {} bytes",
+                MAX_LINE
+            )))
+        } else {
+            IllMessage::parse(&line)
+        };
+        let reply = match parsed {
+            Ok(message) => library.handle_ill(peer.ip(), message),
+            Err(e) => IllMessage::new(
+                &library.ill_code,
+                0,
+                IllBody::Error {
+                    message: e.to_string(),
+                },
+            ),
+        };
+        drop(library);
+        if writeln!(writer, "{}", reply.encode()).is_err() || too_long {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+
+    struct Partners {
+        north: Arc<Mutex<Library>>,
+        south: Arc<Mutex<Library>>,
+        servers: (IllServer, IllServer),
+    }
+
+    // NORTH lends, SOUTH borrows. Each side has a local account for the other.
+    fn partners() -> Partners {
+        let mut north = Library::new();
+        north.set_ill_code("NORTH");
+        north.add_book(Book::new(1, "Piranesi", "Susanna Clarke"));
+        north.register_user(User::new(90, "ill-south"));
+        let mut south = Library::new();
+        south.set_ill_code("SOUTH");
+        south.register_user(User::new(1, "alice"));
+        south.register_user(User::new(90, "ill-north"));
+
+        let north = Arc::new(Mutex::new(north));
+        let south = Arc::new(Mutex::new(south));
+        let north_server = IllServer::start(Arc::clone(&north), "127.0.0.1:0").unwrap();
+        let south_server = IllServer::start(Arc::clone(&south), "127.0.0.1:0").unwrap();
+        north
+            .lock()
+            .unwrap()
+            .add_ill_partner("SOUTH", south_server.addr(), 90)
+            .unwrap();
+        south
+            .lock()
+            .unwrap()
+            .add_ill_partner("NORTH", north_server.addr(), 90)
+            .unwrap();
+        Partners {
+            north,
+            south,
+            servers: (north_server, south_server),
+        }
+    }
+
+    fn borrow_state(p: &Partners, id: u32) -> BorrowState {
+        let south = p.south.lock().unwrap();
+        south.borrowing_request(id).unwrap().state.clone()
+    }
+
+    fn lend_state(p: &Partners, id: u32) -> LendState {
+        let north = p.north.lock().unwrap();
+        north.lending_request("SOUTH", id).unwrap().state.clone()
+    }
+
+    #[test]
+    fn test_full_loan_between_two_libraries() {
+        let p = partners();
+        let id = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "piranesi", "Susanna Clarke")
+            .unwrap();
+        assert_eq!(borrow_state(&p, id), BorrowState::Accepted);
+        assert_eq!(lend_state(&p, id), LendState::Accepted);
+
+        // Nothing has been shipped yet, so there is nothing to receive.
+        assert_eq!(
+            p.south.lock().unwrap().receive_ill(id),
+            Err(IllError::InvalidState {
+                request_id: id,
+                action: "received"
+            })
+        );
+
+        let loan_id = p.north.lock().unwrap().ship_ill("SOUTH", id).unwrap();
+        let due = p.north.lock().unwrap().get_loan(loan_id).unwrap().due_date;
+        assert_eq!(borrow_state(&p, id), BorrowState::Shipped { due });
+        assert!(!p.north.lock().unwrap().get_book(1).unwrap().is_available);
+
+        p.south.lock().unwrap().receive_ill(id).unwrap();
+        assert_eq!(lend_state(&p, id), LendState::Received { loan_id });
+        p.south.lock().unwrap().return_ill(id).unwrap();
+        assert_eq!(borrow_state(&p, id), BorrowState::Returned);
+        assert_eq!(lend_state(&p, id), LendState::Returned { loan_id });
+
+        p.north.lock().unwrap().check_in_ill("SOUTH", id).unwrap();
+        assert_eq!(lend_state(&p, id), LendState::Completed);
+        assert!(p.north.lock().unwrap().get_book(1).unwrap().is_available);
+    }
+
+    #[test]
+    fn test_unfilled_declined_and_cancelled_requests() {
+        let p = partners();
+        let missing = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "Jonathan Strange", "")
+            .unwrap();
+        assert!(matches!(
+            borrow_state(&p, missing),
+            BorrowState::Unfilled { .. }
+        ));
+
+        let declined = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "Piranesi", "")
+            .unwrap();
+        p.north
+            .lock()
+            .unwrap()
+            .decline_ill("SOUTH", declined, "damaged")
+            .unwrap();
+        assert_eq!(
+            borrow_state(&p, declined),
+            BorrowState::Unfilled {
+                reason: "damaged".to_string()
+            }
+        );
+
+        let cancelled = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "Piranesi", "")
+            .unwrap();
+        p.south
+            .lock()
+            .unwrap()
+            .cancel_ill(cancelled, "patron bought it")
+            .unwrap();
+        assert!(matches!(
+            lend_state(&p, cancelled),
+            LendState::Cancelled { .. }
+        ));
+        // A cancelled request can no longer be shipped.
+        assert!(matches!(
+            p.north.lock().unwrap().ship_ill("SOUTH", cancelled),
+            Err(IllError::InvalidState { .. })
+        ));
+    }
+
+    #[test]
+    fn test_messages_from_strangers_are_refused() {
+        let p = partners();
+        let addr = p.servers.0.addr();
+        let reply = send_ill(
+            addr,
+            &IllMessage::new(
+                "WEST",
+                1,
+                IllBody::Request {
+                    title: "Piranesi".to_string(),
+                    author: String::new(),
+                },
+            ),
+        )
+        .unwrap();
+        assert_eq!(reply.from, "NORTH");
+        assert!(matches!(reply.body, IllBody::Error { .. }));
+    }
+
+    #[test]
+    fn test_messages_from_another_host_are_refused() {
+        let p = partners();
+        let id = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "Piranesi", "")
+            .unwrap();
+        // A forged RETURNED that claims to come from SOUTH.
+        let forged = IllMessage::new("SOUTH", id, IllBody::Returned);
+        let peer: IpAddr = "192.0.2.7".parse().unwrap();
+        let reply = p.north.lock().unwrap().handle_ill(peer, forged);
+        assert!(matches!(reply.body, IllBody::Error { .. }));
+        assert_eq!(lend_state(&p, id), LendState::Accepted);
+    }
+
+    #[test]
+    fn test_failed_sends_use_up_the_id_and_leave_no_loan() {
+        let mut p = partners();
+        let id = p
+            .south
+            .lock()
+            .unwrap()
+            .request_ill("NORTH", 1, "Piranesi", "")
+            .unwrap();
+
+        p.servers.1.stop();
+        assert!(matches!(
+            p.north.lock().unwrap().ship_ill("SOUTH", id),
+            Err(IllError::Io(_))
+        ));
+        let north = p.north.lock().unwrap();
+        assert!(north.borrowing_history().is_empty());
+        assert!(north.get_book(1).unwrap().is_available);
+        drop(north);
+
+        p.servers.0.stop();
+        let mut south = p.south.lock().unwrap();
+        assert!(south.request_ill("NORTH", 1, "Piranesi", "").is_err());
+        assert_eq!(south.next_ill_id, id + 2);
+    }
+
+    #[test]
+    fn test_overlong_line_is_refused_and_closed() {
+        let p = partners();
+        let mut stream = TcpStream::connect(p.servers.0.addr()).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        // A full line's worth of bytes with no newline in sight.
+        stream
+            .write_all("x".repeat(MAX_LINE as usize).as_bytes())
+            .unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let reply = IllMessage::parse(&line).unwrap();
+        assert!(matches!(reply.body, IllBody::Error { .. }));
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+    }
+}
@@ -1,16 +1,84 @@
-use crate::models::{book::Book, user::User};
+use crate::models::{
+    acquisition::{Fund, Order, PurchaseSuggestion},
+    book::Book,
+    calendar::LibraryCalendar,
+    copy::{BookCopy, CopyStatus},
+    ill::{BorrowingRequest, LendingRequest},
+    loan::{Hold, Loan},
+    policy::{LoanPolicy, RetentionPolicy},
+    review::Review,
+    shelf::Shelf,
+    user::User,
+};
+use crate::services::{barcode, clock::Clock, ill::IllPartner, transactions::EditBatch};
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 pub struct Library {
-    books: HashMap<u32, Book>,
-    users: HashMap<u32, User>,
+    pub(crate) books: HashMap<u32, Book>,
+    pub(crate) copies: HashMap<u32, BookCopy>,
+    pub(crate) next_copy_id: u32,
+    pub(crate) users: HashMap<u32, User>,
+    pub(crate) loans: HashMap<u32, Loan>,
+    pub(crate) holds: Vec<Hold>,
+    pub(crate) policy: LoanPolicy,
+    pub(crate) retention: RetentionPolicy,
+    pub(crate) next_loan_id: u32,
+    pub(crate) clock: Clock,
+    pub(crate) calendar: LibraryCalendar,
+    pub(crate) shelves: Vec<Shelf>,
+    pub(crate) reviews: HashMap<u32, Review>,
+    pub(crate) next_review_id: u32,
+    pub(crate) suggestions: HashMap<u32, PurchaseSuggestion>,
+    pub(crate) next_suggestion_id: u32,
+    pub(crate) funds: HashMap<String, Fund>,
+    pub(crate) orders: HashMap<u32, Order>,
+    pub(crate) next_order_id: u32,
+    pub(crate) ill_code: String,
+    pub(crate) ill_partners: HashMap<String, IllPartner>,
+    pub(crate) borrowing_requests: HashMap<u32, BorrowingRequest>,
+    pub(crate) next_ill_id: u32,
+    pub(crate) lending_requests: HashMap<(String, u32), LendingRequest>,
+    pub(crate) undo_stack: Vec<EditBatch>,
+    pub(crate) redo_stack: Vec<EditBatch>,
+    // API bearer tokens and the users they were issued to.
+    pub(crate) api_tokens: HashMap<String, u32>,
 }
 
 impl Library {
     pub fn new() -> Self {
+        Library::with_policy(LoanPolicy::default())
+    }
+
+    pub fn with_policy(policy: LoanPolicy) -> Self {
         Library {
             books: HashMap::new(),
+            copies: HashMap::new(),
+            next_copy_id: 1,
             users: HashMap::new(),
+            loans: HashMap::new(),
+            holds: Vec::new(),
+            policy,
+            retention: RetentionPolicy::default(),
+            next_loan_id: 1,
+            clock: Clock::System,
+            calendar: LibraryCalendar::default(),
+            shelves: Vec::new(),
+            reviews: HashMap::new(),
+            next_review_id: 1,
+            suggestions: HashMap::new(),
+            next_suggestion_id: 1,
+            funds: HashMap::new(),
+            orders: HashMap::new(),
+            next_order_id: 1,
+            ill_code: "LOCAL".to_string(),
+            ill_partners: HashMap::new(),
+            borrowing_requests: HashMap::new(),
+            next_ill_id: 1,
+            lending_requests: HashMap::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            api_tokens: HashMap::new(),
         }
     }
 
@@ -22,7 +90,96 @@ impl Library {
         self.books.get(&id)
     }
 
+    // The id after the highest one in the catalogue.
+    pub fn next_book_id(&self) -> u32 {
+        self.books.keys().max().map_or(1, |id| id + 1)
+    }
+
+    // Registers a physical copy of a book and gives it a barcode.
+    pub fn add_copy(&mut self, book_id: u32, location: &str) -> Option<u32> {
+        let book = self.books.get_mut(&book_id)?;
+        book.is_available = true;
+        let id = self.next_copy_id;
+        self.next_copy_id += 1;
+        self.copies.insert(
+            id,
+            BookCopy {
+                id,
+                book_id,
+                barcode: barcode::copy_barcode(id),
+                location: location.to_string(),
+                status: CopyStatus::InCirculation,
+            },
+        );
+        Some(id)
+    }
+
+    pub fn get_copy(&self, id: u32) -> Option<&BookCopy> {
+        self.copies.get(&id)
+    }
+
+    pub fn find_copy_by_barcode(&self, barcode: &str) -> Option<&BookCopy> {
+        self.copies.values().find(|c| c.barcode == barcode)
+    }
+
+    // Copies of a book, ordered by id.
+    pub fn copies_of(&self, book_id: u32) -> Vec<&BookCopy> {
+        let mut copies: Vec<&BookCopy> = self
+            .copies
+            .values()
+            .filter(|c| c.book_id == book_id)
+            .collect();
+        copies.sort_by_key(|c| c.id);
+        copies
+    }
+
     pub fn register_user(&mut self, user: User) {
         self.users.insert(user.id, user);
     }
-}
\ No newline at end of file
+
+    pub fn get_user(&self, id: u32) -> Option<&User> {
+        self.users.get(&id)
+    }
+
+    pub fn get_user_mut(&mut self, id: u32) -> Option<&mut User> {
+        self.users.get_mut(&id)
+    }
+
+    pub fn policy(&self) -> &LoanPolicy {
+        &self.policy
+    }
+
+    pub fn set_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
+    }
+
+    pub fn retention(&self) -> &RetentionPolicy {
+        &self.retention
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    pub fn set_calendar(&mut self, calendar: LibraryCalendar) {
+        self.calendar = calendar;
+    }
+
+    pub fn calendar(&self) -> &LibraryCalendar {
+        &self.calendar
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+impl Default for Library {
+    fn default() -> Self {
+        Library::new()
+    }
+}
@@ -1,3 +1,19 @@
 // Services module declaration - groups all business logic
+pub mod acquisitions;
 pub mod auth;
-pub mod library;
\ No newline at end of file
+pub mod barcode;
+pub mod circulation;
+pub mod clock;
+pub mod collation;
+pub mod duplicates;
+pub mod graphql;
+pub mod ill;
+pub mod library;
+pub mod recommend;
+pub mod retention;
+pub mod reviews;
+pub mod scanner;
+pub mod shelves;
+pub mod simulation;
+pub mod stocktake;
+pub mod transactions;
@@ -0,0 +1,378 @@
+use crate::models::loan::BorrowEvent;
+use crate::services::library::Library;
+use std::collections::{HashMap, HashSet};
+
+// Why a book ended up in someone's recommendations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecommendationReason {
+    // Often borrowed by the same people as books the user has read.
+    CoBorrowed,
+    // Shares an author or subject with books the user has read.
+    Affinity,
+    // Widely borrowed; used when nothing more personal is known.
+    Popular,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub book_id: u32,
+    pub score: f64,
+    pub reason: RecommendationReason,
+}
+
+// Item-to-item recommender built from a borrowing history.
+#[derive(Debug, Clone, Default)]
+pub struct Recommender {
+    borrowed_by_user: HashMap<u32, HashSet<u32>>,
+    readers_per_book: HashMap<u32, usize>,
+    co_borrowed: HashMap<u32, HashMap<u32, usize>>,
+}
+
+impl Recommender {
+    pub fn from_history(history: &[BorrowEvent]) -> Self {
+        let mut borrowed_by_user: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for event in history {
+            borrowed_by_user
+                .entry(event.user_id)
+                .or_default()
+                .insert(event.book_id);
+        }
+
+        let mut readers_per_book: HashMap<u32, usize> = HashMap::new();
+        let mut co_borrowed: HashMap<u32, HashMap<u32, usize>> = HashMap::new();
+        for books in borrowed_by_user.values() {
+            for &a in books {
+                *readers_per_book.entry(a).or_default() += 1;
+                for &b in books {
+                    if a != b {
+                        *co_borrowed.entry(a).or_default().entry(b).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        Recommender {
+            borrowed_by_user,
+            readers_per_book,
+            co_borrowed,
+        }
+    }
+
+    // Cosine similarity between the sets of readers of two books.
+    pub fn similarity(&self, a: u32, b: u32) -> f64 {
+        let together = self
+            .co_borrowed
+            .get(&a)
+            .and_then(|others| others.get(&b))
+            .copied()
+            .unwrap_or(0);
+        if together == 0 {
+            return 0.0;
+        }
+        let readers_a = self.readers_per_book[&a] as f64;
+        let readers_b = self.readers_per_book[&b] as f64;
+        together as f64 / (readers_a * readers_b).sqrt()
+    }
+
+    pub fn borrowed_by(&self, user_id: u32) -> HashSet<u32> {
+        self.borrowed_by_user
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Up to `k` books the user has not borrowed yet, best first. Co-borrowing
+    // scores come first; author/subject affinity and then popularity fill
+    // the list for users with little or no history.
+    pub fn recommend(&self, library: &Library, user_id: u32, k: usize) -> Vec<Recommendation> {
+        let read = self.borrowed_by(user_id);
+        let mut picked: Vec<Recommendation> = Vec::new();
+        let mut seen: HashSet<u32> = read.clone();
+
+        let mut scores: HashMap<u32, f64> = HashMap::new();
+        for &book_id in &read {
+            if let Some(others) = self.co_borrowed.get(&book_id) {
+                for &other in others.keys() {
+                    if !read.contains(&other) {
+                        *scores.entry(other).or_default() += self.similarity(book_id, other);
+                    }
+                }
+            }
+        }
+        take_best(
+            scores,
+            RecommendationReason::CoBorrowed,
+            k,
+            &mut seen,
+            &mut picked,
+        );
+
+        if picked.len() < k {
+            let scores = self.affinity_scores(library, &read, &seen);
+            take_best(
+                scores,
+                RecommendationReason::Affinity,
+                k,
+                &mut seen,
+                &mut picked,
+            );
+        }
+
+        if picked.len() < k {
+            let scores = library
+                .books
+                .keys()
+                .filter(|id| !seen.contains(id))
+                .map(|&id| {
+                    let readers = self.readers_per_book.get(&id).copied().unwrap_or(0);
+                    (id, readers as f64)
+                })
+                .collect();
+            take_best(
+                scores,
+                RecommendationReason::Popular,
+                k,
+                &mut seen,
+                &mut picked,
+            );
+        }
+
+        picked
+    }
+
+    // One point per shared author and half a point per shared subject.
+    fn affinity_scores(
+        &self,
+        library: &Library,
+        read: &HashSet<u32>,
+        seen: &HashSet<u32>,
+    ) -> HashMap<u32, f64> {
+        let mut authors: HashMap<&str, usize> = HashMap::new();
+        let mut subjects: HashMap<&str, usize> = HashMap::new();
+        for book in read.iter().filter_map(|id| library.books.get(id)) {
+            for author in book.authors() {
+                *authors.entry(author).or_default() += 1;
+            }
+            for subject in &book.subjects {
+                *subjects.entry(subject.as_str()).or_default() += 1;
+            }
+        }
+
+        let mut scores = HashMap::new();
+        for (&id, book) in &library.books {
+            if seen.contains(&id) {
+                continue;
+            }
+            let author_score: usize = book.authors().iter().filter_map(|a| authors.get(a)).sum();
+            let subject_score: usize = book
+                .subjects
+                .iter()
+                .filter_map(|s| subjects.get(s.as_str()))
+                .sum();
+            let score = author_score as f64 + 0.5 * subject_score as f64;
+            if score > 0.0 {
+                scores.insert(id, score);
+            }
+        }
+        scores
+    }
+}
+
+// Moves the highest scoring books into `picked` until it holds `k` entries.
+// Ties are broken by book id so results are deterministic.
+fn take_best(
+    scores: HashMap<u32, f64>,
+    reason: RecommendationReason,
+    k: usize,
+    seen: &mut HashSet<u32>,
+    picked: &mut Vec<Recommendation>,
+) {
+    let mut ranked: Vec<(u32, f64)> = scores
+        .into_iter()
+        .filter(|(id, _)| !seen.contains(id))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (book_id, score) in ranked {
+        if picked.len() >= k {
+            break;
+        }
+        seen.insert(book_id);
+        picked.push(Recommendation {
+            book_id,
+            score,
+            reason,
+        });
+    }
+}
+
+impl Library {
+    pub fn recommend_for(&self, user_id: u32, k: usize) -> Vec<Recommendation> {
+        Recommender::from_history(&self.borrowing_history()).recommend(self, user_id, k)
+    }
+}
+
+// Hold-out evaluation: for every user with at least two borrowings, the most
+// recent one is hidden, the recommender is trained on the rest, and we check
+// whether the hidden book appears in the top `k`. Returns precision@k.
+pub fn precision_at_k(library: &Library, history: &[BorrowEvent], k: usize) -> f64 {
+    let mut latest: HashMap<u32, usize> = HashMap::new();
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for (index, event) in history.iter().enumerate() {
+        *counts.entry(event.user_id).or_default() += 1;
+        let entry = latest.entry(event.user_id).or_insert(index);
+        if event.borrowed > history[*entry].borrowed {
+            *entry = index;
+        }
+    }
+    let held_out: HashSet<usize> = latest
+        .into_values()
+        .filter(|&index| counts[&history[index].user_id] >= 2)
+        .collect();
+    if held_out.is_empty() || k == 0 {
+        return 0.0;
+    }
+
+    let training: Vec<BorrowEvent> = history
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !held_out.contains(index))
+        .map(|(_, event)| event.clone())
+        .collect();
+    let recommender = Recommender::from_history(&training);
+
+    let hits = held_out
+        .iter()
+        .map(|&index| &history[index])
+        .filter(|hidden| {
+            recommender
+                .recommend(library, hidden.user_id, k)
+                .iter()
+                .any(|r| r.book_id == hidden.book_id)
+        })
+        .count();
+    hits as f64 / (held_out.len() * k) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+    use crate::services::clock::Clock;
+    use chrono::{Duration, TimeZone, Utc};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const GENRES: [&str; 4] = ["Fantasy", "Mystery", "History", "Poetry"];
+    const BOOKS_PER_GENRE: u32 = 10;
+
+    // Users each favour one genre and mostly borrow from it, with the
+    // occasional book from elsewhere.
+    fn synthetic_library(seed: u64, users: u32, borrows_per_user: usize) -> Library {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut library = Library::new();
+        library.set_clock(Clock::Fixed(
+            Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(),
+        ));
+        for (g, genre) in GENRES.iter().enumerate() {
+            for i in 0..BOOKS_PER_GENRE {
+                let id = g as u32 * BOOKS_PER_GENRE + i + 1;
+                let book = Book::builder(id, &format!("{} {}", genre, i))
+                    .author(&format!("{} author {}", genre, i % 3))
+                    .subject(genre)
+                    .build();
+                library.add_book(book);
+            }
+        }
+        for user_id in 1..=users {
+            library.register_user(User::new(user_id, &format!("reader{}", user_id)));
+        }
+
+        for user_id in 1..=users {
+            let favourite = (user_id as usize) % GENRES.len();
+            let mut borrowed = HashSet::new();
+            while borrowed.len() < borrows_per_user {
+                let genre = if rng.random_bool(0.85) {
+                    favourite
+                } else {
+                    rng.random_range(0..GENRES.len())
+                };
+                // Earlier books in each genre are more popular.
+                let a = rng.random_range(0..BOOKS_PER_GENRE);
+                let b = rng.random_range(0..BOOKS_PER_GENRE);
+                let book_id = genre as u32 * BOOKS_PER_GENRE + a.min(b) + 1;
+                if borrowed.insert(book_id) {
+                    let loan_id = library.checkout(book_id, user_id).unwrap();
+                    library.clock_mut().advance(Duration::hours(1));
+                    library.return_book(loan_id).unwrap();
+                }
+            }
+        }
+        library
+    }
+
+    #[test]
+    fn test_history_records_returned_loans() {
+        let library = synthetic_library(1, 3, 4);
+        let history = library.borrowing_history();
+        assert_eq!(history.len(), 12);
+        assert!(history.windows(2).all(|w| w[0].borrowed <= w[1].borrowed));
+    }
+
+    #[test]
+    fn test_recommendations_exclude_borrowed_titles() {
+        let library = synthetic_library(7, 40, 6);
+        let recommender = Recommender::from_history(&library.borrowing_history());
+        let read = recommender.borrowed_by(1);
+
+        let picks = recommender.recommend(&library, 1, 5);
+        assert_eq!(picks.len(), 5);
+        assert!(picks.iter().all(|r| !read.contains(&r.book_id)));
+        assert_eq!(picks[0].reason, RecommendationReason::CoBorrowed);
+    }
+
+    #[test]
+    fn test_cold_start_falls_back_to_affinity_and_popularity() {
+        let mut library = synthetic_library(7, 20, 5);
+        library.add_book(
+            Book::builder(100, "Lonely Verses")
+                .author("Poetry author 0")
+                .subject("Poetry")
+                .build(),
+        );
+        library.register_user(User::new(99, "newcomer"));
+        let loan_id = library.checkout(100, 99).unwrap();
+        library.return_book(loan_id).unwrap();
+
+        // Nobody else read book 100, so there is nothing to co-borrow.
+        let picks = library.recommend_for(99, 3);
+        assert_eq!(picks.len(), 3);
+        assert!(picks
+            .iter()
+            .all(|r| r.reason == RecommendationReason::Affinity));
+        let first = library.get_book(picks[0].book_id).unwrap();
+        assert_eq!(first.authors(), vec!["Poetry author 0"]);
+
+        // A user with no history at all only gets popular titles.
+        library.register_user(User::new(98, "visitor"));
+        let picks = library.recommend_for(98, 3);
+        assert!(picks
+            .iter()
+            .all(|r| r.reason == RecommendationReason::Popular));
+    }
+
+    #[test]
+    fn test_precision_beats_random_guessing() {
+        let library = synthetic_library(42, 80, 8);
+        let history = library.borrowing_history();
+        let k = 5;
+
+        let precision = precision_at_k(&library, &history, k);
+        // Guessing 5 of the 32 unread titles would hit about 1 in 32 slots.
+        let random = 1.0 / (GENRES.len() as f64 * BOOKS_PER_GENRE as f64 - 8.0);
+        assert!(
+            precision > 2.0 * random,
+            "precision@{} was {:.3}",
+            k,
+            precision
+        );
+    }
+}
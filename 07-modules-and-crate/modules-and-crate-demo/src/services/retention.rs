@@ -0,0 +1,166 @@
+use crate::models::loan::Loan;
+use crate::services::library::Library;
+use chrono::{Datelike, Duration};
+use std::collections::{BTreeMap, HashMap};
+
+// Outcome of a purge run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    // Returned loans whose user link was removed by this run.
+    pub anonymised: usize,
+    // Loans past the retention period kept because the user opted in.
+    pub kept_by_request: usize,
+}
+
+// Counts that do not depend on who borrowed what, so they survive purges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoanStatistics {
+    pub total_loans: usize,
+    pub active_loans: usize,
+    pub loans_per_book: HashMap<u32, usize>,
+    // Keyed by (year, month) of checkout.
+    pub loans_per_month: BTreeMap<(i32, u32), usize>,
+}
+
+impl Library {
+    // Opts a user in or out of keeping their full loan history.
+    pub fn set_keep_history(&mut self, user_id: u32, keep: bool) -> bool {
+        match self.users.get_mut(&user_id) {
+            Some(user) => {
+                user.keep_history = keep;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The loans still linked to a user, oldest first.
+    pub fn loan_history_for(&self, user_id: u32) -> Vec<&Loan> {
+        let mut loans: Vec<&Loan> = self
+            .loans
+            .values()
+            .filter(|l| l.user_id == Some(user_id))
+            .collect();
+        loans.sort_by_key(|l| (l.checked_out, l.id));
+        loans
+    }
+
+    // Removes the user link from loans returned more than the retention
+    // period ago. Active loans and users who opted in are left untouched.
+    pub fn purge_loan_history(&mut self) -> PurgeReport {
+        let cutoff = self.now() - Duration::days(self.retention.anonymise_after_days);
+        let mut report = PurgeReport::default();
+
+        for loan in self.loans.values_mut() {
+            let (Some(user_id), Some(returned)) = (loan.user_id, loan.returned) else {
+                continue;
+            };
+            if returned > cutoff {
+                continue;
+            }
+            if self.users.get(&user_id).is_some_and(|u| u.keep_history) {
+                report.kept_by_request += 1;
+            } else {
+                loan.user_id = None;
+                report.anonymised += 1;
+            }
+        }
+        report
+    }
+
+    pub fn loan_statistics(&self) -> LoanStatistics {
+        let mut stats = LoanStatistics::default();
+        for loan in self.loans.values() {
+            stats.total_loans += 1;
+            if loan.is_active() {
+                stats.active_loans += 1;
+            }
+            *stats.loans_per_book.entry(loan.book_id).or_default() += 1;
+            let month = (loan.checked_out.year(), loan.checked_out.month());
+            *stats.loans_per_month.entry(month).or_default() += 1;
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, policy::RetentionPolicy, user::User};
+    use crate::services::clock::Clock;
+    use chrono::{TimeZone, Utc};
+
+    // Alice and Bob each borrow and return both books, then Alice borrows
+    // book 1 again and keeps it.
+    fn setup() -> Library {
+        let mut library = Library::new();
+        library.set_clock(Clock::Fixed(
+            Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap(),
+        ));
+        library.set_retention(RetentionPolicy {
+            anonymise_after_days: 14,
+        });
+        library.add_book(Book::new(1, "Middlemarch", "George Eliot"));
+        library.add_book(Book::new(2, "Silas Marner", "George Eliot"));
+        library.register_user(User::new(1, "alice"));
+        library.register_user(User::new(2, "bob"));
+
+        for user_id in [1, 2] {
+            for book_id in [1, 2] {
+                let loan_id = library.checkout(book_id, user_id).unwrap();
+                library.clock_mut().advance(Duration::days(3));
+                library.return_book(loan_id).unwrap();
+            }
+        }
+        library.checkout(1, 1).unwrap();
+        library
+    }
+
+    #[test]
+    fn test_purge_respects_retention_period() {
+        let mut library = setup();
+        // The last return happened just now, so nothing is old enough yet.
+        assert_eq!(library.purge_loan_history(), PurgeReport::default());
+
+        library.clock_mut().advance(Duration::days(10));
+        let report = library.purge_loan_history();
+        // Only Alice's two loans were returned more than 14 days ago.
+        assert_eq!(report.anonymised, 2);
+        assert_eq!(library.loan_history_for(1).len(), 1);
+        assert_eq!(library.loan_history_for(2).len(), 2);
+    }
+
+    #[test]
+    fn test_purge_keeps_opted_in_history_and_active_loans() {
+        let mut library = setup();
+        library.set_keep_history(2, true);
+        library.clock_mut().advance(Duration::days(60));
+
+        let report = library.purge_loan_history();
+        assert_eq!(report.anonymised, 2);
+        assert_eq!(report.kept_by_request, 2);
+        // Alice's current loan stays linked so it can be returned and fined.
+        assert_eq!(library.loan_history_for(1).len(), 1);
+        assert_eq!(library.active_loans_for(1).len(), 1);
+        assert_eq!(library.loan_history_for(2).len(), 2);
+        assert_eq!(library.borrowing_history().len(), 3);
+    }
+
+    #[test]
+    fn test_purge_keeps_statistics_intact() {
+        let mut library = setup();
+        let before = library.loan_statistics();
+        library.clock_mut().advance(Duration::days(60));
+        library.purge_loan_history();
+
+        assert_eq!(library.loan_statistics(), before);
+        assert_eq!(before.total_loans, 5);
+        assert_eq!(before.active_loans, 1);
+        assert_eq!(before.loans_per_book[&1], 3);
+        assert!(library
+            .loans
+            .values()
+            .filter(|l| !l.is_active())
+            .all(|l| l.user_id.is_none()));
+    }
+}
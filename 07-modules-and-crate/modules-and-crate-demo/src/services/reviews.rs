@@ -0,0 +1,293 @@
+use crate::models::review::{
+    RatingSummary, Review, ReviewFlag, ReviewStatus, MAX_RATING, MIN_RATING,
+};
+use crate::services::{auth::Auth, library::Library};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewError {
+    UserNotFound(u32),
+    BookNotFound(u32),
+    ReviewNotFound(u32),
+    InvalidRating(u8),
+    AlreadyReviewed { review_id: u32 },
+    AlreadyFlagged { review_id: u32, user_id: u32 },
+    NotAuthor { review_id: u32, user_id: u32 },
+    NotLibrarian(u32),
+}
+
+impl fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReviewError::UserNotFound(id) => write!(f, "user {} not found", id),
+            ReviewError::BookNotFound(id) => write!(f, "book {} not found", id),
+            ReviewError::ReviewNotFound(id) => write!(f, "review {} not found", id),
+            ReviewError::InvalidRating(rating) => write!(
+                f,
+                "rating {} is outside {}..={}",
+                rating, MIN_RATING, MAX_RATING
+            ),
+            ReviewError::AlreadyReviewed { review_id } => {
+                write!(f, "user already reviewed this book (review {})", review_id)
+            }
+            ReviewError::AlreadyFlagged { review_id, user_id } => {
+                write!(f, "user {} already flagged review {}", user_id, review_id)
+            }
+            ReviewError::NotAuthor { review_id, user_id } => {
+                write!(f, "user {} did not write review {}", user_id, review_id)
+            }
+            ReviewError::NotLibrarian(id) => write!(f, "user {} is not a librarian", id),
+        }
+    }
+}
+
+impl std::error::Error for ReviewError {}
+
+// What a librarian decides to do with a flagged review.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    // The review is fine: clear its flags and keep it published.
+    Dismiss,
+    Hide,
+    Remove,
+}
+
+impl Library {
+    // Each user may review a title once; use `edit_review` to change it.
+    pub fn add_review(
+        &mut self,
+        user_id: u32,
+        book_id: u32,
+        rating: u8,
+        text: &str,
+    ) -> Result<u32, ReviewError> {
+        if !self.users.contains_key(&user_id) {
+            return Err(ReviewError::UserNotFound(user_id));
+        }
+        if !self.books.contains_key(&book_id) {
+            return Err(ReviewError::BookNotFound(book_id));
+        }
+        check_rating(rating)?;
+        if let Some(existing) = self
+            .reviews
+            .values()
+            .find(|r| r.user_id == user_id && r.book_id == book_id)
+        {
+            return Err(ReviewError::AlreadyReviewed {
+                review_id: existing.id,
+            });
+        }
+
+        let id = self.next_review_id;
+        self.next_review_id += 1;
+        let written = self.now();
+        self.reviews.insert(
+            id,
+            Review {
+                id,
+                book_id,
+                user_id,
+                rating,
+                text: text.trim().to_string(),
+                written,
+                status: ReviewStatus::Published,
+                flags: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn edit_review(
+        &mut self,
+        user_id: u32,
+        review_id: u32,
+        rating: u8,
+        text: &str,
+    ) -> Result<(), ReviewError> {
+        check_rating(rating)?;
+        let written = self.now();
+        let review = self
+            .reviews
+            .get_mut(&review_id)
+            .ok_or(ReviewError::ReviewNotFound(review_id))?;
+        if review.user_id != user_id {
+            return Err(ReviewError::NotAuthor { review_id, user_id });
+        }
+        review.rating = rating;
+        review.text = text.trim().to_string();
+        review.written = written;
+        Ok(())
+    }
+
+    pub fn get_review(&self, review_id: u32) -> Option<&Review> {
+        self.reviews.get(&review_id)
+    }
+
+    // Published reviews of a book, newest first.
+    pub fn reviews_for(&self, book_id: u32) -> Vec<&Review> {
+        let mut reviews: Vec<&Review> = self
+            .reviews
+            .values()
+            .filter(|r| r.book_id == book_id && r.is_published())
+            .collect();
+        reviews.sort_by(|a, b| b.written.cmp(&a.written).then(b.id.cmp(&a.id)));
+        reviews
+    }
+
+    // Aggregate rating over published reviews; `None` if there are none.
+    pub fn rating_for(&self, book_id: u32) -> Option<RatingSummary> {
+        let ratings: Vec<u8> = self.reviews_for(book_id).iter().map(|r| r.rating).collect();
+        if ratings.is_empty() {
+            return None;
+        }
+        let total: u32 = ratings.iter().map(|&r| r as u32).sum();
+        Some(RatingSummary {
+            average: total as f64 / ratings.len() as f64,
+            count: ratings.len(),
+        })
+    }
+
+    pub fn flag_review(
+        &mut self,
+        reporter_id: u32,
+        review_id: u32,
+        reason: &str,
+    ) -> Result<(), ReviewError> {
+        if !self.users.contains_key(&reporter_id) {
+            return Err(ReviewError::UserNotFound(reporter_id));
+        }
+        let flagged = self.now();
+        let review = self
+            .reviews
+            .get_mut(&review_id)
+            .ok_or(ReviewError::ReviewNotFound(review_id))?;
+        if review.flags.iter().any(|f| f.reporter_id == reporter_id) {
+            return Err(ReviewError::AlreadyFlagged {
+                review_id,
+                user_id: reporter_id,
+            });
+        }
+        review.flags.push(ReviewFlag {
+            reporter_id,
+            reason: reason.trim().to_string(),
+            flagged,
+        });
+        Ok(())
+    }
+
+    // The moderation queue: reviews with outstanding flags, most flagged first.
+    pub fn flagged_reviews(&self, moderator_id: u32) -> Result<Vec<&Review>, ReviewError> {
+        self.require_librarian(moderator_id)?;
+        let mut reviews: Vec<&Review> = self
+            .reviews
+            .values()
+            .filter(|r| !r.flags.is_empty())
+            .collect();
+        reviews.sort_by(|a, b| b.flags.len().cmp(&a.flags.len()).then(a.id.cmp(&b.id)));
+        Ok(reviews)
+    }
+
+    pub fn moderate_review(
+        &mut self,
+        moderator_id: u32,
+        review_id: u32,
+        action: ModerationAction,
+    ) -> Result<(), ReviewError> {
+        self.require_librarian(moderator_id)?;
+        if !self.reviews.contains_key(&review_id) {
+            return Err(ReviewError::ReviewNotFound(review_id));
+        }
+        match action {
+            ModerationAction::Remove => {
+                self.reviews.remove(&review_id);
+            }
+            ModerationAction::Dismiss | ModerationAction::Hide => {
+                if let Some(review) = self.reviews.get_mut(&review_id) {
+                    review.flags.clear();
+                    review.status = if action == ModerationAction::Hide {
+                        ReviewStatus::Hidden
+                    } else {
+                        ReviewStatus::Published
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn require_librarian(&self, user_id: u32) -> Result<(), ReviewError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or(ReviewError::UserNotFound(user_id))?;
+        if Auth::check_librarian(user) {
+            Ok(())
+        } else {
+            Err(ReviewError::NotLibrarian(user_id))
+        }
+    }
+}
+
+fn check_rating(rating: u8) -> Result<(), ReviewError> {
+    if (MIN_RATING..=MAX_RATING).contains(&rating) {
+        Ok(())
+    } else {
+        Err(ReviewError::InvalidRating(rating))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+
+    fn setup() -> Library {
+        let mut library = Library::new();
+        library.add_book(Book::new(1, "Piranesi", "Susanna Clarke"));
+        library.register_user(User::new(1, "alice"));
+        library.register_user(User::new(2, "bob"));
+        let mut librarian = User::new(3, "carol");
+        librarian.is_librarian = true;
+        library.register_user(librarian);
+        library
+    }
+
+    #[test]
+    fn test_one_review_per_user_per_title() {
+        let mut library = setup();
+        let id = library.add_review(1, 1, 5, "Wonderful").unwrap();
+        assert_eq!(
+            library.add_review(1, 1, 3, "Changed my mind"),
+            Err(ReviewError::AlreadyReviewed { review_id: id })
+        );
+        library.edit_review(1, id, 4, "Still great").unwrap();
+        assert_eq!(library.get_review(id).unwrap().rating, 4);
+        assert_eq!(
+            library.add_review(2, 1, 6, ""),
+            Err(ReviewError::InvalidRating(6))
+        );
+    }
+
+    #[test]
+    fn test_rating_ignores_hidden_reviews() {
+        let mut library = setup();
+        library.add_review(1, 1, 5, "Wonderful").unwrap();
+        let spam = library.add_review(2, 1, 1, "Buy cheap watches").unwrap();
+        assert_eq!(library.rating_for(1).unwrap().average, 3.0);
+
+        library.flag_review(1, spam, "Spam").unwrap();
+        assert_eq!(
+            library.moderate_review(2, spam, ModerationAction::Hide),
+            Err(ReviewError::NotLibrarian(2))
+        );
+        assert_eq!(library.flagged_reviews(3).unwrap().len(), 1);
+        library
+            .moderate_review(3, spam, ModerationAction::Hide)
+            .unwrap();
+
+        let summary = library.rating_for(1).unwrap();
+        assert_eq!(summary.count, 1);
+        assert_eq!(summary.average, 5.0);
+        assert!(library.flagged_reviews(3).unwrap().is_empty());
+    }
+}
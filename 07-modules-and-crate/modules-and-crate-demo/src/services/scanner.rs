@@ -0,0 +1,227 @@
+use crate::services::{
+    barcode::{self, BarcodeError, Identifier},
+    circulation::CirculationError,
+    library::Library,
+};
+use std::fmt;
+
+// Keyboard-wedge scanners "type" the barcode followed by Enter, often with a
+// configurable prefix and suffix around it.
+#[derive(Debug, Clone, Default)]
+pub struct ScannerConfig {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl ScannerConfig {
+    // Strips the line ending, prefix and suffix from one line of scanner input.
+    pub fn strip<'a>(&self, line: &'a str) -> Result<&'a str, ScanError> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let code = line
+            .strip_prefix(self.prefix.as_str())
+            .and_then(|rest| rest.strip_suffix(self.suffix.as_str()))
+            .ok_or_else(|| ScanError::Malformed(line.to_string()))?
+            .trim();
+        if code.is_empty() {
+            return Err(ScanError::Malformed(line.to_string()));
+        }
+        Ok(code)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanError {
+    Malformed(String),
+    Barcode(BarcodeError),
+    UnknownCopy(String),
+    UnknownPatron(u32),
+    NoPatronSelected,
+    Circulation(CirculationError),
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Malformed(line) => write!(f, "unreadable scan {:?}", line),
+            ScanError::Barcode(e) => write!(f, "{}", e),
+            ScanError::UnknownCopy(code) => write!(f, "no copy has barcode {}", code),
+            ScanError::UnknownPatron(id) => write!(f, "no patron card {}", id),
+            ScanError::NoPatronSelected => write!(f, "scan a patron card first"),
+            ScanError::Circulation(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+impl From<BarcodeError> for ScanError {
+    fn from(e: BarcodeError) -> Self {
+        ScanError::Barcode(e)
+    }
+}
+
+impl From<CirculationError> for ScanError {
+    fn from(e: CirculationError) -> Self {
+        ScanError::Circulation(e)
+    }
+}
+
+// What a single scan did at the desk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeskEvent {
+    PatronSelected(u32),
+    CheckedOut { loan_id: u32, copy_id: u32 },
+    Returned { loan_id: u32, copy_id: u32 },
+}
+
+impl Library {
+    // Resolves a line of scanner input to a known copy or user.
+    pub fn identify(&self, line: &str, config: &ScannerConfig) -> Result<Identifier, ScanError> {
+        let code = config.strip(line)?;
+        match barcode::parse_identifier(code)? {
+            // Look up the full 13-digit number, since a 12-digit scan has
+            // its check digit added by `parse_identifier`.
+            Identifier::Copy(id) => self
+                .find_copy_by_barcode(&barcode::copy_barcode(id))
+                .map(|c| Identifier::Copy(c.id))
+                .ok_or_else(|| ScanError::UnknownCopy(code.to_string())),
+            Identifier::Patron(id) if self.users.contains_key(&id) => Ok(Identifier::Patron(id)),
+            Identifier::Patron(id) => Err(ScanError::UnknownPatron(id)),
+        }
+    }
+}
+
+// The circulation workflow at a desk: scanning a patron card selects who is
+// borrowing, and each copy scanned afterwards is lent to them. A copy scanned
+// while it is on loan is checked back in instead.
+#[derive(Debug, Clone, Default)]
+pub struct DeskSession {
+    pub config: ScannerConfig,
+    patron: Option<u32>,
+}
+
+impl DeskSession {
+    pub fn new(config: ScannerConfig) -> Self {
+        DeskSession {
+            config,
+            patron: None,
+        }
+    }
+
+    pub fn patron(&self) -> Option<u32> {
+        self.patron
+    }
+
+    // Ends the current patron's transaction.
+    pub fn finish(&mut self) {
+        self.patron = None;
+    }
+
+    pub fn scan(&mut self, library: &mut Library, line: &str) -> Result<DeskEvent, ScanError> {
+        match library.identify(line, &self.config)? {
+            Identifier::Patron(user_id) => {
+                self.patron = Some(user_id);
+                Ok(DeskEvent::PatronSelected(user_id))
+            }
+            Identifier::Copy(copy_id) => {
+                if let Some(loan_id) = library.active_loan_for_copy(copy_id).map(|l| l.id) {
+                    library.return_book(loan_id)?;
+                    return Ok(DeskEvent::Returned { loan_id, copy_id });
+                }
+                let user_id = self.patron.ok_or(ScanError::NoPatronSelected)?;
+                let loan_id = library.checkout_copy(copy_id, user_id)?;
+                Ok(DeskEvent::CheckedOut { loan_id, copy_id })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+    use crate::services::barcode::{copy_barcode, patron_barcode};
+
+    fn setup() -> Library {
+        let mut library = Library::new();
+        library.add_book(Book::new(1, "Beloved", "Toni Morrison"));
+        library.add_copy(1, "Main/Fiction").unwrap();
+        library.add_copy(1, "Main/Fiction").unwrap();
+        library.register_user(User::new(5, "alice"));
+        library
+    }
+
+    #[test]
+    fn test_strip_prefix_and_suffix() {
+        let config = ScannerConfig {
+            prefix: "]C0".to_string(),
+            suffix: "\t".to_string(),
+        };
+        assert_eq!(config.strip("]C02000000000018\t\r\n"), Ok("2000000000018"));
+        assert!(matches!(
+            config.strip("2000000000018\t"),
+            Err(ScanError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_desk_session_checks_out_and_returns() {
+        let mut library = setup();
+        let mut desk = DeskSession::default();
+
+        let copy_line = format!("{}\n", copy_barcode(2));
+        assert_eq!(
+            desk.scan(&mut library, &copy_line),
+            Err(ScanError::NoPatronSelected)
+        );
+
+        let card_line = format!("{}\n", patron_barcode(5));
+        assert_eq!(
+            desk.scan(&mut library, &card_line),
+            Ok(DeskEvent::PatronSelected(5))
+        );
+        let event = desk.scan(&mut library, &copy_line).unwrap();
+        let DeskEvent::CheckedOut { loan_id, copy_id } = event else {
+            panic!("expected a checkout, got {:?}", event);
+        };
+        assert_eq!(copy_id, 2);
+        assert_eq!(library.copy_on_shelf(1), Some(1));
+
+        desk.finish();
+        assert_eq!(
+            desk.scan(&mut library, &copy_line),
+            Ok(DeskEvent::Returned {
+                loan_id,
+                copy_id: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_identify_accepts_scan_without_check_digit() {
+        let library = setup();
+        let code = copy_barcode(2);
+        assert_eq!(
+            library.identify(&code[..12], &ScannerConfig::default()),
+            Ok(Identifier::Copy(2))
+        );
+    }
+
+    #[test]
+    fn test_identify_rejects_unknown_codes() {
+        let library = setup();
+        let config = ScannerConfig::default();
+        assert_eq!(
+            library.identify(&copy_barcode(99), &config),
+            Err(ScanError::UnknownCopy(copy_barcode(99)))
+        );
+        assert_eq!(
+            library.identify(&patron_barcode(6), &config),
+            Err(ScanError::UnknownPatron(6))
+        );
+        assert!(matches!(
+            library.identify("2000000000019", &config),
+            Err(ScanError::Barcode(BarcodeError::BadCheckDigit { .. }))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::models::shelf::Shelf;
+use crate::services::library::Library;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShelfError {
+    UserNotFound(u32),
+    BookNotFound(u32),
+    ShelfNotFound(String),
+    ShelfExists(String),
+    EmptyName,
+}
+
+impl fmt::Display for ShelfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShelfError::UserNotFound(id) => write!(f, "user {} not found", id),
+            ShelfError::BookNotFound(id) => write!(f, "book {} not found", id),
+            ShelfError::ShelfNotFound(name) => write!(f, "shelf '{}' not found", name),
+            ShelfError::ShelfExists(name) => write!(f, "shelf '{}' already exists", name),
+            ShelfError::EmptyName => write!(f, "shelf name cannot be empty"),
+        }
+    }
+}
+
+impl std::error::Error for ShelfError {}
+
+impl Library {
+    // Shelf names are unique per user, ignoring case.
+    pub fn create_shelf(&mut self, user_id: u32, name: &str) -> Result<(), ShelfError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ShelfError::EmptyName);
+        }
+        if !self.users.contains_key(&user_id) {
+            return Err(ShelfError::UserNotFound(user_id));
+        }
+        if self.find_shelf(user_id, name).is_some() {
+            return Err(ShelfError::ShelfExists(name.to_string()));
+        }
+        self.shelves.push(Shelf::new(user_id, name));
+        Ok(())
+    }
+
+    pub fn delete_shelf(&mut self, user_id: u32, name: &str) -> Result<Shelf, ShelfError> {
+        let index = self
+            .shelves
+            .iter()
+            .position(|s| s.owner_id == user_id && s.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| ShelfError::ShelfNotFound(name.to_string()))?;
+        Ok(self.shelves.remove(index))
+    }
+
+    // Adding a book that is already on the shelf is a no-op.
+    pub fn add_to_shelf(
+        &mut self,
+        user_id: u32,
+        name: &str,
+        book_id: u32,
+    ) -> Result<(), ShelfError> {
+        if !self.books.contains_key(&book_id) {
+            return Err(ShelfError::BookNotFound(book_id));
+        }
+        let shelf = self
+            .find_shelf_mut(user_id, name)
+            .ok_or_else(|| ShelfError::ShelfNotFound(name.to_string()))?;
+        if !shelf.contains(book_id) {
+            shelf.book_ids.push(book_id);
+        }
+        Ok(())
+    }
+
+    pub fn remove_from_shelf(
+        &mut self,
+        user_id: u32,
+        name: &str,
+        book_id: u32,
+    ) -> Result<bool, ShelfError> {
+        let shelf = self
+            .find_shelf_mut(user_id, name)
+            .ok_or_else(|| ShelfError::ShelfNotFound(name.to_string()))?;
+        let before = shelf.book_ids.len();
+        shelf.book_ids.retain(|&id| id != book_id);
+        Ok(shelf.book_ids.len() != before)
+    }
+
+    pub fn shelves_for(&self, user_id: u32) -> Vec<&Shelf> {
+        self.shelves
+            .iter()
+            .filter(|s| s.owner_id == user_id)
+            .collect()
+    }
+
+    pub fn find_shelf(&self, user_id: u32, name: &str) -> Option<&Shelf> {
+        self.shelves
+            .iter()
+            .find(|s| s.owner_id == user_id && s.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn find_shelf_mut(&mut self, user_id: u32, name: &str) -> Option<&mut Shelf> {
+        self.shelves
+            .iter_mut()
+            .find(|s| s.owner_id == user_id && s.name.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+
+    fn setup() -> Library {
+        let mut library = Library::new();
+        library.add_book(Book::new(1, "Piranesi", "Susanna Clarke"));
+        library.register_user(User::new(1, "alice"));
+        library.register_user(User::new(2, "bob"));
+        library
+    }
+
+    #[test]
+    fn test_shelves_are_per_user() {
+        let mut library = setup();
+        library.create_shelf(1, "To read").unwrap();
+        library.create_shelf(2, "To read").unwrap();
+        library.add_to_shelf(1, "to read", 1).unwrap();
+        library.add_to_shelf(1, "To read", 1).unwrap();
+
+        assert_eq!(library.find_shelf(1, "To read").unwrap().book_ids, vec![1]);
+        assert!(library
+            .find_shelf(2, "To read")
+            .unwrap()
+            .book_ids
+            .is_empty());
+        assert!(library.create_shelf(1, "TO READ").is_err());
+    }
+}
@@ -0,0 +1,586 @@
+use crate::models::{book::Book, policy::LoanPolicy, user::User};
+use crate::services::{clock::Clock, library::Library};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
+
+// The synthetic population and catalogue a simulation runs against.
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub days: u32,
+    pub patrons: u32,
+    pub titles: u32,
+    pub copies_per_title: u32,
+    // Average library visits per patron per week; visits arrive at random.
+    pub visits_per_week: f64,
+    // Average days a patron takes to read a book. Each patron gets their
+    // own speed, spread around this value.
+    pub reading_days: f64,
+    // Chance that a patron who finds a title out places a hold on it rather
+    // than walking away.
+    pub hold_probability: f64,
+    // Chance that a patron who finishes early keeps the book until it is due.
+    pub keep_until_due: f64,
+    // Chance that a patron still reading when a renewal is refused returns
+    // the book on time anyway instead of keeping it late.
+    pub prompt_return: f64,
+    // Days between a hold becoming ready and the patron collecting it.
+    pub pickup_days: u32,
+    // Patrons cancel holds they have waited on this long.
+    pub patience_days: Option<u32>,
+    // Most loans a patron has out at once.
+    pub max_loans: usize,
+    // Zipf exponent for title popularity; 0 makes every title equally popular.
+    pub popularity_skew: f64,
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            days: 90,
+            patrons: 150,
+            titles: 60,
+            copies_per_title: 2,
+            visits_per_week: 0.5,
+            reading_days: 14.0,
+            hold_probability: 0.6,
+            keep_until_due: 0.4,
+            prompt_return: 0.7,
+            pickup_days: 2,
+            patience_days: Some(60),
+            max_loans: 3,
+            popularity_skew: 1.0,
+            seed: 42,
+        }
+    }
+}
+
+// A config setting the simulator cannot run with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulationError {
+    // Rates and averages must be positive and finite.
+    NotPositive { setting: &'static str, value: f64 },
+    // Chances must lie between 0 and 1.
+    NotAProbability { setting: &'static str, value: f64 },
+    NotFinite { setting: &'static str, value: f64 },
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::NotPositive { setting, value } => {
+                write!(f, "{} must be a positive number, not {}", setting, value)
+            }
+            SimulationError::NotAProbability { setting, value } => {
+                write!(f, "{} must be between 0 and 1, not {}", setting, value)
+            }
+            SimulationError::NotFinite { setting, value } => {
+                write!(f, "{} must be a finite number, not {}", setting, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl SimulationConfig {
+    // Checks the settings that would otherwise stall or crash a run.
+    pub fn validate(&self) -> Result<(), SimulationError> {
+        for (setting, value) in [
+            ("visits_per_week", self.visits_per_week),
+            ("reading_days", self.reading_days),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(SimulationError::NotPositive { setting, value });
+            }
+        }
+        for (setting, value) in [
+            ("hold_probability", self.hold_probability),
+            ("keep_until_due", self.keep_until_due),
+            ("prompt_return", self.prompt_return),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(SimulationError::NotAProbability { setting, value });
+            }
+        }
+        if !self.popularity_skew.is_finite() {
+            return Err(SimulationError::NotFinite {
+                setting: "popularity_skew",
+                value: self.popularity_skew,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailySnapshot {
+    pub day: u32,
+    pub loans_out: usize,
+    pub holds_waiting: usize,
+    // Share of titles with at least one copy on the shelf.
+    pub titles_available: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    pub policy: LoanPolicy,
+    // Visits on which a patron asked for a title.
+    pub requests: usize,
+    pub immediate_checkouts: usize,
+    pub holds_placed: usize,
+    pub holds_filled: usize,
+    pub holds_abandoned: usize,
+    pub walkaways: usize,
+    pub renewals: usize,
+    pub late_returns: usize,
+    // Days from placing a hold to collecting the book.
+    pub average_wait_days: f64,
+    pub max_wait_days: f64,
+    // Holds waiting across the catalogue, averaged over days.
+    pub average_queue_length: f64,
+    // Longest queue seen for a single title.
+    pub max_queue_length: usize,
+    // Share of requests met from the shelf straight away.
+    pub availability: f64,
+    pub daily: Vec<DailySnapshot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Visit { patron: u32 },
+    PickUp { patron: u32, book: u32 },
+    DueDate { loan: u32 },
+    Return { loan: u32 },
+    GiveUp { patron: u32, book: u32 },
+    Snapshot { day: u32 },
+}
+
+struct Patron {
+    visits_per_day: f64,
+    reading_days: f64,
+}
+
+struct Simulator<'a> {
+    config: &'a SimulationConfig,
+    library: Library,
+    rng: StdRng,
+    queue: BinaryHeap<Reverse<(DateTime<Utc>, u64, Event)>>,
+    next_seq: u64,
+    patrons: HashMap<u32, Patron>,
+    // Cumulative popularity weights, indexed by title.
+    popularity: Vec<f64>,
+    // When each active loan's reader finishes the book.
+    finishes: HashMap<u32, DateTime<Utc>>,
+    // Titles whose first hold has been told a copy is ready.
+    notified: HashSet<u32>,
+    waits: Vec<f64>,
+    report: SimulationReport,
+}
+
+// Runs one simulation under `policy`. The same config and seed always give
+// the same report.
+pub fn simulate(
+    policy: LoanPolicy,
+    config: &SimulationConfig,
+) -> Result<SimulationReport, SimulationError> {
+    config.validate()?;
+    Ok(Simulator::new(policy, config).run())
+}
+
+// Runs the same synthetic population under each policy.
+pub fn compare_policies(
+    policies: &[LoanPolicy],
+    config: &SimulationConfig,
+) -> Result<Vec<SimulationReport>, SimulationError> {
+    policies
+        .iter()
+        .map(|policy| simulate(policy.clone(), config))
+        .collect()
+}
+
+impl<'a> Simulator<'a> {
+    fn new(policy: LoanPolicy, config: &'a SimulationConfig) -> Self {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut library = Library::with_policy(policy.clone());
+        library.set_clock(Clock::Fixed(start));
+
+        for id in 1..=config.titles {
+            library.add_book(Book::new(id, &format!("Title {}", id), "Synthetic"));
+            for _ in 0..config.copies_per_title {
+                library.add_copy(id, "Main");
+            }
+        }
+        let mut patrons = HashMap::new();
+        for id in 1..=config.patrons {
+            library.register_user(User::new(id, &format!("patron{}", id)));
+            patrons.insert(
+                id,
+                Patron {
+                    visits_per_day: config.visits_per_week / 7.0 * rng.random_range(0.5..1.5),
+                    reading_days: config.reading_days * rng.random_range(0.5..1.5),
+                },
+            );
+        }
+
+        let mut total = 0.0;
+        let popularity = (1..=config.titles)
+            .map(|rank| {
+                total += 1.0 / (rank as f64).powf(config.popularity_skew);
+                total
+            })
+            .collect();
+
+        let mut simulator = Simulator {
+            config,
+            library,
+            rng,
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            patrons,
+            popularity,
+            finishes: HashMap::new(),
+            notified: HashSet::new(),
+            waits: Vec::new(),
+            report: SimulationReport {
+                policy,
+                requests: 0,
+                immediate_checkouts: 0,
+                holds_placed: 0,
+                holds_filled: 0,
+                holds_abandoned: 0,
+                walkaways: 0,
+                renewals: 0,
+                late_returns: 0,
+                average_wait_days: 0.0,
+                max_wait_days: 0.0,
+                average_queue_length: 0.0,
+                max_queue_length: 0,
+                availability: 0.0,
+                daily: Vec::new(),
+            },
+        };
+        for patron in 1..=config.patrons {
+            simulator.schedule_visit(patron, start);
+        }
+        for day in 0..config.days {
+            let end_of_day = start + Duration::days(day as i64) + Duration::hours(12);
+            simulator.push(end_of_day, Event::Snapshot { day });
+        }
+        simulator
+    }
+
+    fn run(mut self) -> SimulationReport {
+        let end = self.library.now() + Duration::days(self.config.days as i64);
+        while let Some(Reverse((time, _, event))) = self.queue.pop() {
+            if time >= end {
+                break;
+            }
+            self.library.set_clock(Clock::Fixed(time));
+            self.handle(event);
+        }
+
+        let report = &mut self.report;
+        if !self.waits.is_empty() {
+            report.average_wait_days = self.waits.iter().sum::<f64>() / self.waits.len() as f64;
+            report.max_wait_days = self.waits.iter().cloned().fold(0.0, f64::max);
+        }
+        if !report.daily.is_empty() {
+            let total: usize = report.daily.iter().map(|d| d.holds_waiting).sum();
+            report.average_queue_length = total as f64 / report.daily.len() as f64;
+        }
+        if report.requests > 0 {
+            report.availability = report.immediate_checkouts as f64 / report.requests as f64;
+        }
+        self.report
+    }
+
+    fn push(&mut self, time: DateTime<Utc>, event: Event) {
+        self.queue.push(Reverse((time, self.next_seq, event)));
+        self.next_seq += 1;
+    }
+
+    fn after_days(&self, days: f64) -> DateTime<Utc> {
+        self.library.now() + Duration::seconds((days * 86_400.0) as i64)
+    }
+
+    fn schedule_visit(&mut self, patron: u32, from: DateTime<Utc>) {
+        let rate = self.patrons[&patron].visits_per_day;
+        // Exponential gaps between visits make arrivals a Poisson process.
+        let gap = -(1.0 - self.rng.random::<f64>()).ln() / rate;
+        let time = from + Duration::seconds((gap * 86_400.0) as i64);
+        self.push(time, Event::Visit { patron });
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Visit { patron } => self.visit(patron),
+            Event::PickUp { patron, book } => self.pick_up(patron, book),
+            Event::DueDate { loan } => self.due_date(loan),
+            Event::Return { loan } => self.give_back(loan),
+            Event::GiveUp { patron, book } => {
+                if self.is_notified_holder(patron, book) {
+                    return;
+                }
+                if self.library.cancel_hold(book, patron) {
+                    self.report.holds_abandoned += 1;
+                }
+            }
+            Event::Snapshot { day } => self.snapshot(day),
+        }
+    }
+
+    fn visit(&mut self, patron: u32) {
+        let now = self.library.now();
+        self.schedule_visit(patron, now);
+
+        let book = self.pick_title();
+        let active = self.library.active_loans_for(patron);
+        let has_it = active.iter().any(|l| l.book_id == book)
+            || self
+                .library
+                .holds_for_book(book)
+                .iter()
+                .any(|h| h.user_id == patron);
+        if has_it || active.len() >= self.config.max_loans {
+            return;
+        }
+
+        self.report.requests += 1;
+        if self.lend(patron, book) {
+            self.report.immediate_checkouts += 1;
+        } else if self.rng.random_bool(self.config.hold_probability)
+            && self.library.place_hold(book, patron).is_ok()
+        {
+            self.report.holds_placed += 1;
+            if let Some(patience) = self.config.patience_days {
+                let time = self.after_days(patience as f64);
+                self.push(time, Event::GiveUp { patron, book });
+            }
+            self.notify_next_holder(book);
+        } else {
+            self.report.walkaways += 1;
+        }
+    }
+
+    // Checks a title out and schedules the reader finishing it.
+    fn lend(&mut self, patron: u32, book: u32) -> bool {
+        let Ok(loan_id) = self.library.checkout(book, patron) else {
+            return false;
+        };
+        let speed = self.patrons[&patron].reading_days;
+        let reading = speed * self.rng.random_range(0.5..1.5);
+        let finish = self.after_days(reading);
+        let due = self.library.loans[&loan_id].due_date;
+        let keeps = finish < due && self.rng.random_bool(self.config.keep_until_due);
+        let returned = if keeps {
+            due - Duration::hours(1)
+        } else {
+            finish
+        };
+        self.finishes.insert(loan_id, finish);
+        self.push(returned, Event::Return { loan: loan_id });
+        self.push(due, Event::DueDate { loan: loan_id });
+        true
+    }
+
+    fn pick_up(&mut self, patron: u32, book: u32) {
+        self.notified.remove(&book);
+        let placed = self
+            .library
+            .holds_for_book(book)
+            .iter()
+            .find(|h| h.user_id == patron)
+            .map(|h| h.placed);
+        if let Some(placed) = placed {
+            if self.lend(patron, book) {
+                let waited = self.library.now() - placed;
+                self.waits.push(waited.num_seconds() as f64 / 86_400.0);
+                self.report.holds_filled += 1;
+            }
+        }
+        self.notify_next_holder(book);
+    }
+
+    // Readers who have not finished by the due date try to renew. If that is
+    // refused, most bring the book back unfinished.
+    fn due_date(&mut self, loan_id: u32) {
+        let still_reading = self.library.loans[&loan_id].is_active()
+            && self.finishes[&loan_id] > self.library.now();
+        if !still_reading {
+            return;
+        }
+        if let Ok(due) = self.library.renew(loan_id) {
+            self.report.renewals += 1;
+            self.push(due, Event::DueDate { loan: loan_id });
+        } else if self.rng.random_bool(self.config.prompt_return) {
+            self.give_back(loan_id);
+        }
+    }
+
+    // Returns a loan; the scheduled return of a loan given back early is a
+    // no-op.
+    fn give_back(&mut self, loan_id: u32) {
+        let loan = &self.library.loans[&loan_id];
+        let (book, late) = (loan.book_id, self.library.now() > loan.due_date);
+        if self.library.return_book(loan_id).is_ok() {
+            self.finishes.remove(&loan_id);
+            if late {
+                self.report.late_returns += 1;
+            }
+            self.notify_next_holder(book);
+        }
+    }
+
+    // Tells the first patron in the queue when a copy is on the shelf.
+    fn notify_next_holder(&mut self, book: u32) {
+        if self.notified.contains(&book) || self.library.copy_on_shelf(book).is_none() {
+            return;
+        }
+        if let Some(patron) = self.library.holds_for_book(book).first().map(|h| h.user_id) {
+            self.notified.insert(book);
+            let time = self.after_days(self.config.pickup_days as f64);
+            self.push(time, Event::PickUp { patron, book });
+        }
+    }
+
+    fn is_notified_holder(&self, patron: u32, book: u32) -> bool {
+        self.notified.contains(&book)
+            && self
+                .library
+                .holds_for_book(book)
+                .first()
+                .is_some_and(|h| h.user_id == patron)
+    }
+
+    fn pick_title(&mut self) -> u32 {
+        let total = *self.popularity.last().unwrap_or(&1.0);
+        let target = self.rng.random::<f64>() * total;
+        let index = self.popularity.partition_point(|&w| w < target);
+        (index as u32 + 1).min(self.config.titles)
+    }
+
+    fn snapshot(&mut self, day: u32) {
+        let mut queues: HashMap<u32, usize> = HashMap::new();
+        for hold in &self.library.holds {
+            *queues.entry(hold.book_id).or_default() += 1;
+        }
+        let longest = queues.values().copied().max().unwrap_or(0);
+        self.report.max_queue_length = self.report.max_queue_length.max(longest);
+
+        let available = (1..=self.config.titles)
+            .filter(|&id| self.library.copy_on_shelf(id).is_some())
+            .count();
+        self.report.daily.push(DailySnapshot {
+            day,
+            loans_out: self
+                .library
+                .loans
+                .values()
+                .filter(|l| l.is_active())
+                .count(),
+            holds_waiting: self.library.holds.len(),
+            titles_available: available as f64 / self.config.titles.max(1) as f64,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy() -> SimulationConfig {
+        SimulationConfig {
+            days: 120,
+            patrons: 150,
+            titles: 20,
+            copies_per_title: 1,
+            visits_per_week: 1.5,
+            ..SimulationConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible_and_consistent() {
+        let config = busy();
+        let a = simulate(LoanPolicy::default(), &config).unwrap();
+        let b = simulate(LoanPolicy::default(), &config).unwrap();
+        assert_eq!(a.requests, b.requests);
+        assert_eq!(a.holds_filled, b.holds_filled);
+        assert_eq!(a.average_wait_days, b.average_wait_days);
+
+        assert!(a.requests > 0);
+        assert_eq!(
+            a.requests,
+            a.immediate_checkouts + a.holds_placed + a.walkaways
+        );
+        assert!(a.holds_filled + a.holds_abandoned <= a.holds_placed);
+        assert_eq!(a.daily.len(), config.days as usize);
+        assert!((0.0..=1.0).contains(&a.availability));
+    }
+
+    #[test]
+    fn test_shorter_loans_shorten_queues() {
+        let config = busy();
+        let short = LoanPolicy {
+            loan_days: 7,
+            renewal_days: 7,
+            max_renewals: 0,
+            ..LoanPolicy::default()
+        };
+        let long = LoanPolicy {
+            loan_days: 42,
+            max_renewals: 3,
+            ..LoanPolicy::default()
+        };
+        let reports = compare_policies(&[short, long], &config).unwrap();
+        let (short, long) = (&reports[0], &reports[1]);
+        assert!(short.late_returns > long.late_returns);
+        assert!(short.average_queue_length < long.average_queue_length);
+        assert!(short.availability > long.availability);
+    }
+
+    #[test]
+    fn test_no_holds_means_no_waiting() {
+        let config = SimulationConfig {
+            hold_probability: 0.0,
+            ..busy()
+        };
+        let report = simulate(LoanPolicy::default(), &config).unwrap();
+        assert_eq!(report.holds_placed, 0);
+        assert_eq!(report.max_queue_length, 0);
+        assert_eq!(report.average_wait_days, 0.0);
+        assert!(report.walkaways > 0);
+    }
+
+    #[test]
+    fn test_invalid_config_is_refused_before_running() {
+        let no_visits = SimulationConfig {
+            visits_per_week: 0.0,
+            ..busy()
+        };
+        assert_eq!(
+            simulate(LoanPolicy::default(), &no_visits).unwrap_err(),
+            SimulationError::NotPositive {
+                setting: "visits_per_week",
+                value: 0.0
+            }
+        );
+        let eager = SimulationConfig {
+            hold_probability: 1.5,
+            ..busy()
+        };
+        assert_eq!(
+            eager.validate(),
+            Err(SimulationError::NotAProbability {
+                setting: "hold_probability",
+                value: 1.5
+            })
+        );
+        assert!(SimulationConfig::default().validate().is_ok());
+    }
+}
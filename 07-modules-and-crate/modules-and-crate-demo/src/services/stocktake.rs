@@ -0,0 +1,305 @@
+use crate::models::copy::CopyStatus;
+use crate::services::{
+    library::Library,
+    scanner::{ScanError, ScannerConfig},
+};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+
+// One pass over the shelves of a location, collecting every barcode scanned.
+#[derive(Debug, Clone)]
+pub struct StocktakeSession {
+    pub location: String,
+    pub started: DateTime<Utc>,
+    scanned: BTreeSet<String>,
+}
+
+impl StocktakeSession {
+    // Scanning the same copy twice is harmless.
+    pub fn record(&mut self, barcode: &str) {
+        let barcode = barcode.trim();
+        if !barcode.is_empty() {
+            self.scanned.insert(barcode.to_string());
+        }
+    }
+
+    pub fn record_line(&mut self, line: &str, config: &ScannerConfig) -> Result<(), ScanError> {
+        let barcode = config.strip(line)?;
+        self.record(barcode);
+        Ok(())
+    }
+
+    pub fn scanned_count(&self) -> usize {
+        self.scanned.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MisplacedCopy {
+    pub copy_id: u32,
+    pub recorded_location: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyOnLoan {
+    pub copy_id: u32,
+    pub loan_id: u32,
+}
+
+// Differences between the shelves and the catalogue. All lists are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconciliationReport {
+    pub location: String,
+    // Copies that belong here and are not on loan, but were not scanned.
+    pub missing: Vec<u32>,
+    // Copies scanned here that the catalogue places somewhere else.
+    pub wrong_location: Vec<MisplacedCopy>,
+    // Copies scanned on the shelf although a loan is still open for them.
+    pub on_loan: Vec<CopyOnLoan>,
+    // Copies scanned that had been marked missing before.
+    pub found: Vec<u32>,
+    pub unknown_barcodes: Vec<String>,
+}
+
+impl ReconciliationReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.wrong_location.is_empty()
+            && self.on_loan.is_empty()
+            && self.found.is_empty()
+            && self.unknown_barcodes.is_empty()
+    }
+}
+
+// Which corrections to make when applying a reconciliation report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorrectionOptions {
+    pub mark_missing: bool,
+    // Record misplaced copies at the stocktake location instead of re-shelving them.
+    pub relocate: bool,
+    // Close loans for copies that are evidently back on the shelf.
+    pub check_in: bool,
+}
+
+impl Default for CorrectionOptions {
+    fn default() -> Self {
+        CorrectionOptions {
+            mark_missing: true,
+            relocate: false,
+            check_in: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CorrectionSummary {
+    pub marked_missing: usize,
+    pub marked_found: usize,
+    pub relocated: usize,
+    pub checked_in: usize,
+}
+
+impl Library {
+    pub fn start_stocktake(&self, location: &str) -> StocktakeSession {
+        StocktakeSession {
+            location: location.to_string(),
+            started: self.now(),
+            scanned: BTreeSet::new(),
+        }
+    }
+
+    pub fn reconcile(&self, session: &StocktakeSession) -> ReconciliationReport {
+        let mut report = ReconciliationReport {
+            location: session.location.clone(),
+            ..ReconciliationReport::default()
+        };
+        let mut seen = BTreeSet::new();
+
+        for barcode in &session.scanned {
+            let Some(copy) = self.find_copy_by_barcode(barcode) else {
+                report.unknown_barcodes.push(barcode.clone());
+                continue;
+            };
+            seen.insert(copy.id);
+            if !copy.is_in(&session.location) {
+                report.wrong_location.push(MisplacedCopy {
+                    copy_id: copy.id,
+                    recorded_location: copy.location.clone(),
+                });
+            }
+            if let Some(loan) = self.active_loan_for_copy(copy.id) {
+                report.on_loan.push(CopyOnLoan {
+                    copy_id: copy.id,
+                    loan_id: loan.id,
+                });
+            }
+            if copy.status == CopyStatus::Missing {
+                report.found.push(copy.id);
+            }
+        }
+
+        report.missing = self
+            .copies
+            .values()
+            .filter(|c| c.is_in(&session.location))
+            .filter(|c| c.status == CopyStatus::InCirculation)
+            .filter(|c| !seen.contains(&c.id) && !self.is_copy_on_loan(c.id))
+            .map(|c| c.id)
+            .collect();
+        report.missing.sort_unstable();
+        report.wrong_location.sort_by_key(|m| m.copy_id);
+        report.on_loan.sort_by_key(|l| l.copy_id);
+        report.found.sort_unstable();
+        report
+    }
+
+    // Brings the catalogue in line with a reconciliation report. Copies that
+    // were found again always go back into circulation.
+    pub fn apply_reconciliation(
+        &mut self,
+        report: &ReconciliationReport,
+        options: CorrectionOptions,
+    ) -> CorrectionSummary {
+        let mut summary = CorrectionSummary::default();
+
+        for &copy_id in &report.found {
+            if let Some(copy) = self.copies.get_mut(&copy_id) {
+                copy.status = CopyStatus::InCirculation;
+                summary.marked_found += 1;
+            }
+        }
+        if options.mark_missing {
+            for &copy_id in &report.missing {
+                if let Some(copy) = self.copies.get_mut(&copy_id) {
+                    copy.status = CopyStatus::Missing;
+                    summary.marked_missing += 1;
+                }
+            }
+        }
+        if options.relocate {
+            for misplaced in &report.wrong_location {
+                if let Some(copy) = self.copies.get_mut(&misplaced.copy_id) {
+                    copy.location = report.location.clone();
+                    summary.relocated += 1;
+                }
+            }
+        }
+        if options.check_in {
+            for on_loan in &report.on_loan {
+                if self.return_book(on_loan.loan_id).is_ok() {
+                    summary.checked_in += 1;
+                }
+            }
+        }
+
+        let book_ids: BTreeSet<u32> = self.copies.values().map(|c| c.book_id).collect();
+        for book_id in book_ids {
+            let available = self.copy_on_shelf(book_id).is_some();
+            if let Some(book) = self.books.get_mut(&book_id) {
+                book.is_available = available;
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{book::Book, user::User};
+
+    // Copies 1-3 belong in Main/Fiction, copy 4 in Main/History.
+    fn setup() -> Library {
+        let mut library = Library::new();
+        library.add_book(Book::new(1, "Emma", "Jane Austen"));
+        library.add_book(Book::new(2, "SPQR", "Mary Beard"));
+        for _ in 0..3 {
+            library.add_copy(1, "Main/Fiction").unwrap();
+        }
+        library.add_copy(2, "Main/History").unwrap();
+        library.register_user(User::new(1, "alice"));
+        library
+    }
+
+    fn barcode_of(library: &Library, copy_id: u32) -> String {
+        library.get_copy(copy_id).unwrap().barcode.clone()
+    }
+
+    #[test]
+    fn test_reconciliation_report() {
+        let mut library = setup();
+        let loan_id = library.checkout_copy(2, 1).unwrap();
+        library.checkout_copy(3, 1).unwrap();
+
+        let mut session = library.start_stocktake("Main/Fiction");
+        session.record(&barcode_of(&library, 2));
+        session.record(&barcode_of(&library, 4));
+        session.record(&barcode_of(&library, 4));
+        session.record("2000000009993");
+
+        let report = library.reconcile(&session);
+        // Copy 1 is absent; copy 3 is on loan and so expected to be absent.
+        assert_eq!(report.missing, vec![1]);
+        assert_eq!(
+            report.wrong_location,
+            vec![MisplacedCopy {
+                copy_id: 4,
+                recorded_location: "Main/History".to_string(),
+            }]
+        );
+        assert_eq!(
+            report.on_loan,
+            vec![CopyOnLoan {
+                copy_id: 2,
+                loan_id
+            }]
+        );
+        assert_eq!(report.unknown_barcodes, vec!["2000000009993".to_string()]);
+        assert!(report.found.is_empty());
+    }
+
+    #[test]
+    fn test_apply_corrections() {
+        let mut library = setup();
+        let loan_id = library.checkout_copy(2, 1).unwrap();
+
+        let mut session = library.start_stocktake("Main/Fiction");
+        session.record(&barcode_of(&library, 2));
+        session.record(&barcode_of(&library, 4));
+        let report = library.reconcile(&session);
+
+        let options = CorrectionOptions {
+            relocate: true,
+            ..CorrectionOptions::default()
+        };
+        let summary = library.apply_reconciliation(&report, options);
+        assert_eq!(
+            summary,
+            CorrectionSummary {
+                marked_missing: 2,
+                marked_found: 0,
+                relocated: 1,
+                checked_in: 1,
+            }
+        );
+        assert_eq!(library.get_copy(1).unwrap().status, CopyStatus::Missing);
+        assert_eq!(library.get_copy(4).unwrap().location, "Main/Fiction");
+        assert!(!library.get_loan(loan_id).unwrap().is_active());
+        // Copy 2 is the only one of book 1 still on the shelf.
+        assert_eq!(library.copy_on_shelf(1), Some(2));
+
+        // A later stocktake that finds copy 1 puts it back into circulation.
+        let mut session = library.start_stocktake("Main");
+        for copy_id in 1..=4 {
+            session.record(&barcode_of(&library, copy_id));
+        }
+        let report = library.reconcile(&session);
+        assert_eq!(report.found, vec![1, 3]);
+        library.apply_reconciliation(&report, CorrectionOptions::default());
+        assert_eq!(
+            library.get_copy(1).unwrap().status,
+            CopyStatus::InCirculation
+        );
+        assert!(library.reconcile(&session).is_clean());
+    }
+}
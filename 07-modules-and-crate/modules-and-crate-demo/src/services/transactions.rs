@@ -0,0 +1,462 @@
+use crate::models::{
+    book::Book,
+    copy::{BookCopy, CopyStatus},
+};
+use crate::services::{auth::Auth, library::Library};
+use std::fmt;
+
+// How many batches `undo` can go back.
+pub const HISTORY_LIMIT: usize = 100;
+
+// A single change to the catalogue or to a patron record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    AddBook(Book),
+    // Replaces the record with the same id.
+    UpdateBook(Book),
+    // Only books with no copies, loans or holds can be removed.
+    RemoveBook(u32),
+    AddCopy { book_id: u32, location: String },
+    RemoveCopy(u32),
+    // Puts back a copy record exactly as it was, barcode included.
+    RestoreCopy(BookCopy),
+    MoveCopy { copy_id: u32, location: String },
+    SetCopyStatus { copy_id: u32, status: CopyStatus },
+    SetUserBlocked { user_id: u32, blocked: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    UserNotFound(u32),
+    NotLibrarian(u32),
+    BookExists(u32),
+    BookNotFound(u32),
+    BookInUse(u32),
+    CopyNotFound(u32),
+    CopyExists(u32),
+    CopyOnLoan(u32),
+    // The edit at `index` in a batch failed; nothing in the batch was applied.
+    Batch { index: usize, error: Box<EditError> },
+    NothingToUndo,
+    NothingToRedo,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::UserNotFound(id) => write!(f, "user {} not found", id),
+            EditError::NotLibrarian(id) => write!(f, "user {} is not a librarian", id),
+            EditError::BookExists(id) => write!(f, "book {} already exists", id),
+            EditError::BookNotFound(id) => write!(f, "book {} not found", id),
+            EditError::BookInUse(id) => {
+                write!(f, "book {} still has copies, loans or holds", id)
+            }
+            EditError::CopyNotFound(id) => write!(f, "copy {} not found", id),
+            EditError::CopyExists(id) => write!(f, "copy {} already exists", id),
+            EditError::CopyOnLoan(id) => write!(f, "copy {} is on loan", id),
+            EditError::Batch { index, error } => {
+                write!(f, "edit {} failed, batch rolled back: {}", index + 1, error)
+            }
+            EditError::NothingToUndo => write!(f, "nothing to undo"),
+            EditError::NothingToRedo => write!(f, "nothing to redo"),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+// One applied batch, as kept in the undo history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditBatch {
+    pub label: String,
+    pub librarian_id: u32,
+    // What to apply again on redo, in order.
+    pub edits: Vec<Edit>,
+    // What reverses the batch, in the order it must be applied.
+    pub inverse: Vec<Edit>,
+}
+
+impl Library {
+    // Applies every edit or none of them. On success the batch can be undone
+    // as a unit.
+    pub fn apply_batch(
+        &mut self,
+        librarian_id: u32,
+        label: &str,
+        edits: Vec<Edit>,
+    ) -> Result<(), EditError> {
+        self.require_editor(librarian_id)?;
+        let (edits, inverse) = self.apply_all(&edits)?;
+        self.push_history(EditBatch {
+            label: label.to_string(),
+            librarian_id,
+            edits,
+            inverse,
+        });
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    pub fn apply_edit(&mut self, librarian_id: u32, edit: Edit) -> Result<(), EditError> {
+        let label = edit.label();
+        self.apply_batch(librarian_id, &label, vec![edit])
+            .map_err(|e| match e {
+                EditError::Batch { error, .. } => *error,
+                other => other,
+            })
+    }
+
+    // Reverts the most recent batch. Returns its label.
+    pub fn undo(&mut self, librarian_id: u32) -> Result<String, EditError> {
+        self.require_editor(librarian_id)?;
+        let batch = self.undo_stack.pop().ok_or(EditError::NothingToUndo)?;
+        match self.apply_all(&batch.inverse) {
+            Ok(_) => {
+                let label = batch.label.clone();
+                self.redo_stack.push(batch);
+                Ok(label)
+            }
+            Err(e) => {
+                // Later changes made the batch impossible to revert.
+                self.undo_stack.push(batch);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn redo(&mut self, librarian_id: u32) -> Result<String, EditError> {
+        self.require_editor(librarian_id)?;
+        let batch = self.redo_stack.pop().ok_or(EditError::NothingToRedo)?;
+        match self.apply_all(&batch.edits) {
+            Ok((edits, inverse)) => {
+                let label = batch.label.clone();
+                self.push_history(EditBatch {
+                    edits,
+                    inverse,
+                    ..batch
+                });
+                Ok(label)
+            }
+            Err(e) => {
+                self.redo_stack.push(batch);
+                Err(e)
+            }
+        }
+    }
+
+    // Applied batches, oldest first.
+    pub fn edit_history(&self) -> &[EditBatch] {
+        &self.undo_stack
+    }
+
+    fn push_history(&mut self, batch: EditBatch) {
+        self.undo_stack.push(batch);
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    // Applies edits in order, rolling back the ones already applied if one
+    // fails. Returns the edits as applied and their inverse.
+    fn apply_all(&mut self, edits: &[Edit]) -> Result<(Vec<Edit>, Vec<Edit>), EditError> {
+        let mut applied = Vec::with_capacity(edits.len());
+        let mut inverse = Vec::with_capacity(edits.len());
+        for (index, edit) in edits.iter().enumerate() {
+            match self.apply_one(edit) {
+                Ok((done, undo)) => {
+                    applied.push(done);
+                    inverse.push(undo);
+                }
+                Err(error) => {
+                    for undo in inverse.iter().rev() {
+                        // Each inverse restores a state that held a moment ago.
+                        let _ = self.apply_one(undo);
+                    }
+                    return Err(EditError::Batch {
+                        index,
+                        error: Box::new(error),
+                    });
+                }
+            }
+        }
+        inverse.reverse();
+        Ok((applied, inverse))
+    }
+
+    // Applies one edit and returns it as it should be replayed (new copies
+    // keep their id) together with the edit that reverses it.
+    fn apply_one(&mut self, edit: &Edit) -> Result<(Edit, Edit), EditError> {
+        let inverse = match edit {
+            Edit::AddBook(book) => {
+                if self.books.contains_key(&book.id) {
+                    return Err(EditError::BookExists(book.id));
+                }
+                self.books.insert(book.id, book.clone());
+                Edit::RemoveBook(book.id)
+            }
+            Edit::UpdateBook(book) => {
+                let old = self
+                    .books
+                    .get_mut(&book.id)
+                    .ok_or(EditError::BookNotFound(book.id))?;
+                let previous = std::mem::replace(old, book.clone());
+                // Availability follows circulation, not the edit.
+                old.is_available = previous.is_available;
+                Edit::UpdateBook(previous)
+            }
+            Edit::RemoveBook(id) => {
+                if !self.books.contains_key(id) {
+                    return Err(EditError::BookNotFound(*id));
+                }
+                let in_use = self.copies.values().any(|c| c.book_id == *id)
+                    || self.loans.values().any(|l| l.book_id == *id)
+                    || self.holds.iter().any(|h| h.book_id == *id);
+                if in_use {
+                    return Err(EditError::BookInUse(*id));
+                }
+                Edit::AddBook(self.books.remove(id).expect("checked above"))
+            }
+            Edit::AddCopy { book_id, location } => {
+                let copy_id = self
+                    .add_copy(*book_id, location)
+                    .ok_or(EditError::BookNotFound(*book_id))?;
+                let copy = self.copies[&copy_id].clone();
+                return Ok((Edit::RestoreCopy(copy), Edit::RemoveCopy(copy_id)));
+            }
+            Edit::RemoveCopy(id) => {
+                if self.is_copy_on_loan(*id) {
+                    return Err(EditError::CopyOnLoan(*id));
+                }
+                let copy = self.copies.remove(id).ok_or(EditError::CopyNotFound(*id))?;
+                self.refresh_availability(copy.book_id);
+                Edit::RestoreCopy(copy)
+            }
+            Edit::RestoreCopy(copy) => {
+                if self.copies.contains_key(&copy.id) {
+                    return Err(EditError::CopyExists(copy.id));
+                }
+                if !self.books.contains_key(&copy.book_id) {
+                    return Err(EditError::BookNotFound(copy.book_id));
+                }
+                self.copies.insert(copy.id, copy.clone());
+                self.next_copy_id = self.next_copy_id.max(copy.id + 1);
+                self.refresh_availability(copy.book_id);
+                Edit::RemoveCopy(copy.id)
+            }
+            Edit::MoveCopy { copy_id, location } => {
+                let copy = self
+                    .copies
+                    .get_mut(copy_id)
+                    .ok_or(EditError::CopyNotFound(*copy_id))?;
+                let previous = std::mem::replace(&mut copy.location, location.clone());
+                Edit::MoveCopy {
+                    copy_id: *copy_id,
+                    location: previous,
+                }
+            }
+            Edit::SetCopyStatus { copy_id, status } => {
+                let copy = self
+                    .copies
+                    .get_mut(copy_id)
+                    .ok_or(EditError::CopyNotFound(*copy_id))?;
+                let previous = std::mem::replace(&mut copy.status, *status);
+                let book_id = copy.book_id;
+                self.refresh_availability(book_id);
+                Edit::SetCopyStatus {
+                    copy_id: *copy_id,
+                    status: previous,
+                }
+            }
+            Edit::SetUserBlocked { user_id, blocked } => {
+                let user = self
+                    .users
+                    .get_mut(user_id)
+                    .ok_or(EditError::UserNotFound(*user_id))?;
+                let previous = std::mem::replace(&mut user.is_blocked, *blocked);
+                Edit::SetUserBlocked {
+                    user_id: *user_id,
+                    blocked: previous,
+                }
+            }
+        };
+        Ok((edit.clone(), inverse))
+    }
+
+    pub(crate) fn refresh_availability(&mut self, book_id: u32) {
+        let available = if self.copies.values().any(|c| c.book_id == book_id) {
+            self.copy_on_shelf(book_id).is_some()
+        } else {
+            !self
+                .loans
+                .values()
+                .any(|l| l.book_id == book_id && l.is_active())
+        };
+        if let Some(book) = self.books.get_mut(&book_id) {
+            book.is_available = available;
+        }
+    }
+
+    fn require_editor(&self, user_id: u32) -> Result<(), EditError> {
+        let user = self
+            .users
+            .get(&user_id)
+            .ok_or(EditError::UserNotFound(user_id))?;
+        if Auth::check_librarian(user) {
+            Ok(())
+        } else {
+            Err(EditError::NotLibrarian(user_id))
+        }
+    }
+}
+
+impl Edit {
+    // A short description for the history list.
+    pub fn label(&self) -> String {
+        match self {
+            Edit::AddBook(book) => format!("add book {}", book.id),
+            Edit::UpdateBook(book) => format!("update book {}", book.id),
+            Edit::RemoveBook(id) => format!("remove book {}", id),
+            Edit::AddCopy { book_id, .. } => format!("add copy of book {}", book_id),
+            Edit::RemoveCopy(id) => format!("remove copy {}", id),
+            Edit::RestoreCopy(copy) => format!("restore copy {}", copy.id),
+            Edit::MoveCopy { copy_id, location } => {
+                format!("move copy {} to {}", copy_id, location)
+            }
+            Edit::SetCopyStatus { copy_id, status } => {
+                format!("mark copy {} {:?}", copy_id, status)
+            }
+            Edit::SetUserBlocked { user_id, blocked } => {
+                let action = if *blocked { "block" } else { "unblock" };
+                format!("{} user {}", action, user_id)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+
+    const LIBRARIAN: u32 = 1;
+
+    fn setup() -> Library {
+        let mut library = Library::new();
+        let mut librarian = User::new(LIBRARIAN, "carol");
+        librarian.is_librarian = true;
+        library.register_user(librarian);
+        library.register_user(User::new(2, "alice"));
+        for id in 1..=3 {
+            library.add_book(Book::new(id, &format!("Untitled {}", id), "Unknown"));
+        }
+        library
+    }
+
+    fn retitled(library: &Library, id: u32, title: &str) -> Edit {
+        let mut book = library.get_book(id).unwrap().clone();
+        book.title = title.to_string();
+        Edit::UpdateBook(book)
+    }
+
+    fn titles(library: &Library) -> Vec<String> {
+        (1..=3)
+            .map(|id| library.get_book(id).unwrap().title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_failed_batch_changes_nothing() {
+        let mut library = setup();
+        let mut missing = library.get_book(1).unwrap().clone();
+        missing.id = 99;
+        let edits = vec![
+            retitled(&library, 1, "Emma"),
+            Edit::AddCopy {
+                book_id: 1,
+                location: "Main".to_string(),
+            },
+            retitled(&library, 2, "Persuasion"),
+            Edit::UpdateBook(missing),
+        ];
+        let result = library.apply_batch(LIBRARIAN, "retitle Austen", edits);
+        assert_eq!(
+            result,
+            Err(EditError::Batch {
+                index: 3,
+                error: Box::new(EditError::BookNotFound(99)),
+            })
+        );
+        assert_eq!(titles(&library), ["Untitled 1", "Untitled 2", "Untitled 3"]);
+        assert!(library.copies_of(1).is_empty());
+        assert!(library.edit_history().is_empty());
+    }
+
+    #[test]
+    fn test_undo_and_redo_batches() {
+        let mut library = setup();
+        let edits = vec![
+            retitled(&library, 1, "Emma"),
+            retitled(&library, 2, "Persuasion"),
+            Edit::AddCopy {
+                book_id: 2,
+                location: "Main/Fiction".to_string(),
+            },
+        ];
+        library.apply_batch(LIBRARIAN, "Austen", edits).unwrap();
+        library
+            .apply_edit(
+                LIBRARIAN,
+                Edit::SetUserBlocked {
+                    user_id: 2,
+                    blocked: true,
+                },
+            )
+            .unwrap();
+        let barcode = library.copies_of(2)[0].barcode.clone();
+
+        assert_eq!(library.undo(LIBRARIAN), Ok("block user 2".to_string()));
+        assert!(!library.get_user(2).unwrap().is_blocked);
+        assert_eq!(library.undo(LIBRARIAN), Ok("Austen".to_string()));
+        assert_eq!(titles(&library), ["Untitled 1", "Untitled 2", "Untitled 3"]);
+        assert!(library.copies_of(2).is_empty());
+        assert_eq!(library.undo(LIBRARIAN), Err(EditError::NothingToUndo));
+
+        // Redo brings back the same copy, not a new one.
+        assert_eq!(library.redo(LIBRARIAN), Ok("Austen".to_string()));
+        assert_eq!(titles(&library), ["Emma", "Persuasion", "Untitled 3"]);
+        assert_eq!(library.copies_of(2)[0].barcode, barcode);
+
+        // A new edit discards what was left to redo.
+        library
+            .apply_edit(LIBRARIAN, retitled(&library, 3, "Sanditon"))
+            .unwrap();
+        assert_eq!(library.redo(LIBRARIAN), Err(EditError::NothingToRedo));
+        assert_eq!(library.edit_history().len(), 2);
+    }
+
+    #[test]
+    fn test_undo_refused_when_state_moved_on() {
+        let mut library = setup();
+        assert_eq!(
+            library.apply_edit(2, Edit::RemoveBook(3)),
+            Err(EditError::NotLibrarian(2))
+        );
+        library
+            .apply_edit(
+                LIBRARIAN,
+                Edit::AddBook(Book::new(4, "Lady Susan", "Jane Austen")),
+            )
+            .unwrap();
+        library.checkout(4, 2).unwrap();
+        // Removing the book again would orphan the loan.
+        assert_eq!(
+            library.undo(LIBRARIAN),
+            Err(EditError::Batch {
+                index: 0,
+                error: Box::new(EditError::BookInUse(4)),
+            })
+        );
+        assert!(library.get_book(4).is_some());
+        assert_eq!(library.edit_history().len(), 1);
+    }
+}